@@ -6,7 +6,7 @@ use criterion::black_box;
 use criterion::Criterion;
 
 use crust::cpp;
-use std::{error, fs, path::PathBuf};
+use std::{error, fs, path::PathBuf, thread};
 
 fn criterion_benchmark(c: &mut Criterion) -> Result<(), Box<dyn error::Error>> {
     // TODO: change this to iter through every c file under "test/valid/cpp"
@@ -26,10 +26,33 @@ fn criterion_benchmark(c: &mut Criterion) -> Result<(), Box<dyn error::Error>> {
                 cpp::cpp_driver(
                     black_box(fs::read_to_string(input_file).unwrap()),
                     PathBuf::from(input_file),
+                    &cpp::CppOptions::default(),
                 )
             })
         });
     }
+    // each thread runs its own preprocessor, nothing is shared between them
+    c.bench_function("cpp all files in parallel", move |b| {
+        b.iter(|| {
+            let handles: Vec<_> = input_files
+                .iter()
+                .map(|input_file| {
+                    thread::spawn(move || {
+                        cpp::Preprocessor::new(&cpp::CppOptions::default())
+                            .preprocess(
+                                black_box(fs::read_to_string(input_file).unwrap()),
+                                PathBuf::from(input_file),
+                            )
+                            .unwrap()
+                            .text
+                    })
+                })
+                .collect();
+            for handle in handles {
+                handle.join().unwrap();
+            }
+        })
+    });
     return Ok(());
 }
 
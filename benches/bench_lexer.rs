@@ -140,11 +140,36 @@ fn criterion_benchmark(c: &mut Criterion) -> Result<(), Box<dyn error::Error>> {
         "test/valid/if_nested_3.c",
         "test/valid/eq_false.c",
     ];
+    // both lexers are timed on the input read before, with one interner
     for input_file in input_files.iter() {
         c.bench_function(&format!("lex {}", input_file), move |b| {
-            b.iter(|| lexer::lex(black_box(&fs::read_to_string(input_file).unwrap())))
+            let input = fs::read_to_string(input_file).unwrap();
+            let mut interner = lexer::Interner::new();
+            b.iter(|| lexer::lex(black_box(&input), &mut interner))
         });
+        c.bench_function(&format!("stream {}", input_file), move |b| {
+            let input = fs::read_to_string(input_file).unwrap();
+            let mut interner = lexer::Interner::new();
+            b.iter(|| lexer::TokenStream::new(black_box(input.as_bytes()), &mut interner).count())
+        });
+    }
+
+    // all the files above, repeated to about 50k lines
+    let mut large = String::new();
+    while large.lines().count() < 50_000 {
+        for input_file in input_files.iter() {
+            large.push_str(&fs::read_to_string(input_file)?);
+        }
     }
+    let large_input = large.clone();
+    c.bench_function("lex large input", move |b| {
+        let mut interner = lexer::Interner::new();
+        b.iter(|| lexer::lex(black_box(&large_input), &mut interner))
+    });
+    c.bench_function("stream large input", move |b| {
+        let mut interner = lexer::Interner::new();
+        b.iter(|| lexer::TokenStream::new(black_box(large.as_bytes()), &mut interner).count())
+    });
     return Ok(());
 }
 
@@ -4,6 +4,7 @@ extern crate criterion;
 
 use criterion::black_box;
 use criterion::Criterion;
+use criterion::ParameterizedBenchmark;
 
 use crust::{cpp, lexer, parser};
 use std::{error, fs, path::PathBuf};
@@ -141,18 +142,62 @@ fn criterion_benchmark(c: &mut Criterion) -> Result<(), Box<dyn error::Error>> {
         "test/valid/eq_false.c",
     ];
     for input_file in input_files.iter() {
-        let input_file_contents = fs::read_to_string(input_file.clone())?;
+        let input_file_contents = fs::read_to_string(input_file)?;
         // 1. Preprocessing
-        let contents_after_cpp =
-            cpp::cpp_driver(input_file_contents, PathBuf::from(input_file.clone()))?;
+        let contents_after_cpp = cpp::cpp_driver(
+            input_file_contents,
+            PathBuf::from(input_file),
+            &cpp::CppOptions::default(),
+        )?
+        .text;
         // 2. lexing
-        let tokens = lexer::lex(&contents_after_cpp)?;
-        let name = input_file.clone();
+        let mut interner = lexer::Interner::new();
+        let tokens = lexer::lex(&contents_after_cpp, &mut interner)?;
+        let name = input_file.to_string();
 
         c.bench_function(&format!("parse {}", input_file), move |b| {
-            b.iter(|| parser::parser_driver(black_box(&tokens), &name))
+            b.iter(|| {
+                parser::parser_driver(&contents_after_cpp, black_box(&tokens), &interner, &name)
+            })
         });
     }
+
+    // generated expressions, the time should grow linearly with their size
+    c.bench(
+        "parse operators",
+        ParameterizedBenchmark::new(
+            "parse operators",
+            |b, &operators| {
+                let operands = vec!["a"; operators + 1];
+                let input = format!("int f(int a) {{ return {}; }}\n", operands.join(" + "));
+                let mut interner = lexer::Interner::new();
+                let tokens = lexer::lex(&input, &mut interner).unwrap();
+                b.iter(|| {
+                    parser::parser_driver(&input, black_box(&tokens), &interner, "operators.c")
+                })
+            },
+            vec![2_500, 5_000, 10_000],
+        )
+        .sample_size(10),
+    );
+    c.bench(
+        "parse nesting",
+        ParameterizedBenchmark::new(
+            "parse nesting",
+            |b, &depth| {
+                let input = format!(
+                    "int f(int a) {{ return {}a{}; }}\n",
+                    "(a + ".repeat(depth),
+                    ")".repeat(depth)
+                );
+                let mut interner = lexer::Interner::new();
+                let tokens = lexer::lex(&input, &mut interner).unwrap();
+                b.iter(|| parser::parser_driver(&input, black_box(&tokens), &interner, "nesting.c"))
+            },
+            vec![50, 100, 200],
+        )
+        .sample_size(10),
+    );
     return Ok(());
 }
 
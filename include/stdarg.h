@@ -0,0 +1,22 @@
+/* stdarg.h: variable arguments, see C11 7.16 */
+#ifndef __CRUST_STDARG_H
+#define __CRUST_STDARG_H
+
+/* the va_list of the x86_64 System V ABI */
+typedef struct {
+    unsigned int gp_offset;
+    unsigned int fp_offset;
+    void *overflow_arg_area;
+    void *reg_save_area;
+} __builtin_va_list[1];
+
+typedef __builtin_va_list va_list;
+/* used by the glibc headers */
+typedef __builtin_va_list __gnuc_va_list;
+
+#define va_start(ap, param) __builtin_va_start(ap, param)
+#define va_arg(ap, type) __builtin_va_arg(ap, type)
+#define va_copy(dest, src) __builtin_va_copy(dest, src)
+#define va_end(ap) __builtin_va_end(ap)
+
+#endif
@@ -0,0 +1,10 @@
+/* stdbool.h: boolean type and values, see C11 7.18 */
+#ifndef __CRUST_STDBOOL_H
+#define __CRUST_STDBOOL_H
+
+#define bool _Bool
+#define true 1
+#define false 0
+#define __bool_true_false_are_defined 1
+
+#endif
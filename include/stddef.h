@@ -0,0 +1,18 @@
+/* stddef.h: common definitions, see C11 7.19 */
+#ifndef __CRUST_STDDEF_H
+#define __CRUST_STDDEF_H
+
+typedef __SIZE_TYPE__ size_t;
+typedef __PTRDIFF_TYPE__ ptrdiff_t;
+typedef __WCHAR_TYPE__ wchar_t;
+
+typedef struct {
+    long long __max_align_ll;
+    long double __max_align_ld;
+} max_align_t;
+
+#define NULL ((void *)0)
+
+#define offsetof(type, member) ((size_t)&((type *)0)->member)
+
+#endif
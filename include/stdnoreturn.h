@@ -0,0 +1,7 @@
+/* stdnoreturn.h: _Noreturn, see C11 7.23 */
+#ifndef __CRUST_STDNORETURN_H
+#define __CRUST_STDNORETURN_H
+
+#define noreturn _Noreturn
+
+#endif
@@ -87,6 +87,7 @@ pub enum NodeType {
     ExternalDeclaration,
     FunctionDefinition,
     DeclarationList,
+    Pragma(String), // #pragma not handled by the preprocessor
 }
 #[derive(PartialEq, Clone, Debug)]
 pub enum ConstantType {
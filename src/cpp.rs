@@ -16,36 +16,1054 @@
 // cpp.rs: Simple c preprocessor
 // -----------------------------------------------------------------------------
 
-use lazy_static::lazy_static;
-use log::{debug, error};
-use regex::Regex;
-use std::collections::HashMap;
+use crate::lexer::{self, is_identifier_char, is_identifier_start};
+use crate::symtable;
+use log::{debug, warn};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
 use std::path::PathBuf;
-use std::sync::Mutex;
-use std::{error, fs, path::Path};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{env, error, fs, path::Path};
 
 #[derive(Debug)]
 struct Macro {
-    replacement: String,
-    params: Vec<String>,
+    // None for an object-like macro, the variable arguments are the last parameter
+    params: Option<Vec<String>>,
+    variadic: bool,
+    replacement: Vec<PPToken>,
+    // the dynamic macros have no replacement list, they are computed where they are expanded
+    builtin: Option<Builtin>,
+    // the position of the name in `#define`, None for the predefined macros
+    defined_at: Option<SourceLoc>,
 }
-lazy_static! {
-    static ref MACROS: Mutex<HashMap<String, Macro>> = {
-        let m = HashMap::new();
-        Mutex::new(m)
-    };
+
+impl Macro {
+    // two definitions of the same macro must be identical, see C11 6.10.3.2
+    fn is_same(&self, other: &Macro) -> bool {
+        self.params == other.params
+            && self.variadic == other.variadic
+            && self.builtin == other.builtin
+            && self.replacement.len() == other.replacement.len()
+            && self
+                .replacement
+                .iter()
+                .zip(other.replacement.iter())
+                .enumerate()
+                .all(|(idx, (a, b))| {
+                    a.spelling == b.spelling && (idx == 0 || a.has_space == b.has_space)
+                })
+    }
+
+    // the definition as it is written after `#define`, e.g. `f(x,...) x + __VA_ARGS__`
+    fn definition(&self, name: &str) -> String {
+        let mut res = name.to_string();
+        if let Some(ref params) = self.params {
+            let mut params = params.clone();
+            if self.variadic {
+                let last = params.pop().unwrap();
+                params.push(if last == "__VA_ARGS__" {
+                    "...".to_string()
+                } else {
+                    last + "..."
+                });
+            }
+            res.push('(');
+            res.push_str(&params.join(","));
+            res.push(')');
+        }
+        for (idx, tok) in self.replacement.iter().enumerate() {
+            if idx == 0 || tok.has_space {
+                res.push(' ');
+            }
+            res.push_str(&tok.spelling);
+        }
+        res
+    }
+}
+
+// The macros whose value depends on where they are expanded, see C11 6.10.8.1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Builtin {
+    File,
+    Line,
+    Date,
+    Time,
+    Counter,
+}
+
+const BUILTIN_MACROS: [(&str, Builtin); 5] = [
+    ("__FILE__", Builtin::File),
+    ("__LINE__", Builtin::Line),
+    ("__DATE__", Builtin::Date),
+    ("__TIME__", Builtin::Time),
+    ("__COUNTER__", Builtin::Counter),
+];
+
+// The target of the compilation, some of the predefined macros depend on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    X86_64Linux,
+}
+
+impl Target {
+    // the standard system directories, searched after `-I` and `-isystem`
+    fn std_include_dirs(self) -> Vec<PathBuf> {
+        match self {
+            Target::X86_64Linux => vec![
+                PathBuf::from("/usr/local/include"),
+                PathBuf::from("/usr/include/x86_64-linux-gnu"),
+                PathBuf::from("/usr/include"),
+            ],
+        }
+    }
+}
+
+// crust only generates code for x86_64 linux now
+const TARGET: Target = Target::X86_64Linux;
+
+// The freestanding headers shipped with crust, see C11 4p6. They are in the
+// virtual directory `BUNDLED_DIR`, which is searched before the other directories
+// of `#include <...>`, so no header of the host toolchain is needed.
+const BUNDLED_DIR: &str = "<crust>";
+const BUNDLED_HEADERS: [(&str, &str); 9] = [
+    ("float.h", include_str!("../include/float.h")),
+    ("iso646.h", include_str!("../include/iso646.h")),
+    ("limits.h", include_str!("../include/limits.h")),
+    ("stdalign.h", include_str!("../include/stdalign.h")),
+    ("stdarg.h", include_str!("../include/stdarg.h")),
+    ("stdbool.h", include_str!("../include/stdbool.h")),
+    ("stddef.h", include_str!("../include/stddef.h")),
+    ("stdint.h", include_str!("../include/stdint.h")),
+    ("stdnoreturn.h", include_str!("../include/stdnoreturn.h")),
+];
+
+fn bundled_header(path: &Path) -> Option<&'static str> {
+    let name = path.strip_prefix(BUNDLED_DIR).ok()?;
+    BUNDLED_HEADERS
+        .iter()
+        .find(|(header, _)| Path::new(header) == name)
+        .map(|(_, contents)| *contents)
+}
+
+// The command line options of the preprocessor.
+#[derive(Debug, Clone)]
+pub struct CppOptions {
+    // -iquote, only searched for `#include "..."`
+    pub quote_dirs: Vec<PathBuf>,
+    // -I
+    pub bracket_dirs: Vec<PathBuf>,
+    // -isystem
+    pub system_dirs: Vec<PathBuf>,
+    // -nostdinc, do not search the standard system directories and the bundled headers
+    pub no_std_inc: bool,
+    // -D and -U, in the order of the command line
+    pub macros: Vec<MacroOption>,
+    // -imacros, only the macros of these files are kept
+    pub imacros: Vec<String>,
+    // -include, included before the first line of the source file
+    pub includes: Vec<String>,
+    // -fmax-include-depth, how deep `#include` can be nested
+    pub max_include_depth: usize,
+}
+
+impl Default for CppOptions {
+    fn default() -> CppOptions {
+        CppOptions {
+            quote_dirs: Vec::new(),
+            bracket_dirs: Vec::new(),
+            system_dirs: Vec::new(),
+            no_std_inc: false,
+            macros: Vec::new(),
+            imacros: Vec::new(),
+            includes: Vec::new(),
+            // the same limit as GCC
+            max_include_depth: 200,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum MacroOption {
+    // `-D name` defines it as 1, `-D name=value` as value
+    Define(String),
+    Undef(String),
+}
+
+// the name of the source text made of the command line options, like GCC
+const COMMAND_LINE: &str = "<command-line>";
+
+// the directives for the command line options, -D and -U in order, then all the
+// -imacros, see "Preprocessor Options" of the GCC manual
+fn command_line_directives(opts: &CppOptions) -> String {
+    let mut res = String::new();
+    for option in opts.macros.iter() {
+        match option {
+            MacroOption::Define(def) => match def.find('=') {
+                Some(idx) => {
+                    res.push_str(&format!("#define {} {}\n", &def[..idx], &def[idx + 1..]))
+                }
+                None => res.push_str(&format!("#define {} 1\n", def)),
+            },
+            MacroOption::Undef(name) => res.push_str(&format!("#undef {}\n", name)),
+        }
+    }
+    for file in opts.imacros.iter() {
+        res.push_str(&format!("#include \"{}\"\n", file));
+    }
+    res
+}
+
+// the macros defined before the first line of every translation unit,
+// see C11 6.10.8 and "Common Predefined Macros" of the GCC manual
+fn predefined_macros(target: Target) -> Vec<(&'static str, String)> {
+    let mut defs = vec![
+        ("__STDC__", "1".to_string()),
+        ("__STDC_VERSION__", "201112L".to_string()),
+        ("__STDC_HOSTED__", "1".to_string()),
+        ("__STDC_UTF_16__", "1".to_string()),
+        ("__STDC_UTF_32__", "1".to_string()),
+        ("__STDC_NO_ATOMICS__", "1".to_string()),
+        ("__STDC_NO_COMPLEX__", "1".to_string()),
+        ("__STDC_NO_THREADS__", "1".to_string()),
+        ("__STDC_NO_VLA__", "1".to_string()),
+        ("__crust__", "1".to_string()),
+        ("__CHAR_BIT__", "8".to_string()),
+        ("__ORDER_LITTLE_ENDIAN__", "1234".to_string()),
+        ("__ORDER_BIG_ENDIAN__", "4321".to_string()),
+    ];
+    match target {
+        Target::X86_64Linux => defs.extend(vec![
+            ("__x86_64__", "1".to_string()),
+            ("__x86_64", "1".to_string()),
+            ("__amd64__", "1".to_string()),
+            ("__amd64", "1".to_string()),
+            ("__linux__", "1".to_string()),
+            ("__linux", "1".to_string()),
+            ("__gnu_linux__", "1".to_string()),
+            ("__unix__", "1".to_string()),
+            ("__unix", "1".to_string()),
+            ("__ELF__", "1".to_string()),
+            ("__LP64__", "1".to_string()),
+            ("_LP64", "1".to_string()),
+            ("__BYTE_ORDER__", "__ORDER_LITTLE_ENDIAN__".to_string()),
+            ("__SIZEOF_SHORT__", symtable::X86_64_SHORT_BYTES.to_string()),
+            ("__SIZEOF_INT__", symtable::X86_64_INT_BYTES.to_string()),
+            ("__SIZEOF_LONG__", symtable::X86_64_LONG_BYTES.to_string()),
+            (
+                "__SIZEOF_LONG_LONG__",
+                symtable::X86_64_LONG_LONG_BYTES.to_string(),
+            ),
+            (
+                "__SIZEOF_POINTER__",
+                symtable::X86_64_POINTER_BYTES.to_string(),
+            ),
+            (
+                "__SIZEOF_SIZE_T__",
+                symtable::X86_64_POINTER_BYTES.to_string(),
+            ),
+            (
+                "__SIZEOF_PTRDIFF_T__",
+                symtable::X86_64_POINTER_BYTES.to_string(),
+            ),
+            ("__SIZEOF_WCHAR_T__", symtable::X86_64_INT_BYTES.to_string()),
+            ("__SIZEOF_WINT_T__", symtable::X86_64_INT_BYTES.to_string()),
+            ("__SIZEOF_FLOAT__", "4".to_string()),
+            ("__SIZEOF_DOUBLE__", "8".to_string()),
+            ("__SIZEOF_LONG_DOUBLE__", "16".to_string()),
+            (
+                "__SCHAR_MAX__",
+                int_max(symtable::X86_64_CHAR_BYTES, true, ""),
+            ),
+            (
+                "__SHRT_MAX__",
+                int_max(symtable::X86_64_SHORT_BYTES, true, ""),
+            ),
+            ("__INT_MAX__", int_max(symtable::X86_64_INT_BYTES, true, "")),
+            (
+                "__LONG_MAX__",
+                int_max(symtable::X86_64_LONG_BYTES, true, "L"),
+            ),
+            (
+                "__LONG_LONG_MAX__",
+                int_max(symtable::X86_64_LONG_LONG_BYTES, true, "LL"),
+            ),
+            (
+                "__WCHAR_MAX__",
+                int_max(symtable::X86_64_INT_BYTES, true, ""),
+            ),
+            ("__WCHAR_MIN__", "(-__WCHAR_MAX__ - 1)".to_string()),
+            (
+                "__WINT_MAX__",
+                int_max(symtable::X86_64_INT_BYTES, false, "U"),
+            ),
+            ("__WINT_MIN__", "0U".to_string()),
+            (
+                "__PTRDIFF_MAX__",
+                int_max(symtable::X86_64_POINTER_BYTES, true, "L"),
+            ),
+            (
+                "__SIZE_MAX__",
+                int_max(symtable::X86_64_POINTER_BYTES, false, "UL"),
+            ),
+            (
+                "__SIG_ATOMIC_MAX__",
+                int_max(symtable::X86_64_INT_BYTES, true, ""),
+            ),
+            (
+                "__SIG_ATOMIC_MIN__",
+                "(-__SIG_ATOMIC_MAX__ - 1)".to_string(),
+            ),
+            ("__SIZE_TYPE__", "long unsigned int".to_string()),
+            ("__PTRDIFF_TYPE__", "long int".to_string()),
+            ("__WCHAR_TYPE__", "int".to_string()),
+            ("__WINT_TYPE__", "unsigned int".to_string()),
+            ("__SIG_ATOMIC_TYPE__", "int".to_string()),
+            ("__INTMAX_TYPE__", "long int".to_string()),
+            ("__UINTMAX_TYPE__", "long unsigned int".to_string()),
+            (
+                "__INTMAX_MAX__",
+                int_max(symtable::X86_64_LONG_BYTES, true, "L"),
+            ),
+            (
+                "__UINTMAX_MAX__",
+                int_max(symtable::X86_64_LONG_BYTES, false, "UL"),
+            ),
+            ("__INTMAX_C(c)", "c ## L".to_string()),
+            ("__UINTMAX_C(c)", "c ## UL".to_string()),
+            ("__INT8_TYPE__", "signed char".to_string()),
+            ("__UINT8_TYPE__", "unsigned char".to_string()),
+            (
+                "__INT8_MAX__",
+                int_max(symtable::X86_64_CHAR_BYTES, true, ""),
+            ),
+            (
+                "__UINT8_MAX__",
+                int_max(symtable::X86_64_CHAR_BYTES, false, ""),
+            ),
+            ("__INT8_C(c)", "c".to_string()),
+            ("__UINT8_C(c)", "c".to_string()),
+            ("__INT16_TYPE__", "short int".to_string()),
+            ("__UINT16_TYPE__", "short unsigned int".to_string()),
+            (
+                "__INT16_MAX__",
+                int_max(symtable::X86_64_SHORT_BYTES, true, ""),
+            ),
+            (
+                "__UINT16_MAX__",
+                int_max(symtable::X86_64_SHORT_BYTES, false, ""),
+            ),
+            ("__INT16_C(c)", "c".to_string()),
+            ("__UINT16_C(c)", "c".to_string()),
+            ("__INT32_TYPE__", "int".to_string()),
+            ("__UINT32_TYPE__", "unsigned int".to_string()),
+            (
+                "__INT32_MAX__",
+                int_max(symtable::X86_64_INT_BYTES, true, ""),
+            ),
+            (
+                "__UINT32_MAX__",
+                int_max(symtable::X86_64_INT_BYTES, false, "U"),
+            ),
+            ("__INT32_C(c)", "c".to_string()),
+            ("__UINT32_C(c)", "c ## U".to_string()),
+            ("__INT64_TYPE__", "long int".to_string()),
+            ("__UINT64_TYPE__", "long unsigned int".to_string()),
+            (
+                "__INT64_MAX__",
+                int_max(symtable::X86_64_LONG_BYTES, true, "L"),
+            ),
+            (
+                "__UINT64_MAX__",
+                int_max(symtable::X86_64_LONG_BYTES, false, "UL"),
+            ),
+            ("__INT64_C(c)", "c ## L".to_string()),
+            ("__UINT64_C(c)", "c ## UL".to_string()),
+            ("__INT_LEAST8_TYPE__", "signed char".to_string()),
+            ("__UINT_LEAST8_TYPE__", "unsigned char".to_string()),
+            (
+                "__INT_LEAST8_MAX__",
+                int_max(symtable::X86_64_CHAR_BYTES, true, ""),
+            ),
+            (
+                "__UINT_LEAST8_MAX__",
+                int_max(symtable::X86_64_CHAR_BYTES, false, ""),
+            ),
+            ("__INT_LEAST16_TYPE__", "short int".to_string()),
+            ("__UINT_LEAST16_TYPE__", "short unsigned int".to_string()),
+            (
+                "__INT_LEAST16_MAX__",
+                int_max(symtable::X86_64_SHORT_BYTES, true, ""),
+            ),
+            (
+                "__UINT_LEAST16_MAX__",
+                int_max(symtable::X86_64_SHORT_BYTES, false, ""),
+            ),
+            ("__INT_LEAST32_TYPE__", "int".to_string()),
+            ("__UINT_LEAST32_TYPE__", "unsigned int".to_string()),
+            (
+                "__INT_LEAST32_MAX__",
+                int_max(symtable::X86_64_INT_BYTES, true, ""),
+            ),
+            (
+                "__UINT_LEAST32_MAX__",
+                int_max(symtable::X86_64_INT_BYTES, false, "U"),
+            ),
+            ("__INT_LEAST64_TYPE__", "long int".to_string()),
+            ("__UINT_LEAST64_TYPE__", "long unsigned int".to_string()),
+            (
+                "__INT_LEAST64_MAX__",
+                int_max(symtable::X86_64_LONG_BYTES, true, "L"),
+            ),
+            (
+                "__UINT_LEAST64_MAX__",
+                int_max(symtable::X86_64_LONG_BYTES, false, "UL"),
+            ),
+            ("__INT_FAST8_TYPE__", "signed char".to_string()),
+            ("__UINT_FAST8_TYPE__", "unsigned char".to_string()),
+            (
+                "__INT_FAST8_MAX__",
+                int_max(symtable::X86_64_CHAR_BYTES, true, ""),
+            ),
+            (
+                "__UINT_FAST8_MAX__",
+                int_max(symtable::X86_64_CHAR_BYTES, false, ""),
+            ),
+            ("__INT_FAST16_TYPE__", "long int".to_string()),
+            ("__UINT_FAST16_TYPE__", "long unsigned int".to_string()),
+            (
+                "__INT_FAST16_MAX__",
+                int_max(symtable::X86_64_LONG_BYTES, true, "L"),
+            ),
+            (
+                "__UINT_FAST16_MAX__",
+                int_max(symtable::X86_64_LONG_BYTES, false, "UL"),
+            ),
+            ("__INT_FAST32_TYPE__", "long int".to_string()),
+            ("__UINT_FAST32_TYPE__", "long unsigned int".to_string()),
+            (
+                "__INT_FAST32_MAX__",
+                int_max(symtable::X86_64_LONG_BYTES, true, "L"),
+            ),
+            (
+                "__UINT_FAST32_MAX__",
+                int_max(symtable::X86_64_LONG_BYTES, false, "UL"),
+            ),
+            ("__INT_FAST64_TYPE__", "long int".to_string()),
+            ("__UINT_FAST64_TYPE__", "long unsigned int".to_string()),
+            (
+                "__INT_FAST64_MAX__",
+                int_max(symtable::X86_64_LONG_BYTES, true, "L"),
+            ),
+            (
+                "__UINT_FAST64_MAX__",
+                int_max(symtable::X86_64_LONG_BYTES, false, "UL"),
+            ),
+            ("__INTPTR_TYPE__", "long int".to_string()),
+            ("__UINTPTR_TYPE__", "long unsigned int".to_string()),
+            (
+                "__INTPTR_MAX__",
+                int_max(symtable::X86_64_POINTER_BYTES, true, "L"),
+            ),
+            (
+                "__UINTPTR_MAX__",
+                int_max(symtable::X86_64_POINTER_BYTES, false, "UL"),
+            ),
+        ]),
+    }
+    defs
+}
+
+// the largest value of an integer type of `bytes` bytes, with the suffix of the type
+fn int_max(bytes: u64, signed: bool, suffix: &str) -> String {
+    let bits = bytes * 8 - if signed { 1 } else { 0 };
+    format!("{}{}", u64::MAX >> (64 - bits), suffix)
+}
+
+// the macros defined before the first line of a translation unit
+fn seed_macros(macros: &mut HashMap<String, Macro>) {
+    for (name, value) in predefined_macros(TARGET) {
+        // the table above is always a valid definition
+        let (name, m) = parse_define(pp_tokenize(&format!("{} {}", name, value), 0)).unwrap();
+        macros.insert(name, m);
+    }
+    for &(name, builtin) in BUILTIN_MACROS.iter() {
+        let m = Macro {
+            params: None,
+            variadic: false,
+            replacement: Vec::new(),
+            builtin: Some(builtin),
+            defined_at: None,
+        };
+        macros.insert(name.to_string(), m);
+    }
+}
+
+// A position in the source, `file` is an index of the files of the `SourceMap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceLoc {
+    pub file: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+// One macro expansion: the name of the macro, where it was expanded, and the
+// expansion which produced the name if there is one.
+#[derive(Debug, Clone)]
+struct Expansion {
+    name: String,
+    loc: SourceLoc,
+    defined_at: Option<SourceLoc>,
+    parent: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct LineMarker {
+    // the marker is put before the line which starts at this offset of the output
+    offset: usize,
+    file: usize,
+    line: usize,
+    // 1: the start of an included file, 2: back in the including file
+    flag: Option<u8>,
+    system: bool,
+    // the file the lines are read from, and the physical number of the line
+    source: usize,
+    source_line: usize,
+}
+
+// Where the text produced by the preprocessor comes from. Every token of the output
+// is mapped to its position in the source, and the tokens produced by macro
+// expansions keep the chain of the expansions.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    // the presumed names of the files and the paths of the files which are read,
+    // `#line` can add new names
+    files: Vec<PathBuf>,
+    // (offset in the output, position, innermost expansion) of every token, sorted
+    tokens: Vec<(usize, SourceLoc, Option<usize>)>,
+    expansions: Vec<Expansion>,
+    markers: Vec<LineMarker>,
+}
+
+impl SourceMap {
+    fn file_id(&mut self, path: &Path) -> usize {
+        match self.files.iter().position(|f| f == path) {
+            Some(id) => id,
+            None => {
+                self.files.push(path.to_path_buf());
+                self.files.len() - 1
+            }
+        }
+    }
+
+    // the output with GCC style line markers, `# 12 "foo.h" 2`, like `gcc -E`
+    pub fn with_line_markers(&self, text: &str) -> String {
+        let mut res = String::new();
+        let mut last = 0;
+        for marker in self.markers.iter() {
+            res.push_str(&text[last..marker.offset]);
+            last = marker.offset;
+            res.push_str(&format!(
+                "# {} \"{}\"",
+                marker.line,
+                self.files[marker.file].display()
+            ));
+            if let Some(flag) = marker.flag {
+                res.push_str(&format!(" {}", flag));
+            }
+            if marker.system {
+                res.push_str(" 3");
+            }
+            res.push('\n');
+        }
+        res.push_str(&text[last..]);
+        res
+    }
+}
+
+// the positions for the diagnostics of the later stages
+#[allow(dead_code)]
+impl SourceMap {
+    fn entry(&self, offset: usize) -> Option<&(usize, SourceLoc, Option<usize>)> {
+        match self
+            .tokens
+            .binary_search_by_key(&offset, |&(off, _, _)| off)
+        {
+            Ok(idx) => self.tokens.get(idx),
+            Err(0) => None,
+            Err(idx) => self.tokens.get(idx - 1),
+        }
+    }
+
+    // `file:line:col` of a position
+    pub fn display(&self, loc: SourceLoc) -> String {
+        format!(
+            "{}:{}:{}",
+            self.files[loc.file].display(),
+            loc.line,
+            loc.col
+        )
+    }
+
+    // `file:line:col` of a located span
+    pub fn display_span(&self, span: &lexer::Span) -> String {
+        self.display(SourceLoc {
+            file: span.file,
+            line: span.line,
+            col: span.col,
+        })
+    }
+
+    // the source line of a located span and a caret under its column, like
+    //     int x = 1 int y;
+    //               ^
+    // the line is read from the file the preprocessor read, `#line` only changes the
+    // name and the line number which are shown
+    pub fn snippet(&self, span: &lexer::Span) -> Option<String> {
+        let idx = self.markers.partition_point(|m| m.offset <= span.offset);
+        let marker = self.markers.get(idx.checked_sub(1)?)?;
+        let path = self.files.get(marker.source)?;
+        let text = match bundled_header(path) {
+            Some(contents) => contents.to_string(),
+            None => fs::read_to_string(path).ok()?,
+        };
+        let line_no = (span.line + marker.source_line).checked_sub(marker.line)?;
+        let line = text.lines().nth(line_no.checked_sub(1)?)?;
+        // the tabs are kept, so the caret is under the token
+        let indent: String = line
+            .char_indices()
+            .take_while(|&(idx, _)| idx + 1 < span.col)
+            .map(|(_, c)| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        Some(format!("{}\n{}^", line, indent))
+    }
+
+    // the position in the source of the byte `offset` of the output
+    pub fn lookup(&self, offset: usize) -> Option<SourceLoc> {
+        let &(start, mut loc, expansion) = self.entry(offset)?;
+        if expansion.is_none() {
+            loc.col += offset - start;
+        }
+        Some(loc)
+    }
+
+    // the positions in the source of the tokens lexed from the output
+    pub fn locate(&self, toks: &mut [lexer::Token]) {
+        for tok in toks.iter_mut() {
+            self.locate_span(&mut tok.span);
+        }
+    }
+
+    pub fn locate_span(&self, span: &mut lexer::Span) {
+        if let Some(loc) = self.lookup(span.offset) {
+            span.file = loc.file;
+            span.line = loc.line;
+            span.col = loc.col;
+        }
+    }
+
+    // the macro expansions which produced the byte `offset` of the output, the innermost
+    // first, like "in expansion of macro `MAX` at foo.c:3:12, defined at foo.h:1:9"
+    pub fn expansion_backtrace(&self, offset: usize) -> Vec<String> {
+        let mut res = Vec::new();
+        let mut expansion = self.entry(offset).and_then(|&(_, _, expansion)| expansion);
+        while let Some(idx) = expansion {
+            let e = &self.expansions[idx];
+            let mut note = format!(
+                "in expansion of macro `{}` at {}",
+                e.name,
+                self.display(e.loc)
+            );
+            if let Some(defined_at) = e.defined_at {
+                note.push_str(&format!(", defined at {}", self.display(defined_at)));
+            }
+            res.push(note);
+            expansion = e.parent;
+        }
+        res
+    }
+}
+
+// The preprocessed translation unit.
+pub struct CppOutput {
+    pub text: String,
+    pub source_map: SourceMap,
+    // the source file and the headers it includes, in the order they are first
+    // included, and whether they are system headers. The bundled headers are not
+    // files, they are left out.
+    pub dependencies: Vec<(PathBuf, bool)>,
+}
+
+// The options of the Makefile rule, see `-M` of the GCC manual.
+#[derive(Debug, Clone, Default)]
+pub struct DepOptions {
+    // -MT, the targets of the rule
+    pub targets: Vec<String>,
+    // -MM leaves out the system headers
+    pub no_system: bool,
+    // -MP, a phony target for each header
+    pub phony: bool,
+}
+
+// escape the characters which are special to make in a file name
+pub fn make_quote(name: &str) -> String {
+    let mut res = String::new();
+    for c in name.chars() {
+        match c {
+            ' ' | '\t' | '#' => {
+                res.push('\\');
+                res.push(c);
+            }
+            '$' => res.push_str("$$"),
+            _ => res.push(c),
+        }
+    }
+    res
+}
+
+impl CppOutput {
+    // the Makefile rule of the files read, `targets: source.c header.h ...`
+    pub fn make_rule(&self, opts: &DepOptions) -> String {
+        // the lines are wrapped like GCC does
+        const MAX_COLUMN: usize = 75;
+        let deps: Vec<String> = self
+            .dependencies
+            .iter()
+            .filter(|(_, system)| !opts.no_system || !system)
+            .map(|(path, _)| make_quote(&path.display().to_string()))
+            .collect();
+        let mut res = String::new();
+        let mut column = 0;
+        for (idx, word) in opts.targets.iter().chain(deps.iter()).enumerate() {
+            if idx == opts.targets.len() {
+                res.push(':');
+                column += 1;
+            }
+            if idx > 0 {
+                if column + 1 + word.len() > MAX_COLUMN {
+                    res.push_str(" \\\n");
+                    column = 0;
+                }
+                res.push(' ');
+                column += 1;
+            }
+            res.push_str(word);
+            column += word.len();
+        }
+        res.push('\n');
+        if opts.phony {
+            // the source file itself is not a phony target
+            for dep in deps.iter().skip(1) {
+                res.push_str(&format!("\n{}:\n", dep));
+            }
+        }
+        res
+    }
+}
+
+// The preprocessor of one translation unit: the options, the macro table and the
+// state shared by all the files it includes. Nothing is shared between two
+// preprocessors, so each thread can run its own.
+#[derive(Debug)]
+pub struct Preprocessor {
+    opts: CppOptions,
+    macros: HashMap<String, Macro>,
+    // the directories searched for the headers: the `-iquote` directories, then
+    // from `bracket_start` the ones searched for `#include <...>`, which start
+    // with the bundled headers
+    search_dirs: Vec<PathBuf>,
+    bracket_start: usize,
+    // canonical paths of the files which contain `#pragma once`
+    once_files: HashSet<PathBuf>,
+    // canonical paths of the files whose whole contents are inside an include guard,
+    // and the macros of the guards, see `Guard`
+    guards: HashMap<PathBuf, String>,
+    // the files being included, the source file first: (canonical path, path)
+    include_stack: Vec<(PathBuf, PathBuf)>,
+    max_include_depth: usize,
+    // the next value of `__COUNTER__`
+    counter: Cell<u64>,
+    // the values of `__DATE__` and `__TIME__`, the same in the whole translation unit
+    date: String,
+    time: String,
+    // the index of the first `-isystem` directory in `search_dirs`, the headers found
+    // there, in the system directories or in the bundled ones are system headers
+    system_start: usize,
+    // the text of the whole translation unit, and where it comes from
+    output: String,
+    map: SourceMap,
+    // the macro expansions are recorded while the macros are being expanded, they are
+    // moved to `map` at the end
+    expansions: RefCell<Vec<Expansion>>,
+    // the files read, see `CppOutput`
+    dependencies: Vec<(PathBuf, bool)>,
+}
+
+impl Preprocessor {
+    pub fn new(opts: &CppOptions) -> Preprocessor {
+        // `SOURCE_DATE_EPOCH` gives reproducible builds, see
+        // https://reproducible-builds.org/specs/source-date-epoch/
+        let secs = match env::var("SOURCE_DATE_EPOCH") {
+            Ok(epoch) => epoch.trim().parse::<u64>().ok(),
+            Err(_) => None,
+        }
+        .unwrap_or_else(|| match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_secs(),
+            Err(_) => 0,
+        });
+        let (date, time) = date_time(secs);
+        let mut search_dirs = opts.quote_dirs.clone();
+        if !opts.no_std_inc {
+            search_dirs.push(PathBuf::from(BUNDLED_DIR));
+        }
+        search_dirs.extend(opts.bracket_dirs.iter().cloned());
+        let system_start = search_dirs.len();
+        search_dirs.extend(opts.system_dirs.iter().cloned());
+        if !opts.no_std_inc {
+            search_dirs.extend(TARGET.std_include_dirs());
+        }
+        let mut macros = HashMap::new();
+        seed_macros(&mut macros);
+        Preprocessor {
+            opts: opts.clone(),
+            macros,
+            search_dirs,
+            bracket_start: opts.quote_dirs.len(),
+            once_files: HashSet::new(),
+            guards: HashMap::new(),
+            include_stack: Vec::new(),
+            max_include_depth: opts.max_include_depth,
+            counter: Cell::new(0),
+            date,
+            time,
+            system_start,
+            output: String::new(),
+            map: SourceMap::default(),
+            expansions: RefCell::new(Vec::new()),
+            dependencies: Vec::new(),
+        }
+    }
+
+    fn is_system_header(&self, found_in: Option<usize>) -> bool {
+        match found_in {
+            Some(idx) => {
+                idx >= self.system_start || self.search_dirs[idx] == Path::new(BUNDLED_DIR)
+            }
+            None => false,
+        }
+    }
+
+    // the files being included from `include_stack[start]`, then `next`: `a.h -> b.h -> a.h`
+    fn include_chain(&self, start: usize, next: &Path) -> String {
+        let mut files: Vec<String> = self.include_stack[start..]
+            .iter()
+            .map(|(_, path)| path.display().to_string())
+            .collect();
+        files.push(next.display().to_string());
+        files.join(" -> ")
+    }
+
+    // GCC line marker before the next line of the output, see "Preprocessor Output"
+    // of the GCC manual
+    // the marker for the physical `line` of the file of `loc`
+    fn mark_line(&mut self, loc: &PresumedLoc, line: usize, flag: Option<u8>) {
+        self.map.markers.push(LineMarker {
+            offset: self.output.len(),
+            file: loc.file_id,
+            line: loc.line(line),
+            flag,
+            system: loc.system,
+            source: loc.source,
+            source_line: line,
+        });
+    }
+
+    // Find the header of `#include`, see "Search Path" of the GCC manual.
+    // `#include "..."` looks in the directory of the current file first, then in the
+    // `-iquote` directories, and both forms continue with `-I`, `-isystem` and the
+    // system directories. `#include_next` starts after `from`, the directory where the
+    // current file was found. Return the path, and the index of its directory in
+    // `search_dirs` if it was found there.
+    fn find_header(
+        &self,
+        name: &str,
+        quoted: bool,
+        current_file: &Path,
+        from: Option<usize>,
+    ) -> Option<(PathBuf, Option<usize>)> {
+        let name = Path::new(name);
+        if name.is_absolute() {
+            return if name.is_file() {
+                Some((name.to_path_buf(), None))
+            } else {
+                None
+            };
+        }
+        let start = match from {
+            Some(idx) => idx + 1,
+            None if quoted => {
+                let dir = current_file.parent().unwrap_or_else(|| Path::new(""));
+                let full_path = dir.join(name);
+                if full_path.is_file() {
+                    return Some((full_path, None));
+                }
+                0
+            }
+            None => self.bracket_start,
+        };
+        for (idx, dir) in self.search_dirs.iter().enumerate().skip(start) {
+            let full_path = dir.join(name);
+            if bundled_header(&full_path).is_some() || full_path.is_file() {
+                return Some((full_path, Some(idx)));
+            }
+        }
+        None
+    }
+}
+
+// `__DATE__` and `__TIME__` of a unix time, in UTC: ("Mmm dd yyyy", "hh:mm:ss")
+fn date_time(secs: u64) -> (String, String) {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let (days, rem) = (secs / 86400, secs % 86400);
+    // civil date from the number of days, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as u64;
+    (
+        format!("{} {:>2} {}", MONTHS[month as usize - 1], day, year),
+        format!("{:02}:{:02}:{:02}", rem / 3600, rem % 3600 / 60, rem % 60),
+    )
+}
+
+// What the macro expansion needs to know: the defined macros, and for the dynamic
+// macros the location in the current file and the state of the translation unit.
+struct ExpandEnv<'a> {
+    loc: &'a PresumedLoc,
+    ctx: &'a Preprocessor,
+}
+
+impl<'a> ExpandEnv<'a> {
+    fn builtin_value(&self, builtin: Builtin, tok: &PPToken) -> PPToken {
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let (kind, spelling) = match builtin {
+            Builtin::File => (
+                PPTokKind::StringLiteral,
+                quote(&self.loc.file.display().to_string()),
+            ),
+            Builtin::Line => (PPTokKind::Number, self.loc.line(tok.line).to_string()),
+            Builtin::Date => (PPTokKind::StringLiteral, quote(&self.ctx.date)),
+            Builtin::Time => (PPTokKind::StringLiteral, quote(&self.ctx.time)),
+            Builtin::Counter => {
+                let counter = self.ctx.counter.get();
+                self.ctx.counter.set(counter + 1);
+                (PPTokKind::Number, counter.to_string())
+            }
+        };
+        let mut res = PPToken::new(kind, &spelling);
+        res.has_space = tok.has_space;
+        res.line = tok.line;
+        res.col = tok.col;
+        res.expansion = tok.expansion;
+        res
+    }
+}
+
+// The file name and line number presumed for the lines of a source file,
+// they can be changed by `#line`.
+#[derive(Debug)]
+struct PresumedLoc {
+    file: PathBuf,
+    // index of `file` in the files of the `SourceMap`
+    file_id: usize,
+    // index of the file the lines are read from, which `#line` does not change
+    source: usize,
+    // added to the physical line number
+    line_delta: isize,
+    // found in a system directory or included by a system header
+    system: bool,
+}
+
+impl PresumedLoc {
+    fn new(path: &Path, system: bool, map: &mut SourceMap) -> PresumedLoc {
+        let file_id = map.file_id(path);
+        PresumedLoc {
+            file: path.to_path_buf(),
+            file_id,
+            source: file_id,
+            line_delta: 0,
+            system,
+        }
+    }
+
+    fn line(&self, line: usize) -> usize {
+        (line as isize + self.line_delta) as usize
+    }
+
+    fn source_loc(&self, tok: &PPToken) -> SourceLoc {
+        SourceLoc {
+            file: self.file_id,
+            line: self.line(tok.line),
+            col: tok.col,
+        }
+    }
+
+    fn error(&self, line: usize, msg: &str) -> Box<dyn error::Error> {
+        cpp_error(&self.file, self.line(line), msg)
+    }
+}
+
+// Where the characters of the output of a translation phase come from: sorted
+// (output offset, input offset) pairs, each one starts a run of bytes which are
+// copied from the input as they are.
+#[derive(Debug, Default)]
+struct OffsetMap(Vec<(usize, usize)>);
+
+impl OffsetMap {
+    fn push(&mut self, out: usize, input: usize) {
+        if let Some(&(last_out, last_input)) = self.0.last() {
+            if input as isize - out as isize == last_input as isize - last_out as isize {
+                return;
+            }
+        }
+        self.0.push((out, input));
+    }
+
+    fn map(&self, out: usize) -> usize {
+        let idx = match self.0.binary_search_by_key(&out, |&(o, _)| o) {
+            Ok(idx) => idx,
+            Err(0) => return out,
+            Err(idx) => idx - 1,
+        };
+        let (o, i) = self.0[idx];
+        i + (out - o)
+    }
+}
+
+// next char of `input` at the byte `idx`
+fn char_at(input: &str, idx: usize) -> char {
+    input[idx..].chars().next().unwrap()
 }
 
-fn trigraph_processor(input: String) -> Result<String, String> {
+fn trigraph_processor(input: String) -> Result<(String, OffsetMap), String> {
     // Trigraph:       ??(  ??)  ??<  ??>  ??=  ??/  ??'  ??!  ??-
     // Replacement:      [    ]    {    }    #    \    ^    |    ~
     let mut res = "".to_string();
+    let mut map = OffsetMap::default();
 
     let mut idx = 0;
     while idx < input.len() {
-        if idx > input.len() - 3 {
-            res.push(char::from(input.as_bytes()[idx]));
-            idx = idx + 1;
+        if idx + 3 > input.len() {
+            let c = char_at(&input, idx);
+            res.push(c);
+            idx += c.len_utf8();
             continue;
         }
         let mut combine = String::new();
@@ -90,315 +1108,1913 @@ fn trigraph_processor(input: String) -> Result<String, String> {
                 idx += 3;
             }
             _ => {
-                res.push(char::from(input.as_bytes()[idx]));
-                idx += 1;
+                let c = char_at(&input, idx);
+                res.push(c);
+                idx += c.len_utf8();
+                continue;
             }
         }
+        map.push(res.len(), idx);
     }
-    return Ok(res);
+    Ok((res, map))
 }
 
-fn line_concat(input: String) -> Result<String, String> {
+fn line_concat(input: String) -> Result<(String, OffsetMap), String> {
     let mut res = String::new();
-    let mut it = input.chars().peekable();
-    while let Some(&c) = it.peek() {
-        if c == '\\' {
-            it.next();
-            if let Some(&nc) = it.peek() {
-                match nc {
-                    '\n' => {
+    let mut map = OffsetMap::default();
+    // the newlines removed by splicing are put back after the logical line ends,
+    // so every line keeps its physical line number for the diagnostics.
+    let mut spliced_lines = 0;
+    let mut it = input.char_indices().peekable();
+    while let Some((idx, c)) = it.next() {
+        match c {
+            '\\' => {
+                match it.peek().map(|&(_, nc)| nc) {
+                    Some('\n') => {
                         // remove this `\` and `\n`
-                        // so nothing here, just skip
+                        it.next();
+                        spliced_lines += 1;
+                        map.push(res.len(), idx + 2);
                     }
-                    _ => {
+                    Some(_) => {
                         res.push(c);
-                        res.push(nc);
                     }
-                }
-            } else {
-                // no other characters, at the end of file
-                // Should give warning, but continue compile.
-                // cause crust now has no warning option, so just remove this `\`
-                break;
-            }
-        } else {
-            res.push(c);
-        }
-        it.next();
-    }
-
-    return Ok(res);
-}
-
-fn remove_comment(input: String) -> Result<String, String> {
-    fn in_comment(single: bool, multi: bool) -> bool {
-        return single || multi;
-    }
-    let mut res = String::new();
-
-    let mut idx = 0;
-    let mut single_line_in_comment = false;
-    let mut multi_line_in_comment = false;
-    while idx < input.len() {
-        if idx == input.len() - 1 {
-            if single_line_in_comment || multi_line_in_comment {
-                // skip
-                break;
-            } else {
-                res.push(char::from(input.as_bytes()[idx]));
-                break;
-            }
-        }
-        let b1 = char::from(input.as_bytes()[idx]);
-        let b2 = char::from(input.as_bytes()[idx + 1]);
-
-        let mut combine = String::new();
-        combine.push(b1);
-        combine.push(b2);
-        match combine.as_ref() {
-            "//" => {
-                if !in_comment(single_line_in_comment, multi_line_in_comment) {
-                    single_line_in_comment = true;
-                }
-                idx = idx + 2;
-            }
-            "/*" => {
-                if !in_comment(single_line_in_comment, multi_line_in_comment) {
-                    multi_line_in_comment = true;
-                }
-                idx = idx + 2;
-            }
-            "*/" => {
-                if multi_line_in_comment {
-                    multi_line_in_comment = false;
-                    idx = idx + 2;
-                } else {
-                    res.push(b1);
-                    idx = idx + 1;
-                }
-            }
-            _ => {
-                if b1 == '\n' && single_line_in_comment {
-                    single_line_in_comment = false;
-                    idx = idx + 1;
-                } else {
-                    if in_comment(single_line_in_comment, multi_line_in_comment) {
-                        idx = idx + 1;
-                    } else {
-                        res.push(b1);
-                        idx = idx + 1;
+                    None => {
+                        // no other characters, at the end of file
+                        // Should give warning, but continue compile.
+                        // cause crust now has no warning option, so just remove this `\`
+                        break;
                     }
                 }
             }
-        }
-    }
-    return Ok(res);
-}
-
-fn include_headers(input: String, parent: Option<&Path>) -> Result<String, Box<dyn error::Error>> {
-    // TODO: now only support "header.h", system header file not supported now
-    //       should add system header support when the macro expension was finished and
-    //       other directives are supported.
-    fn include_file_path(relational_name: &str) -> Result<&Path, String> {
-        if char::from(relational_name.as_bytes()[0]) == '<' {
-            return Err("Crust do not support system header now".to_string());
-        }
-        let name = Path::new(&relational_name[1..(relational_name.len() - 1)]);
-        return Ok(name);
-    }
-
-    let mut res = String::new();
-
-    for line in input.lines() {
-        if line.is_empty() {
-            // empty line
-            res.push_str("\n");
-            continue;
-        }
-        match char::from(line.trim_start().as_bytes()[0]) {
-            '#' => {
-                let a: Vec<&str> = line.split_whitespace().collect();
-                let directive = *a.get(0).unwrap();
-                match directive {
-                    "#include" => {
-                        let file_name = include_file_path(*a.get(1).unwrap())?;
-                        let full_relational_path = file_name;
-
-                        match parent {
-                            None => {
-                                let header_contents = fs::read_to_string(full_relational_path)?;
-                                res.push_str(include_headers(header_contents, None)?.as_ref());
-                            }
-                            Some(p_dir) => {
-                                let full_relational_path = p_dir.join(file_name);
-                                let header_contents = fs::read_to_string(full_relational_path)?;
-                                res.push_str(include_headers(header_contents, parent)?.as_ref());
-                            }
-                        }
-                    }
-                    _ => {
-                        // just leave other directives to be handled by the directive_handler
-                        res.push_str(line);
-                        res.push_str("\n");
-                    }
+            '\n' => {
+                res.push('\n');
+                for _ in 0..spliced_lines {
+                    res.push('\n');
                 }
+                spliced_lines = 0;
+                map.push(res.len(), idx + 1);
             }
             _ => {
-                res.push_str(line);
-                res.push_str("\n");
+                res.push(c);
             }
         }
     }
+    for _ in 0..spliced_lines {
+        res.push('\n');
+    }
 
-    return Ok(res);
+    Ok((res, map))
 }
 
-fn replace(input: String) -> String {
-    // This is a function, which replace the string if contains defined identifier.
-    let mut it = input.chars().peekable();
+fn remove_comment(input: String) -> Result<(String, OffsetMap), String> {
     let mut res = String::new();
-    while let Some(&c) = it.peek() {
+    let mut map = OffsetMap::default();
+    // like the spliced lines, newlines inside a multi-line comment are emitted
+    // after the logical line, and the comment itself is replaced by one space.
+    let mut comment_lines = 0;
+    let mut it = input.char_indices().peekable();
+    let next_is = |it: &mut std::iter::Peekable<std::str::CharIndices>, c: char| {
+        it.peek().map(|&(_, nc)| nc) == Some(c)
+    };
+    while let Some((idx, c)) = it.next() {
         match c {
-            'a'..='z' | 'A'..='Z' | '_' => {
+            '/' if next_is(&mut it, '/') => {
+                while let Some(&(_, nc)) = it.peek() {
+                    if nc == '\n' {
+                        break;
+                    }
+                    it.next();
+                }
+                map.push(res.len(), idx);
+                res.push(' ');
+                map.push(res.len(), it.peek().map_or(input.len(), |&(end, _)| end));
+            }
+            '/' if next_is(&mut it, '*') => {
                 it.next();
-                let mut id = String::new();
-                id.push(c);
-                while let Some(&tmp) = it.peek() {
-                    match tmp {
-                        'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => {
-                            id.push(tmp);
+                loop {
+                    match it.next() {
+                        Some((_, '*')) if next_is(&mut it, '/') => {
                             it.next();
-                        }
-                        _ => {
                             break;
                         }
+                        Some((_, '\n')) => comment_lines += 1,
+                        Some(_) => {}
+                        None => return Err("unterminated comment".to_string()),
                     }
                 }
-                let mut get_val = String::new();
-                match MACROS.lock().unwrap().get(&id) {
-                    Some(s) => {
-                        // XXX: I use this method to get rid of deadlock.
-                        //      cause call replace function in this scope will cause the
-                        //      child function to lock the MACROS, which is locked here.
-
-                        let mut replacement = s.replacement.clone();
-                        if !s.params.is_empty() {
-                            let re_args: Regex =
-                                Regex::new(&format!(r"{}\(([^)]+)\)", id)).unwrap();
-
-                            if re_args.is_match(&input) {
-                                let caps = re_args.captures(&input).unwrap();
-                                let matched = caps[0].to_string();
-                                let all_args = caps[1].to_string();
-                                let args: Vec<&str> = all_args.split(",").collect();
-                                if args.len() != s.params.len() {
-                                    error!("Not matched with macro arguments{:?}", id);
-                                }
-
-                                for (i, p) in s.params.iter().enumerate() {
-                                    replacement = replacement.replace(p, args[i]);
-                                }
-
-                                // Skip matched text
-                                for _ in 0..matched.len() - id.len() {
-                                    it.next();
-                                }
-                            } else {
-                                error!("Unable to find macro's parameter, {:?}", id);
+                map.push(res.len(), idx);
+                res.push(' ');
+                map.push(res.len(), it.peek().map_or(input.len(), |&(end, _)| end));
+            }
+            '"' | '\'' => {
+                // copy the literal as it is, `//` or `/*` inside it is not a comment
+                res.push(c);
+                while let Some(&(_, nc)) = it.peek() {
+                    if nc == '\n' {
+                        break;
+                    }
+                    res.push(nc);
+                    it.next();
+                    if nc == c {
+                        break;
+                    }
+                    if nc == '\\' {
+                        if let Some(&(_, escaped)) = it.peek() {
+                            if escaped != '\n' {
+                                res.push(escaped);
+                                it.next();
                             }
                         }
-                        get_val.push_str(&replacement);
-                    }
-                    None => {
-                        res.push_str(&id);
                     }
                 }
-                // after the lock was released, can lock in child replace function
-                res.push_str(&replace(get_val));
+            }
+            '\n' => {
+                res.push('\n');
+                for _ in 0..comment_lines {
+                    res.push('\n');
+                }
+                comment_lines = 0;
+                map.push(res.len(), idx + 1);
             }
             _ => {
                 res.push(c);
-                it.next();
             }
         }
     }
-    return res;
+    for _ in 0..comment_lines {
+        res.push('\n');
+    }
+    Ok((res, map))
 }
 
-fn directive_handler(input: String) -> Result<String, Box<dyn error::Error>> {
-    // TODO: now only support #define directive
-    let mut res = String::new();
+// A source file after the translation phases 1 to 3, with what is needed to find
+// the physical line and column of its characters.
+struct SourceText {
+    text: String,
+    // byte offsets of the lines of the file as it was read
+    line_starts: Vec<usize>,
+    // one map for each translation phase, in order
+    maps: Vec<OffsetMap>,
+}
 
-    let mut lines = input.lines();
-    loop {
-        let iter = lines.next();
-        match iter {
-            Some(line) => {
-                if line.trim_start().is_empty() {
-                    // empty line
-                    res.push_str("\n");
-                    continue;
-                }
-                match char::from(line.trim_start().as_bytes()[0]) {
-                    '#' => {
-                        lazy_static! {
-                            static ref RE_MACRO: Regex = Regex::new(
-                                r"^[ \t]*#define[ \t]+([A-Za-z0-9_]+)(\(.*\))?[ \t]*((?:.*\\\r?\n)*.*)"
-                            )
-                            .unwrap();
-                        }
-                        if RE_MACRO.is_match(line) {
-                            let caps = RE_MACRO.captures(line).unwrap();
-                            let name = caps[1].to_string();
-                            let params = caps
-                                .get(2)
-                                .map_or("", |m| m.as_str())
-                                .to_string()
-                                .replace(&['(', ')', ' ', '\t'][..], "");
-                            let replacement = caps[3].to_string();
-
-                            let mut param_list = Vec::new();
-                            for t in params.split(",") {
-                                if !t.is_empty() {
-                                    param_list.push(t.to_string());
-                                }
-                            }
+impl SourceText {
+    fn new(original: String) -> Result<SourceText, String> {
+        let mut line_starts = vec![0];
+        line_starts.extend(original.match_indices('\n').map(|(idx, _)| idx + 1));
+        // first translate trigraph into chars
+        let (text, trigraph_map) = trigraph_processor(original)?;
+        // concatenate lines
+        let (text, concat_map) = line_concat(text)?;
+        // remove comment
+        let (text, comment_map) = remove_comment(text)?;
+        Ok(SourceText {
+            text,
+            line_starts,
+            maps: vec![trigraph_map, concat_map, comment_map],
+        })
+    }
 
-                            let m = Macro {
-                                replacement: replacement,
-                                params: param_list,
-                            };
-                            debug!("Macro detected: name:{:?} => {:?}", name, m);
+    // the physical (line, column) of the byte `offset` of `text`
+    fn position(&self, offset: usize) -> (usize, usize) {
+        let offset = self.maps.iter().rev().fold(offset, |off, map| map.map(off));
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        };
+        // the columns count bytes, as the ones of `lexer::Span`
+        (line + 1, offset - self.line_starts[line] + 1)
+    }
+}
 
-                            MACROS.lock().unwrap().insert(name, m);
-                        }
-                    }
-                    _ => {
-                        // not directive starting sentence, so replace the token if it's defined before.
-                        // check every identifier name
-                        res.push_str(&replace(line.to_string()));
-                        res.push('\n');
-                    }
+#[derive(PartialEq, Clone, Copy, Debug)]
+enum PPTokKind {
+    Identifier,
+    Number,
+    CharConstant,
+    StringLiteral,
+    Punctuator,
+    // a single non-white-space character that is not any of the above
+    Other,
+    // stands for an empty macro argument next to `##`, see C11 6.10.3.3
+    Placemarker,
+}
+
+// preprocessing token, see C11 6.4
+#[derive(Clone, Debug)]
+struct PPToken {
+    kind: PPTokKind,
+    spelling: String,
+    // whether there are white spaces before this token
+    has_space: bool,
+    // physical line and column in the source file, the tokens produced by a macro
+    // expansion take the position of the macro name
+    line: usize,
+    col: usize,
+    // the innermost macro expansion which produced this token, an index of the
+    // expansions of the `SourceMap`
+    expansion: Option<usize>,
+    // names of the macros this token was produced by, see `expand_macros`
+    hideset: BTreeSet<String>,
+}
+
+impl PPToken {
+    fn new(kind: PPTokKind, spelling: &str) -> PPToken {
+        PPToken {
+            kind,
+            spelling: spelling.to_string(),
+            has_space: false,
+            line: 0,
+            col: 0,
+            expansion: None,
+            hideset: BTreeSet::new(),
+        }
+    }
+
+    fn is_punct(&self, punct: &str) -> bool {
+        self.kind == PPTokKind::Punctuator && self.spelling == punct
+    }
+}
+
+// longest ones first, so the first match is the longest match
+const PUNCTUATORS: [&str; 54] = [
+    "%:%:", "...", "<<=", ">>=", "->", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||",
+    "*=", "/=", "%=", "+=", "-=", "&=", "^=", "|=", "##", "<:", ":>", "<%", "%>", "%:", "[", "]",
+    "(", ")", "{", "}", ".", "&", "*", "+", "-", "~", "!", "/", "%", "<", ">", "^", "|", "?", ":",
+    ";", "=", ",", "#",
+];
+
+// a universal character name `\uXXXX` or `\UXXXXXXXX` at `idx`, its length and
+// the character it names
+fn ucn_at(chars: &[char], idx: usize) -> Option<(usize, char)> {
+    let len = match chars.get(idx..idx + 2) {
+        Some(['\\', 'u']) => 6,
+        Some(['\\', 'U']) => 10,
+        _ => return None,
+    };
+    let digits = chars.get(idx + 2..idx + len)?;
+    if !digits.iter().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let value = u32::from_str_radix(&digits.iter().collect::<String>(), 16).ok()?;
+    // the basic characters can not be named, see C11 6.4.3p2
+    Some((len, std::char::from_u32(value).filter(|c| !c.is_ascii())?))
+}
+
+// split one logical line into preprocessing tokens
+fn pp_tokenize(line: &str, line_no: usize) -> Vec<PPToken> {
+    pp_tokenize_at(line, &|offset| (line_no, offset + 1))
+}
+
+// split one logical line into preprocessing tokens, `position` gives the physical
+// line and column of a byte offset of `line`
+fn pp_tokenize_at(line: &str, position: &dyn Fn(usize) -> (usize, usize)) -> Vec<PPToken> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut byte_offset = 0;
+    let mut res = Vec::new();
+    let mut has_space = false;
+    let mut idx = 0;
+    while idx < chars.len() {
+        let c = chars[idx];
+        let start = idx;
+        let kind;
+        let mut identifier = None;
+        if c.is_whitespace() {
+            has_space = true;
+            idx += 1;
+            byte_offset += c.len_utf8();
+            continue;
+        } else if c.is_ascii_digit()
+            || (c == '.' && idx + 1 < chars.len() && chars[idx + 1].is_ascii_digit())
+        {
+            // pp-number
+            idx += 1;
+            while idx < chars.len() {
+                let nc = chars[idx];
+                let is_exponent_sign = (nc == '+' || nc == '-') && "eEpP".contains(chars[idx - 1]);
+                if is_exponent_sign || is_identifier_char(nc) || nc == '.' {
+                    idx += 1;
+                } else {
+                    break;
+                }
+            }
+            kind = PPTokKind::Number;
+        } else if let Some(end) = literal_end(&chars, idx) {
+            idx = end;
+            kind = if chars[idx - 1] == '"' {
+                PPTokKind::StringLiteral
+            } else {
+                PPTokKind::CharConstant
+            };
+        } else if is_identifier_start(c)
+            || ucn_at(&chars, idx).is_some_and(|(_, u)| is_identifier_start(u))
+        {
+            // the universal character names are replaced by the characters they name
+            let mut name = String::new();
+            while idx < chars.len() {
+                if let Some((len, u)) = ucn_at(&chars, idx).filter(|&(_, u)| is_identifier_char(u))
+                {
+                    name.push(u);
+                    idx += len;
+                } else if is_identifier_char(chars[idx]) {
+                    name.push(chars[idx]);
+                    idx += 1;
+                } else {
+                    break;
+                }
+            }
+            identifier = Some(name);
+            kind = PPTokKind::Identifier;
+        } else if let Some(punct) = PUNCTUATORS.iter().find(|p| {
+            let rest: String = chars[idx..].iter().take(p.len()).collect();
+            rest == **p
+        }) {
+            idx += punct.len();
+            kind = PPTokKind::Punctuator;
+        } else {
+            // an unmatched ' or " is also a single character token
+            idx += 1;
+            kind = PPTokKind::Other;
+        }
+        let spelling: String = chars[start..idx].iter().collect();
+        let mut tok = PPToken::new(kind, identifier.as_ref().unwrap_or(&spelling));
+        tok.has_space = has_space;
+        let (tok_line, tok_col) = position(byte_offset);
+        tok.line = tok_line;
+        tok.col = tok_col;
+        byte_offset += spelling.len();
+        has_space = false;
+        res.push(tok);
+    }
+    res
+}
+
+// if a string literal or a character constant starts at `start`, return the index
+// after its closing quote
+fn literal_end(chars: &[char], start: usize) -> Option<usize> {
+    let prefix_len = match &chars[start..] {
+        ['u', '8', '"', ..] => 2,
+        ['u', '"', ..] | ['U', '"', ..] | ['L', '"', ..] => 1,
+        ['u', '\'', ..] | ['U', '\'', ..] | ['L', '\'', ..] => 1,
+        ['"', ..] | ['\'', ..] => 0,
+        _ => return None,
+    };
+    let quote = chars[start + prefix_len];
+    let mut idx = start + prefix_len + 1;
+    while idx < chars.len() {
+        match chars[idx] {
+            '\\' => idx += 2,
+            c if c == quote => return Some(idx + 1),
+            _ => idx += 1,
+        }
+    }
+    None
+}
+
+// whether two adjacent tokens would be read as one token, if there was no space
+// between them
+fn needs_space(prev: &PPToken, cur: &PPToken) -> bool {
+    match (prev.kind, cur.kind) {
+        (PPTokKind::Identifier, PPTokKind::Identifier)
+        | (PPTokKind::Identifier, PPTokKind::Number)
+        | (PPTokKind::Identifier, PPTokKind::CharConstant)
+        | (PPTokKind::Identifier, PPTokKind::StringLiteral)
+        | (PPTokKind::Number, PPTokKind::Identifier)
+        | (PPTokKind::Number, PPTokKind::Number) => true,
+        (PPTokKind::Number, PPTokKind::Punctuator) => {
+            cur.spelling.starts_with('.')
+                || (cur.spelling.starts_with('+') || cur.spelling.starts_with('-'))
+                    && prev.spelling.ends_with(|c| "eEpP".contains(c))
+        }
+        (PPTokKind::Punctuator, PPTokKind::Number) => prev.spelling == ".",
+        (PPTokKind::Punctuator, PPTokKind::Punctuator) => {
+            let mut joined = prev.spelling.clone();
+            joined.push(cur.spelling.chars().next().unwrap());
+            // `/` followed by `/` or `*` would start a comment
+            joined == "//" || joined == "/*" || PUNCTUATORS.iter().any(|p| p.starts_with(&joined))
+        }
+        _ => false,
+    }
+}
+
+// turn the tokens of the lines `first_line..=last_line` back into text, each token
+// is put on the line it came from, so the line numbers of the output match the source
+fn pp_tokens_to_text(toks: &[PPToken], first_line: usize, last_line: usize) -> String {
+    let mut res = String::new();
+    write_tokens(&mut res, toks, first_line, last_line, &mut |_, _| {});
+    res
+}
+
+// append the tokens to `res`, see `pp_tokens_to_text`, and call `on_token` with the
+// offset in `res` of every token
+fn write_tokens(
+    res: &mut String,
+    toks: &[PPToken],
+    first_line: usize,
+    last_line: usize,
+    on_token: &mut dyn FnMut(usize, &PPToken),
+) {
+    let mut line = first_line;
+    let mut prev: Option<&PPToken> = None;
+    for tok in toks {
+        while line < tok.line {
+            res.push('\n');
+            line += 1;
+            prev = None;
+        }
+        let space = match prev {
+            Some(prev) => tok.has_space || needs_space(prev, tok),
+            None => tok.has_space,
+        };
+        if space {
+            res.push(' ');
+        }
+        on_token(res.len(), tok);
+        res.push_str(&tok.spelling);
+        prev = Some(tok);
+    }
+    while line <= last_line {
+        res.push('\n');
+        line += 1;
+    }
+}
+
+// #define identifier replacement-list
+// #define identifier lparen identifier-list(opt) ) replacement-list
+// #define identifier lparen ... ) replacement-list
+// #define identifier lparen identifier-list , ... ) replacement-list
+fn parse_define(toks: Vec<PPToken>) -> Result<(String, Macro), String> {
+    let mut it = toks.into_iter().peekable();
+    let name = match it.next() {
+        Some(ref tok) if tok.kind == PPTokKind::Identifier => tok.spelling.clone(),
+        _ => return Err("macro names must be identifiers".to_string()),
+    };
+    if name == "defined" {
+        return Err("\"defined\" cannot be used as a macro name".to_string());
+    }
+    let mut params = None;
+    let mut variadic = false;
+    // a function-like macro has no white space between its name and the `(`
+    if let Some(tok) = it.peek() {
+        if tok.is_punct("(") && !tok.has_space {
+            it.next();
+            let mut list: Vec<String> = Vec::new();
+            loop {
+                match it.next() {
+                    Some(ref tok) if tok.is_punct(")") && list.is_empty() => break,
+                    Some(ref tok) if tok.is_punct("...") => {
+                        // the variable arguments are named `__VA_ARGS__`
+                        variadic = true;
+                        list.push("__VA_ARGS__".to_string());
+                    }
+                    Some(ref tok) if tok.kind == PPTokKind::Identifier => {
+                        if tok.spelling == "__VA_ARGS__" || tok.spelling == "__VA_OPT__" {
+                            return Err(format!(
+                                "{} can not be used as a parameter name",
+                                tok.spelling
+                            ));
+                        }
+                        if list.contains(&tok.spelling) {
+                            return Err(format!("duplicate macro parameter \"{}\"", tok.spelling));
+                        }
+                        list.push(tok.spelling.clone());
+                        // GNU extension: `name...` gives the variable arguments a name
+                        if it.peek().is_some_and(|t| t.is_punct("...")) {
+                            it.next();
+                            variadic = true;
+                        }
+                    }
+                    _ => return Err("expected parameter name in macro parameter list".to_string()),
+                }
+                match it.next() {
+                    Some(ref tok) if tok.is_punct(")") => break,
+                    Some(ref tok) if tok.is_punct(",") && !variadic => {}
+                    _ if variadic => return Err("missing ')' after \"...\"".to_string()),
+                    _ => return Err("expected `,` or `)` in macro parameter list".to_string()),
+                }
+            }
+            params = Some(list);
+        }
+    }
+    let mut replacement: Vec<PPToken> = it.collect();
+    if let Some(tok) = replacement.first_mut() {
+        tok.has_space = false;
+    }
+    let ends_with_paste = match (replacement.first(), replacement.last()) {
+        (Some(first), Some(last)) => is_paste_op(first) || is_paste_op(last),
+        _ => false,
+    };
+    if ends_with_paste {
+        return Err("'##' cannot appear at either end of a macro expansion".to_string());
+    }
+    for (idx, tok) in replacement.iter().enumerate() {
+        if tok.kind != PPTokKind::Identifier {
+            continue;
+        }
+        if (tok.spelling == "__VA_ARGS__"
+            && !params.as_ref().is_some_and(|p| p.contains(&tok.spelling)))
+            || (tok.spelling == "__VA_OPT__" && !variadic)
+        {
+            return Err(format!(
+                "{} can only appear in the expansion of a variadic macro",
+                tok.spelling
+            ));
+        }
+        if is_va_opt(tok) {
+            match matching_paren(&replacement, idx + 1) {
+                Some(end) if !replacement[idx + 1..end].iter().any(is_va_opt) => {}
+                Some(_) => return Err("__VA_OPT__ may not appear in a __VA_OPT__".to_string()),
+                None => return Err("unterminated __VA_OPT__".to_string()),
+            }
+        }
+    }
+    if let Some(ref params) = params {
+        let is_param =
+            |t: &PPToken| t.kind == PPTokKind::Identifier && params.contains(&t.spelling);
+        for (idx, tok) in replacement.iter().enumerate() {
+            if is_stringify_op(tok) && !replacement.get(idx + 1).is_some_and(is_param) {
+                return Err("'#' is not followed by a macro parameter".to_string());
+            }
+        }
+    }
+    Ok((
+        name,
+        Macro {
+            params,
+            variadic,
+            replacement,
+            builtin: None,
+            defined_at: None,
+        },
+    ))
+}
+
+// the errors of the macro expansion carry the line of the macro name
+type ExpandResult<T> = Result<T, (usize, String)>;
+
+// Expand all the macros in `toks`, see C11 6.10.3.4.
+// Every token carries a hide set with the names of the macros it was produced by,
+// and a name in the hide set of its own token is never expanded again, so the rescan
+// of `#define f(x) f(x + 1)` stops after one step (Prosser's algorithm).
+fn expand_macros(env: &ExpandEnv, toks: Vec<PPToken>) -> ExpandResult<Vec<PPToken>> {
+    let mut input: VecDeque<PPToken> = toks.into();
+    let mut res = Vec::new();
+    while let Some(tok) = input.pop_front() {
+        let m = match env.ctx.macros.get(&tok.spelling) {
+            Some(m)
+                if tok.kind == PPTokKind::Identifier && !tok.hideset.contains(&tok.spelling) =>
+            {
+                m
+            }
+            _ => {
+                res.push(tok);
+                continue;
+            }
+        };
+        if let Some(builtin) = m.builtin {
+            res.push(env.builtin_value(builtin, &tok));
+            continue;
+        }
+        let expansion = Expansion {
+            name: tok.spelling.clone(),
+            loc: env.loc.source_loc(&tok),
+            defined_at: m.defined_at,
+            parent: tok.expansion,
+        };
+        let expanded = match m.params {
+            None => {
+                let mut hideset = tok.hideset.clone();
+                hideset.insert(tok.spelling.clone());
+                substitute(env, m, &[], &hideset, &tok, expansion)?
+            }
+            Some(ref params) => {
+                // the name of a function-like macro not followed by `(` is not expanded
+                match input.front() {
+                    Some(next) if next.is_punct("(") => {}
+                    _ => {
+                        res.push(tok);
+                        continue;
+                    }
+                }
+                let variadic_from = if m.variadic {
+                    Some(params.len() - 1)
+                } else {
+                    None
+                };
+                let (mut args, rparen) = collect_args(&mut input, &tok, variadic_from)?;
+                if params.is_empty() && args.len() == 1 && args[0].is_empty() {
+                    args.clear();
+                }
+                if m.variadic && args.len() + 1 == params.len() {
+                    // the variable arguments are left out
+                    args.push(Vec::new());
+                }
+                if args.len() < params.len() {
+                    return Err((
+                        tok.line,
+                        format!(
+                            "macro \"{}\" requires {}{} arguments, but only {} given",
+                            tok.spelling,
+                            if m.variadic { "at least " } else { "" },
+                            params.len() - m.variadic as usize,
+                            args.len()
+                        ),
+                    ));
+                }
+                if args.len() > params.len() {
+                    return Err((
+                        tok.line,
+                        format!(
+                            "macro \"{}\" passed {} arguments, but takes just {}",
+                            tok.spelling,
+                            args.len(),
+                            params.len()
+                        ),
+                    ));
+                }
+                let mut hideset: BTreeSet<String> =
+                    tok.hideset.intersection(&rparen.hideset).cloned().collect();
+                hideset.insert(tok.spelling.clone());
+                substitute(env, m, &args, &hideset, &tok, expansion)?
+            }
+        };
+        // the result is rescanned together with the rest of the tokens
+        for t in expanded.into_iter().rev() {
+            input.push_front(t);
+        }
+    }
+    Ok(res)
+}
+
+// collect the arguments of a function-like macro invocation, the input starts
+// at the `(`. Return the arguments and the closing `)`.
+// The commas in the variable arguments, which start from the argument `variadic_from`,
+// do not separate the arguments.
+fn collect_args(
+    input: &mut VecDeque<PPToken>,
+    name: &PPToken,
+    variadic_from: Option<usize>,
+) -> ExpandResult<(Vec<Vec<PPToken>>, PPToken)> {
+    input.pop_front();
+    let mut args = vec![Vec::new()];
+    let mut depth = 0;
+    loop {
+        let tok = match input.pop_front() {
+            Some(tok) => tok,
+            None => {
+                return Err((
+                    name.line,
+                    format!(
+                        "unterminated argument list invoking macro \"{}\"",
+                        name.spelling
+                    ),
+                ))
+            }
+        };
+        if tok.is_punct("(") {
+            depth += 1;
+        } else if tok.is_punct(")") {
+            if depth == 0 {
+                return Ok((args, tok));
+            }
+            depth -= 1;
+        } else if tok.is_punct(",") && depth == 0 && variadic_from != Some(args.len() - 1) {
+            args.push(Vec::new());
+            continue;
+        }
+        args.last_mut().unwrap().push(tok);
+    }
+}
+
+// replace the parameters in the replacement list by the arguments and apply the
+// `#` and `##` operators, then add `hideset` to every token of the result.
+fn substitute(
+    env: &ExpandEnv,
+    m: &Macro,
+    args: &[Vec<PPToken>],
+    hideset: &BTreeSet<String>,
+    name: &PPToken,
+    expansion: Expansion,
+) -> ExpandResult<Vec<PPToken>> {
+    let mut subst = Substitution {
+        env,
+        m,
+        args,
+        expanded_args: vec![None; args.len()],
+        line: name.line,
+    };
+    let mut res = subst.substitute_list(&m.replacement)?;
+
+    res.retain(|tok| tok.kind != PPTokKind::Placemarker);
+    let mut expansions = env.ctx.expansions.borrow_mut();
+    expansions.push(expansion);
+    for tok in res.iter_mut() {
+        tok.hideset.extend(hideset.iter().cloned());
+        tok.line = name.line;
+        tok.col = name.col;
+        tok.expansion = Some(expansions.len() - 1);
+    }
+    if let Some(first) = res.first_mut() {
+        first.has_space = name.has_space;
+    }
+    Ok(res)
+}
+
+// The arguments of one macro invocation. The operands of `#` and `##` are the arguments
+// as they are written, the other parameters are replaced by the fully expanded arguments.
+struct Substitution<'a> {
+    env: &'a ExpandEnv<'a>,
+    m: &'a Macro,
+    args: &'a [Vec<PPToken>],
+    // each argument is expanded when it is used for the first time
+    expanded_args: Vec<Option<Vec<PPToken>>>,
+    line: usize,
+}
+
+impl<'a> Substitution<'a> {
+    fn param_index(&self, tok: &PPToken) -> Option<usize> {
+        match self.m.params {
+            Some(ref params) if tok.kind == PPTokKind::Identifier => {
+                params.iter().position(|p| *p == tok.spelling)
+            }
+            _ => None,
+        }
+    }
+
+    fn is_va_args(&self, idx: usize) -> bool {
+        self.m.variadic && idx + 1 == self.args.len()
+    }
+
+    // an empty argument next to `##` is replaced by a placemarker
+    fn unexpanded_arg(&self, idx: usize, has_space: bool) -> Vec<PPToken> {
+        let mut toks = self.args[idx].clone();
+        if toks.is_empty() {
+            toks.push(PPToken::new(PPTokKind::Placemarker, ""));
+        }
+        toks[0].has_space = has_space;
+        toks
+    }
+
+    fn expanded_arg(&mut self, idx: usize) -> ExpandResult<&[PPToken]> {
+        if self.expanded_args[idx].is_none() {
+            let expanded = expand_macros(self.env, self.args[idx].clone())?;
+            self.expanded_args[idx] = Some(expanded);
+        }
+        Ok(self.expanded_args[idx].as_ref().unwrap())
+    }
+
+    // `__VA_OPT__ ( content )` at `body[idx]` is replaced by the content if the variable
+    // arguments are not empty, see C2x 6.10.4.1. Return the result and the index after `)`.
+    fn va_opt(&mut self, body: &[PPToken], idx: usize) -> ExpandResult<(Vec<PPToken>, usize)> {
+        // checked by `parse_define`, the parentheses are balanced
+        let end = matching_paren(body, idx + 1).unwrap();
+        let va_args = self.args.len() - 1;
+        let mut res = if self.expanded_arg(va_args)?.is_empty() {
+            Vec::new()
+        } else {
+            self.substitute_list(&body[idx + 2..end])?
+        };
+        if res.is_empty() {
+            res.push(PPToken::new(PPTokKind::Placemarker, ""));
+        }
+        res[0].has_space = body[idx].has_space;
+        Ok((res, end + 1))
+    }
+
+    fn substitute_list(&mut self, body: &[PPToken]) -> ExpandResult<Vec<PPToken>> {
+        let mut res: Vec<PPToken> = Vec::new();
+        let mut idx = 0;
+        while idx < body.len() {
+            let tok = &body[idx];
+            let next = body.get(idx + 1);
+            if self.m.params.is_some() && is_stringify_op(tok) {
+                // checked by `parse_define`, `#` is always followed by a parameter here
+                let arg = self.param_index(next.unwrap()).unwrap();
+                let mut s = stringify(&self.args[arg]);
+                s.has_space = tok.has_space;
+                res.push(s);
+                idx += 2;
+                continue;
+            }
+            if is_paste_op(tok) {
+                // checked by `parse_define`, `##` is never at either end of the replacement
+                let rhs = next.unwrap();
+                let (mut rhs, next_idx) = match self.param_index(rhs) {
+                    // GNU extension: in `, ## __VA_ARGS__` the comma is removed if the
+                    // variable arguments are empty, and nothing is pasted otherwise
+                    Some(arg)
+                        if self.is_va_args(arg) && res.last().is_some_and(|t| t.is_punct(",")) =>
+                    {
+                        if self.args[arg].is_empty() {
+                            res.pop();
+                        } else {
+                            res.extend(self.unexpanded_arg(arg, rhs.has_space));
+                        }
+                        idx += 2;
+                        continue;
+                    }
+                    Some(arg) => (self.unexpanded_arg(arg, rhs.has_space), idx + 2),
+                    None if self.m.variadic && is_va_opt(rhs) => self.va_opt(body, idx + 1)?,
+                    None => (vec![rhs.clone()], idx + 2),
+                };
+                let lhs = res.pop().unwrap();
+                let pasted = paste(&lhs, &rhs[0]).map_err(|msg| (self.line, msg))?;
+                res.push(pasted);
+                res.extend(rhs.drain(1..));
+                idx = next_idx;
+                continue;
+            }
+            if self.m.variadic && is_va_opt(tok) {
+                let (group, next_idx) = self.va_opt(body, idx)?;
+                res.extend(group);
+                idx = next_idx;
+                continue;
+            }
+            let arg = match self.param_index(tok) {
+                Some(arg) => arg,
+                None => {
+                    res.push(tok.clone());
+                    idx += 1;
+                    continue;
+                }
+            };
+            if next.is_some_and(is_paste_op) {
+                res.extend(self.unexpanded_arg(arg, tok.has_space));
+            } else {
+                let start = res.len();
+                res.extend(self.expanded_arg(arg)?.iter().cloned());
+                if let Some(first) = res.get_mut(start) {
+                    first.has_space = tok.has_space;
+                }
+            }
+            idx += 1;
+        }
+        Ok(res)
+    }
+}
+
+fn is_va_opt(tok: &PPToken) -> bool {
+    tok.kind == PPTokKind::Identifier && tok.spelling == "__VA_OPT__"
+}
+
+// index of the `)` matching the `(` at `toks[start]`
+fn matching_paren(toks: &[PPToken], start: usize) -> Option<usize> {
+    if !toks.get(start)?.is_punct("(") {
+        return None;
+    }
+    let mut depth = 0;
+    for (idx, tok) in toks.iter().enumerate().skip(start) {
+        if tok.is_punct("(") {
+            depth += 1;
+        } else if tok.is_punct(")") {
+            depth -= 1;
+            if depth == 0 {
+                return Some(idx);
+            }
+        }
+    }
+    None
+}
+
+// `#` or its alternative spelling `%:`
+fn is_stringify_op(tok: &PPToken) -> bool {
+    tok.is_punct("#") || tok.is_punct("%:")
+}
+
+// `##` or its alternative spelling `%:%:`
+fn is_paste_op(tok: &PPToken) -> bool {
+    tok.is_punct("##") || tok.is_punct("%:%:")
+}
+
+// the `#` operator, see C11 6.10.3.2
+fn stringify(arg: &[PPToken]) -> PPToken {
+    let mut s = String::from("\"");
+    for (idx, tok) in arg.iter().enumerate() {
+        if idx != 0 && tok.has_space {
+            s.push(' ');
+        }
+        match tok.kind {
+            PPTokKind::StringLiteral | PPTokKind::CharConstant => {
+                for c in tok.spelling.chars() {
+                    if c == '"' || c == '\\' {
+                        s.push('\\');
+                    }
+                    s.push(c);
+                }
+            }
+            _ => s.push_str(&tok.spelling),
+        }
+    }
+    s.push('"');
+    PPToken::new(PPTokKind::StringLiteral, &s)
+}
+
+// the `##` operator, see C11 6.10.3.3
+fn paste(lhs: &PPToken, rhs: &PPToken) -> Result<PPToken, String> {
+    if rhs.kind == PPTokKind::Placemarker {
+        return Ok(lhs.clone());
+    }
+    if lhs.kind == PPTokKind::Placemarker {
+        let mut tok = rhs.clone();
+        tok.has_space = lhs.has_space;
+        return Ok(tok);
+    }
+    let joined = format!("{}{}", lhs.spelling, rhs.spelling);
+    let mut toks = pp_tokenize(&joined, lhs.line);
+    if toks.len() != 1 {
+        return Err(format!(
+            "pasting \"{}\" and \"{}\" does not give a valid preprocessing token",
+            lhs.spelling, rhs.spelling
+        ));
+    }
+    let mut tok = toks.remove(0);
+    tok.has_space = lhs.has_space;
+    Ok(tok)
+}
+
+// One entry for each `#if`, `#ifdef` or `#ifndef` that is not closed yet.
+#[derive(Debug)]
+struct Conditional {
+    // whether the group which contains this conditional is being compiled
+    parent_active: bool,
+    // whether one of the groups of this conditional was already taken
+    taken: bool,
+    // whether the current group is being compiled
+    active: bool,
+    seen_else: bool,
+    // line of the opening directive, for diagnostics
+    line: usize,
+}
+
+// Whether a file is wrapped in an include guard, `#ifndef X` or `#if !defined X`,
+// then `#endif`, with only white spaces and comments outside. A header like this
+// can be skipped if it is included again while X is defined.
+#[derive(Debug, PartialEq)]
+enum Guard {
+    // nothing but white spaces so far
+    Start,
+    Inside(String),
+    Closed(String),
+    NotGuarded,
+}
+
+impl Guard {
+    // the state after a line, `depth` is the number of open conditionals before it
+    fn next(self, line: &str, depth: usize) -> Guard {
+        if line.trim().is_empty() {
+            return self;
+        }
+        let directive = if is_directive(line) {
+            Some(split_directive(line))
+        } else {
+            None
+        };
+        match (self, directive) {
+            (Guard::Start, Some(("ifndef", rest))) => match rest.split_whitespace().next() {
+                Some(name) => Guard::Inside(name.to_string()),
+                None => Guard::NotGuarded,
+            },
+            (Guard::Start, Some(("if", rest))) => match guard_condition(rest) {
+                Some(name) => Guard::Inside(name),
+                None => Guard::NotGuarded,
+            },
+            (Guard::Inside(name), Some((directive, _))) if depth == 1 => match directive {
+                "endif" => Guard::Closed(name),
+                // the file has a part which is compiled when the macro is defined
+                "else" | "elif" => Guard::NotGuarded,
+                _ => Guard::Inside(name),
+            },
+            (Guard::Inside(name), _) => Guard::Inside(name),
+            _ => Guard::NotGuarded,
+        }
+    }
+
+    // the macro of the include guard of a whole file
+    fn of_text(text: &str) -> Option<String> {
+        let mut guard = Guard::Start;
+        let mut depth = 0;
+        for line in text.lines() {
+            guard = guard.next(line, depth);
+            if is_directive(line) {
+                match split_directive(line).0 {
+                    "endif" => depth = depth.saturating_sub(1),
+                    directive if directive.starts_with("if") => depth += 1,
+                    _ => {}
+                }
+            }
+        }
+        match guard {
+            Guard::Closed(name) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+// X of `#if !defined X` and `#if !defined(X)`
+fn guard_condition(cond: &str) -> Option<String> {
+    let toks = pp_tokenize(cond, 0);
+    let spellings: Vec<&str> = toks.iter().map(|tok| tok.spelling.as_str()).collect();
+    match spellings.as_slice() {
+        ["!", "defined", name] | ["!", "defined", "(", name, ")"]
+            if is_identifier_start(name.chars().next().unwrap()) =>
+        {
+            Some(name.to_string())
+        }
+        _ => None,
+    }
+}
+
+fn cpp_error(path: &Path, line: usize, msg: &str) -> Box<dyn error::Error> {
+    format!("{}:{}: error: {}", path.display(), line, msg).into()
+}
+
+// split a directive line into its name and the rest of the line,
+// e.g. `#  ifdef FOO` -> ("ifdef", "FOO")
+// a directive line starts with `#` or its alternative spelling `%:`
+fn is_directive(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with('#') || line.starts_with("%:")
+}
+
+fn split_directive(line: &str) -> (&str, &str) {
+    let line = line.trim_start();
+    let hash_len = if line.starts_with('#') { 1 } else { 2 };
+    let body = line[hash_len..].trim_start();
+    let end = body
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(body.len());
+    (&body[..end], body[end..].trim())
+}
+
+fn is_active(conds: &[Conditional]) -> bool {
+    match conds.last() {
+        Some(cond) => cond.active,
+        None => true,
+    }
+}
+
+// value of an integer constant expression in #if, all the signed integer types act
+// as intmax_t and all the unsigned ones as uintmax_t, see C11 6.10.1.4
+#[derive(PartialEq, Clone, Copy, Debug)]
+enum PPValue {
+    Signed(i64),
+    Unsigned(u64),
+}
+
+impl PPValue {
+    fn is_true(self) -> bool {
+        match self {
+            PPValue::Signed(v) => v != 0,
+            PPValue::Unsigned(v) => v != 0,
+        }
+    }
+
+    fn as_unsigned(self) -> u64 {
+        match self {
+            PPValue::Signed(v) => v as u64,
+            PPValue::Unsigned(v) => v,
+        }
+    }
+
+    fn from_bool(b: bool) -> PPValue {
+        PPValue::Signed(b as i64)
+    }
+}
+
+// convert the spelling of a pp-number to the value of an integer constant
+fn pp_number_value(spelling: &str) -> Result<PPValue, String> {
+    let lower = spelling.to_ascii_lowercase();
+    let exponent = if lower.starts_with("0x") { 'p' } else { 'e' };
+    if lower.contains('.') || lower.contains(exponent) {
+        return Err(format!(
+            "floating constant {} in preprocessor expression",
+            spelling
+        ));
+    }
+    // all the types act as intmax_t and uintmax_t, only `u` or a value too large
+    // for intmax_t makes it unsigned
+    let (value, _) = lexer::parse_integer(spelling)?;
+    if lower.contains('u') || value > i64::MAX as u64 {
+        Ok(PPValue::Unsigned(value))
+    } else {
+        Ok(PPValue::Signed(value as i64))
+    }
+}
+
+// precedence of the binary operators, higher binds tighter
+fn binary_precedence(tok: &PPToken) -> Option<u8> {
+    if tok.kind != PPTokKind::Punctuator {
+        return None;
+    }
+    match tok.spelling.as_ref() {
+        "*" | "/" | "%" => Some(10),
+        "+" | "-" => Some(9),
+        "<<" | ">>" => Some(8),
+        "<" | ">" | "<=" | ">=" => Some(7),
+        "==" | "!=" => Some(6),
+        "&" => Some(5),
+        "^" => Some(4),
+        "|" => Some(3),
+        "&&" => Some(2),
+        "||" => Some(1),
+        _ => None,
+    }
+}
+
+// Evaluator of the controlling expression of #if and #elif. `live` is false inside
+// the operands which are not evaluated because of `&&`, `||` and `?:`, so
+// `0 && 1 / 0` is not an error.
+struct CondEvaluator<'a> {
+    toks: &'a [PPToken],
+    pos: usize,
+}
+
+impl<'a> CondEvaluator<'a> {
+    fn peek(&self) -> Option<&'a PPToken> {
+        self.toks.get(self.pos)
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), String> {
+        match self.peek() {
+            Some(tok) if tok.is_punct(punct) => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(tok) => Err(format!("expected `{}`, found `{}`", punct, tok.spelling)),
+            None => Err(format!("expected `{}` at the end of expression", punct)),
+        }
+    }
+
+    // expression
+    //  : conditional_expression { ',' conditional_expression }
+    fn expression(&mut self, live: bool) -> Result<PPValue, String> {
+        let mut val = self.conditional(live)?;
+        while let Some(tok) = self.peek() {
+            if !tok.is_punct(",") {
+                break;
+            }
+            self.pos += 1;
+            val = self.conditional(live)?;
+        }
+        Ok(val)
+    }
+
+    // conditional_expression
+    //  : binary_expression [ '?' expression ':' conditional_expression ]
+    fn conditional(&mut self, live: bool) -> Result<PPValue, String> {
+        let cond = self.binary(0, live)?;
+        match self.peek() {
+            Some(tok) if tok.is_punct("?") => {
+                self.pos += 1;
+                let taken = cond.is_true();
+                let lhs = self.expression(live && taken)?;
+                self.expect(":")?;
+                let rhs = self.conditional(live && !taken)?;
+                let val = if taken { lhs } else { rhs };
+                // the usual arithmetic conversions apply to the second and third operands
+                match (lhs, rhs) {
+                    (PPValue::Signed(_), PPValue::Signed(_)) => Ok(val),
+                    _ => Ok(PPValue::Unsigned(val.as_unsigned())),
                 }
             }
-            None => break,
+            _ => Ok(cond),
         }
     }
-    return Ok(res);
+
+    // precedence climbing over all the binary operators
+    fn binary(&mut self, min_prec: u8, live: bool) -> Result<PPValue, String> {
+        let mut lhs = self.unary(live)?;
+        while let Some(tok) = self.peek() {
+            let prec = match binary_precedence(tok) {
+                Some(prec) if prec > min_prec => prec,
+                _ => break,
+            };
+            self.pos += 1;
+            lhs = match tok.spelling.as_ref() {
+                "&&" => {
+                    let rhs = self.binary(prec, live && lhs.is_true())?;
+                    PPValue::from_bool(lhs.is_true() && rhs.is_true())
+                }
+                "||" => {
+                    let rhs = self.binary(prec, live && !lhs.is_true())?;
+                    PPValue::from_bool(lhs.is_true() || rhs.is_true())
+                }
+                op => {
+                    let rhs = self.binary(prec, live)?;
+                    eval_binary(op, lhs, rhs, live)?
+                }
+            };
+        }
+        Ok(lhs)
+    }
+
+    // unary_expression
+    //  : unary_operator unary_expression
+    //  | primary_expression
+    fn unary(&mut self, live: bool) -> Result<PPValue, String> {
+        let tok = match self.peek() {
+            Some(tok) => tok,
+            None => return Err("expected value in expression".to_string()),
+        };
+        if tok.kind == PPTokKind::Punctuator {
+            match tok.spelling.as_ref() {
+                "+" | "-" | "~" | "!" => {
+                    self.pos += 1;
+                    let val = self.unary(live)?;
+                    return Ok(match (tok.spelling.as_ref(), val) {
+                        ("+", _) => val,
+                        ("-", PPValue::Signed(v)) => PPValue::Signed(v.wrapping_neg()),
+                        ("-", PPValue::Unsigned(v)) => PPValue::Unsigned(v.wrapping_neg()),
+                        ("~", PPValue::Signed(v)) => PPValue::Signed(!v),
+                        ("~", PPValue::Unsigned(v)) => PPValue::Unsigned(!v),
+                        _ => PPValue::from_bool(!val.is_true()),
+                    });
+                }
+                "(" => {
+                    self.pos += 1;
+                    let val = self.expression(live)?;
+                    self.expect(")")?;
+                    return Ok(val);
+                }
+                _ => {}
+            }
+        }
+        self.pos += 1;
+        match tok.kind {
+            PPTokKind::Number => pp_number_value(&tok.spelling),
+            PPTokKind::CharConstant => char_constant_value(&tok.spelling),
+            // identifiers left after the macro expansion are replaced by 0
+            PPTokKind::Identifier => Ok(PPValue::Signed(0)),
+            _ => Err(format!(
+                "token `{}` is not valid in preprocessor expressions",
+                tok.spelling
+            )),
+        }
+    }
+}
+
+fn char_constant_value(spelling: &str) -> Result<PPValue, String> {
+    match lexer::lex(spelling, &mut lexer::Interner::new()) {
+        Ok(ref toks) if toks.len() == 1 => match toks[0].kind {
+            lexer::TokType::IConstant(v, lexer::IntType::UInt) => Ok(PPValue::Unsigned(v as u64)),
+            lexer::TokType::IConstant(v, _) => Ok(PPValue::Signed(v)),
+            _ => Err(format!("invalid character constant {}", spelling)),
+        },
+        _ => Err(format!("invalid character constant {}", spelling)),
+    }
+}
+
+fn eval_binary(op: &str, lhs: PPValue, rhs: PPValue, live: bool) -> Result<PPValue, String> {
+    if (op == "/" || op == "%") && !rhs.is_true() {
+        if live {
+            return Err("division by zero in #if".to_string());
+        }
+        return Ok(PPValue::Signed(0));
+    }
+    if op == "<<" || op == ">>" {
+        // a negative count or one not less than the width is undefined, see C11 6.5.7p3
+        let negative = matches!(rhs, PPValue::Signed(v) if v < 0);
+        if negative || rhs.as_unsigned() >= 64 {
+            if live {
+                return Err(if negative {
+                    "negative shift count in #if".to_string()
+                } else {
+                    "shift count too large in #if".to_string()
+                });
+            }
+            return Ok(match lhs {
+                PPValue::Signed(_) => PPValue::Signed(0),
+                PPValue::Unsigned(_) => PPValue::Unsigned(0),
+            });
+        }
+        // the type of the result is that of the promoted left operand
+        let shift = rhs.as_unsigned() as u32;
+        return Ok(match (op, lhs) {
+            ("<<", PPValue::Signed(v)) => PPValue::Signed(v << shift),
+            ("<<", PPValue::Unsigned(v)) => PPValue::Unsigned(v << shift),
+            (_, PPValue::Signed(v)) => PPValue::Signed(v >> shift),
+            (_, PPValue::Unsigned(v)) => PPValue::Unsigned(v >> shift),
+        });
+    }
+    match (lhs, rhs) {
+        (PPValue::Signed(l), PPValue::Signed(r)) => Ok(match op {
+            "*" => PPValue::Signed(l.wrapping_mul(r)),
+            "/" => PPValue::Signed(l.wrapping_div(r)),
+            "%" => PPValue::Signed(l.wrapping_rem(r)),
+            "+" => PPValue::Signed(l.wrapping_add(r)),
+            "-" => PPValue::Signed(l.wrapping_sub(r)),
+            "&" => PPValue::Signed(l & r),
+            "^" => PPValue::Signed(l ^ r),
+            "|" => PPValue::Signed(l | r),
+            _ => PPValue::from_bool(compare(op, l, r)),
+        }),
+        // the other operand is converted to uintmax_t
+        _ => {
+            let (l, r) = (lhs.as_unsigned(), rhs.as_unsigned());
+            Ok(match op {
+                "*" => PPValue::Unsigned(l.wrapping_mul(r)),
+                "/" => PPValue::Unsigned(l / r),
+                "%" => PPValue::Unsigned(l % r),
+                "+" => PPValue::Unsigned(l.wrapping_add(r)),
+                "-" => PPValue::Unsigned(l.wrapping_sub(r)),
+                "&" => PPValue::Unsigned(l & r),
+                "^" => PPValue::Unsigned(l ^ r),
+                "|" => PPValue::Unsigned(l | r),
+                _ => PPValue::from_bool(compare(op, l, r)),
+            })
+        }
+    }
+}
+
+fn compare<T: PartialOrd>(op: &str, l: T, r: T) -> bool {
+    match op {
+        "<" => l < r,
+        ">" => l > r,
+        "<=" => l <= r,
+        ">=" => l >= r,
+        "==" => l == r,
+        _ => l != r,
+    }
 }
 
-pub fn cpp_driver(input: String, path: PathBuf) -> Result<String, Box<dyn error::Error>> {
-    let parent = path.parent();
-    // include the header files in the source file
-    let after_cpp_str = include_headers(input, parent)?;
-    // first translate trigraph into chars
-    let after_cpp_str = trigraph_processor(after_cpp_str)?;
-    // concatenate lines
-    let after_cpp_str = line_concat(after_cpp_str)?;
-    // remove comment
-    let after_cpp_str = remove_comment(after_cpp_str)?;
-    // directives handler
-    let after_cpp_str = directive_handler(after_cpp_str)?;
+// replace `defined X` and `defined ( X )` by 1 or 0, this should be done before
+// the macro expansion of the controlling expression
+fn replace_defined(
+    toks: Vec<PPToken>,
+    macros: &HashMap<String, Macro>,
+) -> Result<Vec<PPToken>, String> {
+    let mut res = Vec::new();
+    let mut it = toks.into_iter();
+    while let Some(tok) = it.next() {
+        if tok.kind != PPTokKind::Identifier || tok.spelling != "defined" {
+            res.push(tok);
+            continue;
+        }
+        let mut name = it.next();
+        let has_paren = match name {
+            Some(ref t) => t.is_punct("("),
+            None => false,
+        };
+        if has_paren {
+            name = it.next();
+        }
+        let name = match name {
+            Some(ref t) if t.kind == PPTokKind::Identifier => t.spelling.clone(),
+            _ => return Err("operator \"defined\" requires an identifier".to_string()),
+        };
+        if has_paren {
+            match it.next() {
+                Some(ref t) if t.is_punct(")") => {}
+                _ => return Err("missing ')' after \"defined\"".to_string()),
+            }
+        }
+        let is_defined = macros.contains_key(&name);
+        let mut val = PPToken::new(PPTokKind::Number, if is_defined { "1" } else { "0" });
+        val.has_space = tok.has_space;
+        res.push(val);
+    }
+    Ok(res)
+}
+
+fn eval_condition(
+    expr: &str,
+    loc: &PresumedLoc,
+    ctx: &Preprocessor,
+    line: usize,
+) -> Result<bool, Box<dyn error::Error>> {
+    let eval = || -> Result<bool, String> {
+        let toks = replace_defined(pp_tokenize(expr, line), &ctx.macros)?;
+        let env = ExpandEnv { loc, ctx };
+        let toks = expand_macros(&env, toks).map_err(|(_, msg)| msg)?;
+        if toks.is_empty() {
+            return Err("#if with no expression".to_string());
+        }
+        let mut evaluator = CondEvaluator {
+            toks: &toks,
+            pos: 0,
+        };
+        let val = evaluator.expression(true)?;
+        if let Some(tok) = evaluator.peek() {
+            return Err(format!("missing binary operator before `{}`", tok.spelling));
+        }
+        Ok(val.is_true())
+    };
+    eval().map_err(|msg| loc.error(line, &msg))
+}
+
+fn canonical_path(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+// the header name of `#include`, return the name and whether it is in quotes
+fn header_name(rest: &str, env: &ExpandEnv, line: usize) -> Result<(String, bool), String> {
+    let expected = "#include expects \"FILENAME\" or <FILENAME>";
+    let (text, computed) = if rest.starts_with('"') || rest.starts_with('<') {
+        (rest.to_string(), false)
+    } else {
+        // computed include, the macros are expanded first, see C11 6.10.2p4
+        let toks = expand_macros(env, pp_tokenize(rest, line)).map_err(|(_, msg)| msg)?;
+        (pp_tokens_to_text(&toks, line, line), true)
+    };
+    let text = text.trim();
+    let close = match text.chars().next() {
+        Some('"') => '"',
+        Some('<') => '>',
+        _ => return Err(expected.to_string()),
+    };
+    let end = match text[1..].find(close) {
+        Some(end) => end + 1,
+        None => return Err(expected.to_string()),
+    };
+    let name = &text[1..end];
+    if name.is_empty() {
+        return Err("empty filename in #include".to_string());
+    }
+    if !computed && !text[end + 1..].trim().is_empty() {
+        warn!(
+            "{}:{}: extra tokens at end of #include directive",
+            env.loc.file.display(),
+            env.loc.line(line)
+        );
+    }
+    Ok((name.to_string(), close == '"'))
+}
+
+// `#include` and `#include_next`, `found_in` is the directory where the current file was found.
+// Return whether the header was included, it is skipped if it has `#pragma once`.
+fn include_header(
+    directive: &str,
+    rest: &str,
+    path: &Path,
+    found_in: Option<usize>,
+    loc: &PresumedLoc,
+    line: usize,
+    ctx: &mut Preprocessor,
+) -> Result<bool, Box<dyn error::Error>> {
+    let (name, quoted) = {
+        let env = ExpandEnv { loc, ctx };
+        header_name(rest, &env, line).map_err(|msg| loc.error(line, &msg))?
+    };
+    let from = match found_in {
+        Some(idx) if directive == "include_next" => Some(idx),
+        // there is no next directory, it is searched like `#include`, as GCC does
+        None if directive == "include_next" => {
+            let place = if ctx.include_stack.len() <= 1 {
+                "primary source file"
+            } else {
+                "a file not found in the include path"
+            };
+            warn!(
+                "{}:{}: #include_next in {}, searching as #include",
+                loc.file.display(),
+                loc.line(line),
+                place
+            );
+            None
+        }
+        _ => None,
+    };
+    let (header_path, header_found_in) = match ctx.find_header(&name, quoted, path, from) {
+        Some(found) => found,
+        None => return Err(loc.error(line, &format!("{}: No such file or directory", name))),
+    };
+    let canonical = canonical_path(&header_path);
+    if ctx.once_files.contains(&canonical) {
+        debug!("Skip {:?}, it has #pragma once", header_path);
+        return Ok(false);
+    }
+    if let Some(guard) = ctx.guards.get(&canonical) {
+        if ctx.macros.contains_key(guard) {
+            debug!(
+                "Skip {:?}, its include guard {} is defined",
+                header_path, guard
+            );
+            return Ok(false);
+        }
+    }
+    let header_contents = match bundled_header(&header_path) {
+        Some(contents) => contents.to_string(),
+        None => match fs::read_to_string(&header_path) {
+            Ok(contents) => contents,
+            Err(e) => return Err(loc.error(line, &format!("{}: {}", header_path.display(), e))),
+        },
+    };
+    // a file being included again stops only if its include guard is defined by now,
+    // the conditionals of other files are not followed until the maximum depth
+    if let Some(idx) = ctx.include_stack.iter().rposition(|(f, _)| *f == canonical) {
+        let src = SourceText::new(header_contents.clone())?;
+        match Guard::of_text(&src.text) {
+            Some(guard) if ctx.macros.contains_key(&guard) => {
+                debug!(
+                    "Skip {:?}, its include guard {} is defined",
+                    header_path, guard
+                );
+                return Ok(false);
+            }
+            _ => {
+                let msg = format!("#include cycle: {}", ctx.include_chain(idx, &header_path));
+                return Err(loc.error(line, &msg));
+            }
+        }
+    }
+    if ctx.include_stack.len() > ctx.max_include_depth {
+        let mut msg = format!(
+            "#include nested depth {} exceeds maximum of {}",
+            ctx.include_stack.len() - 1,
+            ctx.max_include_depth
+        );
+        if let Some(idx) = ctx.include_stack.iter().rposition(|(f, _)| *f == canonical) {
+            msg.push_str(&format!(
+                ", in the cycle {}",
+                ctx.include_chain(idx, &header_path)
+            ));
+        }
+        return Err(loc.error(line, &msg));
+    }
+    let system = loc.system || ctx.is_system_header(header_found_in);
+    if bundled_header(&header_path).is_none()
+        && !ctx.dependencies.iter().any(|(dep, _)| *dep == header_path)
+    {
+        ctx.dependencies.push((header_path.clone(), system));
+    }
+    let header_loc = PresumedLoc::new(&header_path, system, &mut ctx.map);
+    ctx.mark_line(&header_loc, 1, Some(1));
+    ctx.include_stack.push((canonical, header_path.clone()));
+    preprocess_file(header_contents, &header_path, header_found_in, system, ctx)?;
+    ctx.include_stack.pop();
+    Ok(true)
+}
+
+// the name of `#define`, `#undef`, `#ifdef` and `#ifndef`
+fn macro_name(toks: &[PPToken], directive: &str) -> Result<String, String> {
+    match toks.first() {
+        None => Err(format!("no macro name given in #{} directive", directive)),
+        Some(tok) if tok.kind != PPTokKind::Identifier => {
+            Err("macro names must be identifiers".to_string())
+        }
+        Some(tok) if tok.spelling == "defined" => {
+            Err("\"defined\" cannot be used as a macro name".to_string())
+        }
+        Some(tok) => Ok(tok.spelling.clone()),
+    }
+}
+
+// `# line digit-sequence "s-char-sequence"opt`, after macro expansion, see C11 6.10.4
+fn parse_line_directive(toks: &[PPToken]) -> Result<(usize, Option<String>), String> {
+    let line = match toks.first() {
+        None => return Err("#line directive requires a simple digit sequence".to_string()),
+        Some(tok) => tok,
+    };
+    if line.kind != PPTokKind::Number || !line.spelling.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!(
+            "\"{}\" after #line is not a positive integer",
+            line.spelling
+        ));
+    }
+    let line_no = match line.spelling.parse::<usize>() {
+        Ok(n) if (1..=2147483647).contains(&n) => n,
+        _ => return Err("line number out of range".to_string()),
+    };
+    let file = match toks.get(1) {
+        None => None,
+        Some(tok) if tok.kind == PPTokKind::StringLiteral && tok.spelling.starts_with('"') => {
+            Some(tok.spelling[1..tok.spelling.len() - 1].to_string())
+        }
+        Some(tok) => return Err(format!("invalid filename \"{}\"", tok.spelling)),
+    };
+    if toks.len() > 2 {
+        return Err("extra tokens at end of #line directive".to_string());
+    }
+    Ok((line_no, file))
+}
+
+// expand the macros in a block of text lines, and append it to the output
+fn expand_text(
+    toks: Vec<PPToken>,
+    first_line: usize,
+    last_line: usize,
+    loc: &PresumedLoc,
+    ctx: &mut Preprocessor,
+) -> ExpandResult<()> {
+    let toks = {
+        let env = ExpandEnv { loc, ctx };
+        expand_macros(&env, toks)?
+    };
+    let tokens = &mut ctx.map.tokens;
+    write_tokens(
+        &mut ctx.output,
+        &toks,
+        first_line,
+        last_line,
+        &mut |offset, tok| tokens.push((offset, loc.source_loc(tok), tok.expansion)),
+    );
+    Ok(())
+}
+
+// the output of the file is appended to `ctx.output`
+fn directive_handler(
+    src: &SourceText,
+    path: &Path,
+    found_in: Option<usize>,
+    system: bool,
+    ctx: &mut Preprocessor,
+) -> Result<(), Box<dyn error::Error>> {
+    let mut loc = PresumedLoc::new(path, system, &mut ctx.map);
+    let mut conds: Vec<Conditional> = Vec::new();
+    // the text lines are expanded together until the next directive, so
+    // the arguments of a macro invocation can span several lines
+    let mut text: Vec<PPToken> = Vec::new();
+    let mut text_from = 0;
+    let mut line_no = 0;
+    let mut line_start = 0;
+    let mut guard = Guard::Start;
+
+    for raw_line in src.text.split_inclusive('\n') {
+        let line_off = line_start;
+        line_start += raw_line.len();
+        line_no += 1;
+        let line = raw_line.trim_end_matches('\n').trim_end_matches('\r');
+        guard = guard.next(line, conds.len());
+        let tokenize = |line: &str| pp_tokenize_at(line, &|b| src.position(line_off + b));
+        if !is_directive(line) {
+            if is_active(&conds) {
+                if text_from == 0 {
+                    text_from = line_no;
+                }
+                text.extend(tokenize(line));
+            } else {
+                ctx.output.push('\n');
+            }
+            continue;
+        }
+        if text_from != 0 {
+            let toks = std::mem::take(&mut text);
+            expand_text(toks, text_from, line_no - 1, &loc, ctx)
+                .map_err(|(line, msg)| loc.error(line, &msg))?;
+            text_from = 0;
+        }
+
+        let (directive, rest) = split_directive(line);
+        match directive {
+            "if" | "ifdef" | "ifndef" => {
+                let parent_active = is_active(&conds);
+                // the condition of a skipped group is not evaluated at all
+                let active = parent_active
+                    && match directive {
+                        "ifdef" | "ifndef" => {
+                            let toks = pp_tokenize(rest, line_no);
+                            let name = macro_name(&toks, directive)
+                                .map_err(|msg| loc.error(line_no, &msg))?;
+                            ctx.macros.contains_key(&name) == (directive == "ifdef")
+                        }
+                        _ => eval_condition(rest, &loc, ctx, line_no)?,
+                    };
+                conds.push(Conditional {
+                    parent_active,
+                    taken: active,
+                    active,
+                    seen_else: false,
+                    line: line_no,
+                });
+            }
+            "elif" => {
+                let cond = match conds.last_mut() {
+                    Some(cond) => cond,
+                    None => return Err(loc.error(line_no, "#elif without #if")),
+                };
+                if cond.seen_else {
+                    return Err(loc.error(line_no, "#elif after #else"));
+                }
+                cond.active =
+                    cond.parent_active && !cond.taken && eval_condition(rest, &loc, ctx, line_no)?;
+                cond.taken = cond.taken || cond.active;
+            }
+            "else" => {
+                let cond = match conds.last_mut() {
+                    Some(cond) => cond,
+                    None => return Err(loc.error(line_no, "#else without #if")),
+                };
+                if cond.seen_else {
+                    return Err(loc.error(line_no, "#else after #else"));
+                }
+                cond.seen_else = true;
+                cond.active = cond.parent_active && !cond.taken;
+                cond.taken = true;
+            }
+            "endif" => {
+                if conds.pop().is_none() {
+                    return Err(loc.error(line_no, "#endif without #if"));
+                }
+            }
+            _ if !is_active(&conds) => {
+                // other directives in a skipped group are ignored
+            }
+            "include" | "include_next" => {
+                if include_header(directive, rest, path, found_in, &loc, line_no, ctx)? {
+                    // the line marker takes the place of the line of the directive
+                    ctx.mark_line(&loc, line_no + 1, Some(2));
+                    continue;
+                }
+            }
+            "define" => {
+                // skip `#` and `define`, the tokens keep their columns in the line
+                let toks: Vec<PPToken> = tokenize(line).into_iter().skip(2).collect();
+                let defined_at = toks.first().map(|tok| loc.source_loc(tok));
+                let (name, mut m) = parse_define(toks).map_err(|msg| loc.error(line_no, &msg))?;
+                m.defined_at = defined_at;
+                debug!("Macro detected: name:{:?} => {:?}", name, m);
+
+                if let Some(old) = ctx.macros.get(&name) {
+                    if !old.is_same(&m) {
+                        warn!(
+                            "{}:{}: \"{}\" redefined",
+                            loc.file.display(),
+                            loc.line(line_no),
+                            name
+                        );
+                    }
+                }
+                ctx.macros.insert(name, m);
+            }
+            "undef" => {
+                let toks = pp_tokenize(rest, line_no);
+                let name = macro_name(&toks, directive).map_err(|msg| loc.error(line_no, &msg))?;
+                if toks.len() > 1 {
+                    warn!(
+                        "{}:{}: extra tokens at end of #undef directive",
+                        loc.file.display(),
+                        loc.line(line_no)
+                    );
+                }
+                debug!("Macro undefined: {:?}", name);
+                ctx.macros.remove(&name);
+            }
+            "line" => {
+                let toks = {
+                    let env = ExpandEnv { loc: &loc, ctx };
+                    expand_macros(&env, pp_tokenize(rest, line_no))
+                        .map_err(|(_, msg)| loc.error(line_no, &msg))?
+                };
+                let (presumed_line, file) =
+                    parse_line_directive(&toks).map_err(|msg| loc.error(line_no, &msg))?;
+                // the line after the directive gets the given line number
+                loc.line_delta = presumed_line as isize - (line_no as isize + 1);
+                if let Some(file) = file {
+                    loc.file = PathBuf::from(file);
+                    loc.file_id = ctx.map.file_id(&loc.file);
+                }
+                ctx.output.push('\n');
+                ctx.mark_line(&loc, line_no + 1, None);
+                continue;
+            }
+            "error" => {
+                return Err(loc.error(line_no, &format!("#error {}", rest)));
+            }
+            "warning" => {
+                warn!(
+                    "{}:{}: warning: #warning {}",
+                    loc.file.display(),
+                    loc.line(line_no),
+                    rest
+                );
+            }
+            "pragma" => {
+                if rest == "once" {
+                    ctx.once_files.insert(canonical_path(path));
+                } else {
+                    // the other pragmas are left to the later stages, at the position
+                    // of their `#`
+                    let indent = line.len() - line.trim_start().len();
+                    let (_, col) = src.position(line_off + indent);
+                    let pragma_loc = SourceLoc {
+                        file: loc.file_id,
+                        line: loc.line(line_no),
+                        col,
+                    };
+                    ctx.map.tokens.push((ctx.output.len(), pragma_loc, None));
+                    ctx.output.push_str("#pragma ");
+                    ctx.output.push_str(rest);
+                }
+            }
+            "" => {
+                // the null directive has no effect
+            }
+            _ => {
+                return Err(loc.error(
+                    line_no,
+                    &format!("invalid preprocessing directive #{}", directive),
+                ));
+            }
+        }
+        // keep the line of the directive, so the line numbers of the output
+        // still match the source file
+        ctx.output.push('\n');
+    }
+
+    if text_from != 0 {
+        expand_text(text, text_from, line_no, &loc, ctx)
+            .map_err(|(line, msg)| loc.error(line, &msg))?;
+    }
+
+    if let Some(cond) = conds.last() {
+        return Err(loc.error(cond.line, "unterminated conditional directive"));
+    }
+    if let Guard::Closed(name) = guard {
+        ctx.guards.insert(canonical_path(path), name);
+    }
+    Ok(())
+}
+
+// run the translation phases 1 to 4 on one source file, the included headers
+// are processed recursively by the directive handler.
+fn preprocess_file(
+    input: String,
+    path: &Path,
+    found_in: Option<usize>,
+    system: bool,
+    ctx: &mut Preprocessor,
+) -> Result<(), Box<dyn error::Error>> {
+    // trigraphs, line splicing and comments
+    let src = SourceText::new(input)?;
+    // directives handler, the header files are included here
+    directive_handler(&src, path, found_in, system, ctx)
+}
+
+impl Preprocessor {
+    // run the preprocessor on the source file `path`, the macros defined in it are
+    // kept, see `dump_macros`
+    pub fn preprocess(
+        &mut self,
+        input: String,
+        path: PathBuf,
+    ) -> Result<CppOutput, Box<dyn error::Error>> {
+        let command_line = Path::new(COMMAND_LINE);
+        // the output of -D, -U and -imacros is thrown away, only their macros are kept
+        preprocess_file(
+            command_line_directives(&self.opts),
+            command_line,
+            None,
+            false,
+            self,
+        )?;
+        self.output.clear();
+        self.map.tokens.clear();
+        self.map.markers.clear();
+        if !self.opts.includes.is_empty() {
+            // -include is searched in the working directory first, as `#include "..."`
+            // in the command line
+            let includes: String = self
+                .opts
+                .includes
+                .iter()
+                .map(|file| format!("#include \"{}\"\n", file))
+                .collect();
+            let loc = PresumedLoc::new(command_line, false, &mut self.map);
+            self.mark_line(&loc, 1, None);
+            preprocess_file(includes, command_line, None, false, self)?;
+        }
+        let loc = PresumedLoc::new(&path, false, &mut self.map);
+        self.mark_line(&loc, 1, None);
+        self.dependencies.insert(0, (path.clone(), false));
+        self.include_stack
+            .push((canonical_path(&path), path.clone()));
+        preprocess_file(input, &path, None, false, self)?;
+        self.include_stack.pop();
+        let mut source_map = std::mem::take(&mut self.map);
+        source_map.expansions = self.expansions.take();
+        Ok(CppOutput {
+            text: std::mem::take(&mut self.output),
+            source_map,
+            dependencies: std::mem::take(&mut self.dependencies),
+        })
+    }
+
+    // `#define` lines of all the macros except the dynamic ones, sorted by name,
+    // like `gcc -dM -E`
+    pub fn dump_macros(&self) -> String {
+        let macros = &self.macros;
+        let mut names: Vec<&String> = macros
+            .iter()
+            .filter(|(_, m)| m.builtin.is_none())
+            .map(|(name, _)| name)
+            .collect();
+        names.sort();
+        let mut res = String::new();
+        for name in names {
+            res.push_str("#define ");
+            res.push_str(&macros[name].definition(name));
+            res.push('\n');
+        }
+        res
+    }
+}
 
-    Ok(after_cpp_str)
+// preprocess one translation unit with a new `Preprocessor`
+#[allow(dead_code)]
+pub fn cpp_driver(
+    input: String,
+    path: PathBuf,
+    opts: &CppOptions,
+) -> Result<CppOutput, Box<dyn error::Error>> {
+    Preprocessor::new(opts).preprocess(input, path)
 }
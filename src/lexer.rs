@@ -15,11 +15,8 @@
 // lexer.rs: lexer for c11 tokens.
 //           some situations should be added later.
 // ------------------------------------------------------------------------
-// TODO: 1. add token information for error message.
-//       2. seperate each TokType to their type, now just a global type TokType.
-//       3. add some check in lexer for enum and typedef.
-//       4. add floating point number support.
-//       5. number with postfix.
+// TODO: 1. seperate each TokType to their type, now just a global type TokType.
+//       2. add some check in lexer for enum and typedef.
 
 #[allow(dead_code)]
 #[derive(PartialEq, Clone, Debug)]
@@ -48,36 +45,37 @@ pub enum TokType {
     InclusiveOr,  // |
     ExclusiveOr,  // ^
     Mod,          // %
-    IDENTIFIER(String),
-    IConstant(i64),
-    FConstant(f64),
-    StringLiteral(String, String),
-    FuncName,    // __func__
-    SIZEOF,      // sizeof
-    PtrOp,       // ->
-    IncOp,       // ++
-    DecOp,       // --
-    LeftOp,      // <<
-    RightOp,     // >>
-    LeOp,        // <=
-    GeOp,        // >=
-    EqOp,        // ==
-    NeOp,        // !=
-    AndOp,       // &&
-    OrOp,        // ||
-    MulAssign,   // *=
-    DivAssign,   // /=
-    ModAssign,   // %=
-    AddAssign,   // +=
-    SubAssign,   // -=
-    LeftAssign,  // <<=
-    RightAssign, // >>=
-    AndAssign,   // &=
-    XorAssign,   // ^=
-    OrAssign,    // |=
-    // TODO: this should be done when we found this is a typedef name,
-    //       typedef LL int, then LL is typedef_name
-    TypedefName,
+    // the name is in the `Interner` of the tokens
+    IDENTIFIER(Symbol),
+    IConstant(i64, IntType), // the unsigned values are kept as their bits
+    FConstant(f64, FloatType),
+    StringLiteral(Encoding), // the spelling is the text of its span
+    FuncName,                // __func__
+    SIZEOF,                  // sizeof
+    PtrOp,                   // ->
+    IncOp,                   // ++
+    DecOp,                   // --
+    LeftOp,                  // <<
+    RightOp,                 // >>
+    LeOp,                    // <=
+    GeOp,                    // >=
+    EqOp,                    // ==
+    NeOp,                    // !=
+    AndOp,                   // &&
+    OrOp,                    // ||
+    MulAssign,               // *=
+    DivAssign,               // /=
+    ModAssign,               // %=
+    AddAssign,               // +=
+    SubAssign,               // -=
+    LeftAssign,              // <<=
+    RightAssign,             // >>=
+    AndAssign,               // &=
+    XorAssign,               // ^=
+    OrAssign,                // |=
+    // an identifier declared by `typedef`, the parser classifies the identifiers by
+    // their declarations in scope, the lexer does not make it
+    TypedefName(String),
     ELLIPSIS,                    // ...
     EnumerationConstant(String), // TODO: add check
     TYPEDEF,
@@ -123,484 +121,1361 @@ pub enum TokType {
     NORETURN,
     StaticAssert,
     ThreadLocal,
+    Pragma(String), // #pragma forwarded by the preprocessor, the rest of the line
+    Error,          // in place of the characters of a lexical error
 }
 
-use std::sync::atomic;
+// the spelling of a token, like `<<=` or `while`
+impl fmt::Display for TokType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let spelling = match self {
+            TokType::LBrace => "{",
+            TokType::RBrace => "}",
+            TokType::LParen => "(",
+            TokType::RParen => ")",
+            TokType::LBracket => "[",
+            TokType::RBracket => "]",
+            TokType::Semicolon => ";",
+            TokType::Assign => "=",
+            TokType::Lt => "<",
+            TokType::Gt => ">",
+            TokType::Minus => "-",
+            TokType::Tilde => "~",
+            TokType::Exclamation => "!",
+            TokType::Plus => "+",
+            TokType::Multi => "*",
+            TokType::Splash => "/",
+            TokType::Colon => ":",
+            TokType::QuestionMark => "?",
+            TokType::Comma => ",",
+            TokType::Dot => ".",
+            TokType::SingleAnd => "&",
+            TokType::InclusiveOr => "|",
+            TokType::ExclusiveOr => "^",
+            TokType::Mod => "%",
+            TokType::FuncName => "__func__",
+            TokType::SIZEOF => "sizeof",
+            TokType::PtrOp => "->",
+            TokType::IncOp => "++",
+            TokType::DecOp => "--",
+            TokType::LeftOp => "<<",
+            TokType::RightOp => ">>",
+            TokType::LeOp => "<=",
+            TokType::GeOp => ">=",
+            TokType::EqOp => "==",
+            TokType::NeOp => "!=",
+            TokType::AndOp => "&&",
+            TokType::OrOp => "||",
+            TokType::MulAssign => "*=",
+            TokType::DivAssign => "/=",
+            TokType::ModAssign => "%=",
+            TokType::AddAssign => "+=",
+            TokType::SubAssign => "-=",
+            TokType::LeftAssign => "<<=",
+            TokType::RightAssign => ">>=",
+            TokType::AndAssign => "&=",
+            TokType::XorAssign => "^=",
+            TokType::OrAssign => "|=",
+            TokType::ELLIPSIS => "...",
+            TokType::TYPEDEF => "typedef",
+            TokType::EXTERN => "extern",
+            TokType::STATIC => "static",
+            TokType::AUTO => "auto",
+            TokType::REGISTER => "register",
+            TokType::INLINE => "inline",
+            TokType::CONST => "const",
+            TokType::RESTRICT => "restrict",
+            TokType::VOLATILE => "volatile",
+            TokType::BOOL => "_Bool",
+            TokType::CHAR => "char",
+            TokType::SHORT => "short",
+            TokType::INT => "int",
+            TokType::LONG => "long",
+            TokType::SIGNED => "signed",
+            TokType::UNSIGNED => "unsigned",
+            TokType::FLOAT => "float",
+            TokType::DOUBLE => "double",
+            TokType::VOID => "void",
+            TokType::COMPLEX => "_Complex",
+            TokType::IMAGINARY => "_Imaginary",
+            TokType::STRUCT => "struct",
+            TokType::UNION => "union",
+            TokType::ENUM => "enum",
+            TokType::CASE => "case",
+            TokType::DEFAULT => "default",
+            TokType::IF => "if",
+            TokType::ELSE => "else",
+            TokType::SWITCH => "switch",
+            TokType::WHILE => "while",
+            TokType::DO => "do",
+            TokType::FOR => "for",
+            TokType::GOTO => "goto",
+            TokType::CONTINUE => "continue",
+            TokType::BREAK => "break",
+            TokType::RETURN => "return",
+            TokType::ALIGNAS => "_Alignas",
+            TokType::ALIGNOF => "_Alignof",
+            TokType::ATOMIC => "_Atomic",
+            TokType::GENERIC => "_Generic",
+            TokType::NORETURN => "_Noreturn",
+            TokType::StaticAssert => "_Static_assert",
+            TokType::ThreadLocal => "_Thread_local",
+            // `Interner::spelling` has the name
+            TokType::IDENTIFIER(_) => "<identifier>",
+            TokType::TypedefName(name) | TokType::EnumerationConstant(name) => name,
+            TokType::IConstant(value, int_type) => match int_type {
+                IntType::UInt | IntType::ULong | IntType::ULongLong => {
+                    return write!(f, "{}", *value as u64)
+                }
+                _ => return write!(f, "{}", value),
+            },
+            TokType::FConstant(value, _) => return write!(f, "{}", value),
+            TokType::StringLiteral(_) => "<string literal>",
+            TokType::Pragma(text) => return write!(f, "#pragma {}", text),
+            TokType::Error => "<error>",
+        };
+        write!(f, "{}", spelling)
+    }
+}
 
-static LABEL_COUNTER: atomic::AtomicUsize = atomic::AtomicUsize::new(0);
-fn gen_string_tag() -> String {
-    let label_counter = LABEL_COUNTER.fetch_add(1, atomic::Ordering::SeqCst);
-    let label = format!(".LSTR{}", label_counter);
+// Where a token is: its bytes in the input of the lexer, and its position in the
+// source. `line` and `col` start at 1 and count the lines and the bytes of the input,
+// and `file` is 0, until `cpp::SourceMap::locate` maps them to the source files.
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub struct Span {
+    pub offset: usize,
+    pub len: usize,
+    pub line: usize,
+    pub col: usize,
+    pub file: usize,
+}
 
-    label
+#[derive(PartialEq, Clone, Debug)]
+pub struct Token {
+    pub kind: TokType,
+    pub span: Span,
+    // whether there is whitespace before the token, and whether it is the first
+    // token of its line
+    pub leading_space: bool,
+    pub start_of_line: bool,
 }
 
-pub fn lex(input: &str) -> Result<Vec<TokType>, String> {
-    let mut result = Vec::new();
+// A lexical error, and where it is.
+#[derive(PartialEq, Clone, Debug)]
+pub struct LexError {
+    pub span: Span,
+    pub message: String,
+}
 
-    let mut it = input.chars().peekable();
+// the type of an integer constant, see C11 6.4.4.1p5
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum IntType {
+    Int,
+    UInt,
+    Long,
+    ULong,
+    LongLong,
+    ULongLong,
+}
 
-    while let Some(&c) = it.peek() {
-        match c {
-            '"' => {
-                it.next();
-                let mut s = "".to_string();
-                loop {
-                    let &c = it.peek().unwrap();
-                    if c == '"' {
-                        break;
-                    }
-                    s.push(c);
-                    it.next();
-                }
-                result.push(TokType::StringLiteral(s, gen_string_tag()));
-                it.next();
+impl IntType {
+    // the largest value of the type, `int` has 32 bits, `long` and `long long` 64
+    fn max_value(self) -> u64 {
+        match self {
+            IntType::Int => i32::MAX as u64,
+            IntType::UInt => u32::MAX as u64,
+            IntType::Long | IntType::LongLong => i64::MAX as u64,
+            IntType::ULong | IntType::ULongLong => u64::MAX,
+        }
+    }
+}
+
+// The value and the type of an integer constant: a decimal, octal (`0755`), hexadecimal
+// (`0x1F`) or binary (`0b101`, a GNU extension) number, with an optional `u`, `l` or
+// `ll` suffix. The type is the first one of the list of C11 6.4.4.1p5 which can
+// represent the value.
+pub fn parse_integer(spelling: &str) -> Result<(u64, IntType), String> {
+    let lower = spelling.to_ascii_lowercase();
+    let (radix, digits, name) = if let Some(hex) = lower.strip_prefix("0x") {
+        (16, hex, "hexadecimal")
+    } else if let Some(bin) = lower.strip_prefix("0b") {
+        (2, bin, "binary")
+    } else if lower.starts_with('0') {
+        (8, &lower[..], "octal")
+    } else {
+        (10, &lower[..], "decimal")
+    };
+    let digits_end = digits
+        .find(|c: char| !(c.is_ascii_digit() || c.is_digit(radix)))
+        .unwrap_or(digits.len());
+    let (digits, suffix) = digits.split_at(digits_end);
+    let spelled_suffix = &spelling[spelling.len() - suffix.len()..];
+    if let Some(c) = digits.chars().find(|c| !c.is_digit(radix)) {
+        return Err(format!("invalid digit \"{}\" in {} constant", c, name));
+    }
+    // `lL` and `Ll` are not valid suffixes
+    let (unsigned, long) = match suffix {
+        "" => (false, 0),
+        "u" => (true, 0),
+        "l" => (false, 1),
+        "ul" | "lu" => (true, 1),
+        "ll" if spelled_suffix != "lL" && spelled_suffix != "Ll" => (false, 2),
+        "ull" | "llu" if !spelled_suffix.contains("lL") && !spelled_suffix.contains("Ll") => {
+            (true, 2)
+        }
+        _ => {
+            return Err(format!(
+                "invalid suffix \"{}\" on integer constant",
+                spelled_suffix
+            ))
+        }
+    };
+    if digits.is_empty() && radix != 8 {
+        return Err(format!(
+            "invalid suffix \"{}\" on integer constant",
+            &spelling[1..]
+        ));
+    }
+    let value = match u64::from_str_radix(if digits.is_empty() { "0" } else { digits }, radix) {
+        Ok(value) => value,
+        Err(_) => {
+            return Err(format!(
+                "integer constant {} is too large for its type",
+                spelling
+            ))
+        }
+    };
+    // the decimal constants without `u` are never unsigned
+    let candidates: &[IntType] = match (radix == 10, unsigned, long) {
+        (true, false, 0) => &[IntType::Int, IntType::Long, IntType::LongLong],
+        (true, false, 1) => &[IntType::Long, IntType::LongLong],
+        (true, false, _) => &[IntType::LongLong],
+        (false, false, 0) => &[
+            IntType::Int,
+            IntType::UInt,
+            IntType::Long,
+            IntType::ULong,
+            IntType::LongLong,
+            IntType::ULongLong,
+        ],
+        (false, false, 1) => &[
+            IntType::Long,
+            IntType::ULong,
+            IntType::LongLong,
+            IntType::ULongLong,
+        ],
+        (false, false, _) => &[IntType::LongLong, IntType::ULongLong],
+        (_, true, 0) => &[IntType::UInt, IntType::ULong, IntType::ULongLong],
+        (_, true, 1) => &[IntType::ULong, IntType::ULongLong],
+        (_, true, _) => &[IntType::ULongLong],
+    };
+    match candidates.iter().find(|t| value <= t.max_value()) {
+        Some(&t) => Ok((value, t)),
+        None => {
+            // like GCC, a decimal constant too large for `long long` is unsigned
+            warn!(
+                "integer constant {} is so large that it is unsigned",
+                spelling
+            );
+            Ok((value, IntType::ULongLong))
+        }
+    }
+}
+
+// the type of a floating constant, see C11 6.4.4.2p4
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum FloatType {
+    Float,
+    Double,
+    // TODO: the value is only as precise as a double now
+    LongDouble,
+}
+
+// The value and the type of a floating constant: a decimal one like `1.5e-3`, or a
+// hexadecimal one like `0x1.8p3`, with an optional `f` or `l` suffix. The value is
+// correctly rounded to the type, a `float` value is kept in an f64 exactly.
+pub fn parse_float(spelling: &str) -> Result<(f64, FloatType), String> {
+    let (body, float_type) = match spelling.chars().last() {
+        Some('f') | Some('F') => (&spelling[..spelling.len() - 1], FloatType::Float),
+        Some('l') | Some('L') => (&spelling[..spelling.len() - 1], FloatType::LongDouble),
+        _ => (spelling, FloatType::Double),
+    };
+    let is_hex = body.starts_with("0x") || body.starts_with("0X");
+    let value = if is_hex {
+        parse_hex_float(&body[2..], float_type)?
+    } else {
+        parse_decimal_float(body, float_type)?
+    };
+    if value.is_infinite() {
+        warn!(
+            "floating constant {} exceeds the range of its type",
+            spelling
+        );
+    }
+    Ok((value, float_type))
+}
+
+fn parse_decimal_float(body: &str, float_type: FloatType) -> Result<f64, String> {
+    let (mantissa, exponent) = match body.find(['e', 'E']) {
+        Some(idx) => (&body[..idx], Some(&body[idx + 1..])),
+        None => (body, None),
+    };
+    let valid_mantissa = mantissa.chars().all(|c| c.is_ascii_digit() || c == '.')
+        && mantissa.matches('.').count() <= 1
+        && mantissa != ".";
+    if !valid_mantissa {
+        return Err(format!("invalid floating constant {}", body));
+    }
+    if let Some(exponent) = exponent {
+        let digits = exponent.trim_start_matches(['+', '-']);
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return Err(format!("exponent has no digits in {}", body));
+        }
+    }
+    // the conversions of the standard library are correctly rounded
+    let value = match float_type {
+        FloatType::Float => body.parse::<f32>().map(f64::from),
+        _ => body.parse::<f64>(),
+    };
+    value.map_err(|_| format!("invalid floating constant {}", body))
+}
+
+// `1.8p3` of `0x1.8p3`, the exponent is required
+fn parse_hex_float(body: &str, float_type: FloatType) -> Result<f64, String> {
+    let (mantissa, exponent) = match body.find(['p', 'P']) {
+        Some(idx) => (&body[..idx], &body[idx + 1..]),
+        None => return Err("hexadecimal floating constants require an exponent".to_string()),
+    };
+    let (negative, digits) = match exponent.as_bytes().first() {
+        Some(b'-') => (true, &exponent[1..]),
+        Some(b'+') => (false, &exponent[1..]),
+        _ => (false, exponent),
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("exponent has no digits in 0x{}", body));
+    }
+    // a larger exponent is out of the range of all the types, whatever the digits are
+    let magnitude = digits.parse::<i64>().unwrap_or(i64::MAX).min(1 << 32);
+    let exponent = if negative { -magnitude } else { magnitude };
+    if mantissa.is_empty() || mantissa == "." || mantissa.matches('.').count() > 1 {
+        return Err(format!("invalid floating constant 0x{}", body));
+    }
+    // the first 64 bits of the digits, and whether any of the other bits is set
+    let mut bits: u64 = 0;
+    let mut sticky = false;
+    let mut exp2 = exponent;
+    let mut after_point = false;
+    for c in mantissa.chars() {
+        if c == '.' {
+            after_point = true;
+            continue;
+        }
+        let digit = match c.to_digit(16) {
+            Some(digit) => u64::from(digit),
+            None => return Err(format!("invalid digit \"{}\" in hexadecimal constant", c)),
+        };
+        if bits >> 60 == 0 {
+            bits = bits << 4 | digit;
+            if after_point {
+                exp2 -= 4;
             }
-            '\'' => {
-                // try parse a char
-                it.next(); // skip '
-                let &c = it.peek().unwrap();
-                if c == '\'' {
-                    return Err(format!("Error: empty character constant"));
-                }
-                if c == '\\' {
-                    it.next();
-                    let &c = it.peek().unwrap();
-                    match c {
-                        'a' => {
-                            result.push(TokType::IConstant(0x07));
-                        } // Alert (Beep, Bell) (added in C89)
-                        'b' => {
-                            result.push(TokType::IConstant(0x08));
-                        } // Backspace
-                        'e' => {
-                            result.push(TokType::IConstant(0x1B));
-                        } // escape character
-                        'f' => {
-                            result.push(TokType::IConstant(0x0C));
-                        } // Formfeed Page Break
-                        'n' => {
-                            result.push(TokType::IConstant(0x0A));
-                        } // Newline (Line Feed)
-                        'r' => {
-                            result.push(TokType::IConstant(0x0D));
-                        } // Carriage Return
-                        't' => {
-                            result.push(TokType::IConstant(0x09));
-                        } // Horizontal Tab
-                        'v' => {
-                            result.push(TokType::IConstant(0x0B));
-                        } // Vertical Tab
-                        '\\' => {
-                            result.push(TokType::IConstant(0x5C));
-                        } // Backslash
-                        '\'' => {
-                            result.push(TokType::IConstant(0x27));
-                        } // Apostrophe or single quotation mark
-                        '\"' => {
-                            result.push(TokType::IConstant(0x22));
-                        } // Double quotation mark
-                        '?' => {
-                            result.push(TokType::IConstant(0x3F));
-                        } // question mark
-                        _ => {
-                            return Err(format!("unrecongnized character"));
-                        }
-                    }
-                    it.next();
-                    if it.peek().unwrap() != &'\'' {
-                        return Err(format!("Error: unmatched '"));
-                    }
-                    it.next();
-                } else {
-                    result.push(TokType::IConstant(c as i64));
-                    it.next(); // skip char
-                    it.next(); // skip '
-                }
+        } else {
+            sticky |= digit != 0;
+            if !after_point {
+                exp2 += 4;
             }
-            '0'..='9' => {
-                it.next();
-                let mut number = c
-                    .to_string()
-                    .parse::<i64>()
-                    .expect("The caller should have passed a digit.");
-
-                while let Some(Ok(digit)) = it.peek().map(|c| c.to_string().parse::<i64>()) {
-                    number = number * 10 + digit;
-                    it.next();
+        }
+    }
+    let (precision, min_exp, max_exp) = match float_type {
+        FloatType::Float => (24, -149, 127),
+        _ => (53, -1074, 1023),
+    };
+    let value = round_to_float(bits, sticky, exp2, precision, (min_exp, max_exp));
+    Ok(match float_type {
+        // exact, or infinity if it is too large for a float
+        FloatType::Float => f64::from(value as f32),
+        _ => value,
+    })
+}
+
+// `bits * 2^exp2` rounded to nearest even with `precision` bits, `min_exp` is the
+// exponent of the smallest subnormal number and `max_exp` that of the largest number
+fn round_to_float(
+    mut bits: u64,
+    sticky: bool,
+    mut exp2: i64,
+    precision: i64,
+    (min_exp, max_exp): (i64, i64),
+) -> f64 {
+    if bits == 0 {
+        return 0.0;
+    }
+    let len = 64 - i64::from(bits.leading_zeros());
+    let shift = (len - precision).max(min_exp - exp2);
+    if shift > 0 {
+        if shift > 64 {
+            return 0.0;
+        }
+        let dropped = if shift == 64 {
+            bits
+        } else {
+            bits & ((1 << shift) - 1)
+        };
+        let half = 1u64 << (shift - 1);
+        bits = if shift == 64 { 0 } else { bits >> shift };
+        exp2 += shift;
+        if dropped > half || (dropped == half && (sticky || bits & 1 == 1)) {
+            bits += 1;
+        }
+    }
+    if 63 - i64::from(bits.leading_zeros()) + exp2 > max_exp {
+        return f64::INFINITY;
+    }
+    // `bits` fits in the precision now, so the products are exact until they overflow
+    let mut value = bits as f64;
+    while exp2 > 0 {
+        let step = exp2.min(1000);
+        value *= 2f64.powi(step as i32);
+        exp2 -= step;
+    }
+    while exp2 < 0 {
+        let step = exp2.max(-1000);
+        value *= 2f64.powi(step as i32);
+        exp2 -= step;
+    }
+    value
+}
+
+// the encoding prefix of a character constant or a string literal, see C11 6.4.4.4
+// and 6.4.5
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Encoding {
+    Char,  // no prefix, a multibyte (UTF-8) string
+    Utf8,  // u8, strings only
+    Wide,  // L, wchar_t is a 32 bits int
+    Utf16, // u, char16_t
+    Utf32, // U, char32_t
+}
+
+impl Encoding {
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Encoding::Char => "",
+            Encoding::Utf8 => "u8",
+            Encoding::Wide => "L",
+            Encoding::Utf16 => "u",
+            Encoding::Utf32 => "U",
+        }
+    }
+
+    // the largest value of a code unit
+    fn max_unit(self) -> u32 {
+        match self {
+            Encoding::Char | Encoding::Utf8 => 0xFF,
+            Encoding::Utf16 => 0xFFFF,
+            Encoding::Wide | Encoding::Utf32 => 0xFFFF_FFFF,
+        }
+    }
+
+    // the encoding of the concatenation of two adjacent string literals, C11 6.4.5p5
+    fn concat(self, other: Encoding) -> Result<Encoding, String> {
+        match (self, other) {
+            (Encoding::Char, _) => Ok(other),
+            (_, Encoding::Char) => Ok(self),
+            _ if self == other => Ok(self),
+            _ => Err(format!(
+                "unsupported concatenation of {}\"\" and {}\"\" string literals",
+                self.prefix(),
+                other.prefix()
+            )),
+        }
+    }
+}
+
+// a character of a literal before it is encoded, the octal and hexadecimal escape
+// sequences give the value of a code unit directly
+#[derive(Clone, Copy)]
+enum LiteralChar {
+    Char(char),
+    Unit(u32, &'static str),
+}
+
+// the encoding of a character constant or a string literal starting at `bytes`, the
+// length of its prefix and its quote
+fn literal_prefix(bytes: &[u8]) -> Option<(Encoding, usize, u8)> {
+    match bytes {
+        [q @ b'"', ..] | [q @ b'\'', ..] => Some((Encoding::Char, 0, *q)),
+        [b'u', b'8', b'"', ..] => Some((Encoding::Utf8, 2, b'"')),
+        [b'L', q @ b'"', ..] | [b'L', q @ b'\'', ..] => Some((Encoding::Wide, 1, *q)),
+        [b'u', q @ b'"', ..] | [b'u', q @ b'\'', ..] => Some((Encoding::Utf16, 1, *q)),
+        [b'U', q @ b'"', ..] | [b'U', q @ b'\'', ..] => Some((Encoding::Utf32, 1, *q)),
+        _ => None,
+    }
+}
+
+// the encoding and the characters of the spelling of a literal, with its prefix and
+// its quotes
+fn decode_literal(spelling: &str) -> Result<(Encoding, Vec<LiteralChar>), String> {
+    let mut chars = Vec::new();
+    let encoding = literal_chars(spelling, &mut |c| chars.push(c))?;
+    Ok((encoding, chars))
+}
+
+// like `decode_literal`, but the characters are given to `push` one by one
+fn literal_chars(spelling: &str, push: &mut impl FnMut(LiteralChar)) -> Result<Encoding, String> {
+    let (encoding, prefix_len, quote) = literal_prefix(spelling.as_bytes()).unwrap();
+    let mut it = spelling[prefix_len + 1..].chars().peekable();
+    lex_literal(&mut it, char::from(quote), push)?;
+    Ok(encoding)
+}
+
+// the characters between the quotes of a literal, `it` is after the opening quote
+fn lex_literal(
+    it: &mut Peekable<Chars>,
+    quote: char,
+    push: &mut impl FnMut(LiteralChar),
+) -> Result<(), String> {
+    // the literal is read up to its end after a bad escape sequence
+    let mut error = None;
+    loop {
+        match it.next() {
+            Some(c) if c == quote => return error.map_or(Ok(()), Err),
+            Some('\\') => match lex_escape(it) {
+                Ok(c) => push(c),
+                Err(e) => {
+                    error.get_or_insert(e);
                 }
-                result.push(TokType::IConstant(number));
-            }
-            'a'..='z' | 'A'..='Z' | '_' => {
-                it.next();
-                let mut s = String::new();
-                s.push(c);
-                while let Some(&tmp) = it.peek() {
-                    match tmp {
-                        'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => {
-                            s.push(tmp);
-                            it.next();
-                        }
-                        _ => {
-                            break;
-                        }
+            },
+            Some('\n') | None => return Err(format!("missing terminating {} character", quote)),
+            Some(c) => push(LiteralChar::Char(c)),
+        }
+    }
+}
+
+// an escape sequence, see C11 6.4.4.4, `it` is after the backslash
+fn lex_escape(it: &mut Peekable<Chars>) -> Result<LiteralChar, String> {
+    let c = match it.next() {
+        Some(c) => c,
+        None => return Err("missing terminating character after \\".to_string()),
+    };
+    let simple = match c {
+        'a' => 0x07, // alert
+        'b' => 0x08, // backspace
+        'e' => 0x1B, // escape character, a GNU extension
+        'f' => 0x0C, // form feed
+        'n' => 0x0A, // new line
+        'r' => 0x0D, // carriage return
+        't' => 0x09, // horizontal tab
+        'v' => 0x0B, // vertical tab
+        '\\' | '\'' | '"' | '?' => c as u32,
+        '0'..='7' => {
+            let mut value = c.to_digit(8).unwrap();
+            for _ in 0..2 {
+                match it.peek().and_then(|c| c.to_digit(8)) {
+                    Some(digit) => {
+                        value = value * 8 + digit;
+                        it.next();
                     }
+                    None => break,
                 }
-                match s.as_ref() {
-                    "auto" => result.push(TokType::AUTO),
-                    "break" => result.push(TokType::BREAK),
-                    "case" => result.push(TokType::CASE),
-                    "char" => result.push(TokType::CHAR),
-                    "const" => result.push(TokType::CONST),
-                    "continue" => result.push(TokType::CONTINUE),
-                    "default" => result.push(TokType::DEFAULT),
-                    "do" => result.push(TokType::DO),
-                    "double" => result.push(TokType::DOUBLE),
-                    "else" => result.push(TokType::ELSE),
-                    "enum" => result.push(TokType::ENUM),
-                    "extern" => result.push(TokType::EXTERN),
-                    "float" => result.push(TokType::FLOAT),
-                    "for" => result.push(TokType::FOR),
-                    "goto" => result.push(TokType::GOTO),
-                    "if" => result.push(TokType::IF),
-                    "inline" => result.push(TokType::INLINE),
-                    "int" => result.push(TokType::INT),
-                    "long" => result.push(TokType::LONG),
-                    "register" => result.push(TokType::REGISTER),
-                    "restrict" => result.push(TokType::RESTRICT),
-                    "return" => result.push(TokType::RETURN),
-                    "short" => result.push(TokType::SHORT),
-                    "signed" => result.push(TokType::SIGNED),
-                    "sizeof" => result.push(TokType::SIZEOF),
-                    "static" => result.push(TokType::STATIC),
-                    "struct" => result.push(TokType::STRUCT),
-                    "switch" => result.push(TokType::SWITCH),
-                    "typedef" => result.push(TokType::TYPEDEF),
-                    "union" => result.push(TokType::UNION),
-                    "unsigned" => result.push(TokType::UNSIGNED),
-                    "void" => result.push(TokType::VOID),
-                    "volatile" => result.push(TokType::VOLATILE),
-                    "while" => result.push(TokType::WHILE),
-                    "_Alignas" => result.push(TokType::ALIGNAS),
-                    "_Alignof" => result.push(TokType::ALIGNOF),
-                    "_Atomic" => result.push(TokType::ATOMIC),
-                    "_Bool" => result.push(TokType::BOOL),
-                    "_Complex" => result.push(TokType::COMPLEX),
-                    "_Generic" => result.push(TokType::GENERIC),
-                    "_Imaginary" => result.push(TokType::IMAGINARY),
-                    "_Noreturn" => result.push(TokType::NORETURN),
-                    "_Static_assert" => result.push(TokType::StaticAssert),
-                    "_Thread_local" => result.push(TokType::ThreadLocal),
-                    "__func__" => result.push(TokType::FuncName),
-                    _ => result.push(TokType::IDENTIFIER(s)),
-                }
-            }
-            '(' => {
-                result.push(TokType::LParen);
-                it.next();
-            }
-            ')' => {
-                result.push(TokType::RParen);
-                it.next();
-            }
-            '{' => {
-                result.push(TokType::LBrace);
-                it.next();
-            }
-            '}' => {
-                result.push(TokType::RBrace);
-                it.next();
-            }
-            '[' => {
-                result.push(TokType::LBracket);
-                it.next();
-            }
-            ']' => {
-                result.push(TokType::RBracket);
-                it.next();
             }
-            ';' => {
-                result.push(TokType::Semicolon);
+            return Ok(LiteralChar::Unit(value, "octal"));
+        }
+        'x' => {
+            let mut value: Option<u32> = None;
+            while let Some(digit) = it.peek().and_then(|c| c.to_digit(16)) {
                 it.next();
+                value = match value.unwrap_or(0).checked_mul(16) {
+                    Some(v) => Some(v + digit),
+                    None => return Err("hex escape sequence out of range".to_string()),
+                };
             }
-            '=' => {
+            return match value {
+                Some(value) => Ok(LiteralChar::Unit(value, "hex")),
+                None => Err("\\x used with no following hex digits".to_string()),
+            };
+        }
+        'u' | 'U' => {
+            let len = if c == 'u' { 4 } else { 8 };
+            let digits: String = it
+                .clone()
+                .take(len)
+                .take_while(|c| c.is_ascii_hexdigit())
+                .collect();
+            for _ in 0..digits.len() {
                 it.next();
-                match it.peek() {
-                    Some(tmp) => match tmp {
-                        '=' => {
-                            result.push(TokType::EqOp);
-                            it.next();
-                        }
-                        _ => {
-                            result.push(TokType::Assign);
-                        }
-                    },
-                    _ => return Err(format!("Can not peek next char")),
-                }
             }
-            '<' => {
-                it.next();
-                match it.peek() {
-                    Some(tmp) => match tmp {
-                        '=' => {
-                            it.next();
-                            result.push(TokType::LeOp);
-                            it.next();
-                        }
-                        '<' => {
-                            it.next();
-                            match it.peek() {
-                                Some(tmp) => match tmp {
-                                    '=' => {
-                                        it.next();
-                                        result.push(TokType::LeftAssign); // <<=
-                                        it.next();
-                                    }
-                                    _ => {
-                                        result.push(TokType::LeftOp);
-                                        it.next();
-                                    }
-                                },
-                                _ => {
-                                    result.push(TokType::LeftOp);
-                                }
-                            }
-                        }
-                        _ => {
-                            result.push(TokType::Lt);
-                        }
-                    },
-                    _ => {
-                        result.push(TokType::Lt);
-                    }
+            return Ok(LiteralChar::Char(ucn_char(c, &digits)?));
+        }
+        _ => return Err(format!("unknown escape sequence '\\{}'", c)),
+    };
+    Ok(LiteralChar::Unit(simple, "simple"))
+}
+
+// the character of a universal character name `\u` or `\U` and its hexadecimal
+// digits, see C11 6.4.3
+fn ucn_char(u: char, digits: &str) -> Result<char, String> {
+    let len = if u == 'u' { 4 } else { 8 };
+    if digits.len() < len {
+        return Err(format!(
+            "incomplete universal character name \\{}{}",
+            u, digits
+        ));
+    }
+    let value = u32::from_str_radix(digits, 16).unwrap();
+    let allowed = value >= 0xA0 || value == 0x24 || value == 0x40 || value == 0x60;
+    match std::char::from_u32(value) {
+        Some(c) if allowed => Ok(c),
+        _ => Err(format!(
+            "\\{}{} is not a valid universal character",
+            u, digits
+        )),
+    }
+}
+
+// C11 Annex D.1, the ranges of the characters allowed in identifiers
+const IDENTIFIER_RANGES: [(u32, u32); 44] = [
+    (0xA8, 0xA8),
+    (0xAA, 0xAA),
+    (0xAD, 0xAD),
+    (0xAF, 0xAF),
+    (0xB2, 0xB5),
+    (0xB7, 0xBA),
+    (0xBC, 0xBE),
+    (0xC0, 0xD6),
+    (0xD8, 0xF6),
+    (0xF8, 0xFF),
+    (0x100, 0x167F),
+    (0x1681, 0x180D),
+    (0x180F, 0x1FFF),
+    (0x200B, 0x200D),
+    (0x202A, 0x202E),
+    (0x203F, 0x2040),
+    (0x2054, 0x2054),
+    (0x2060, 0x206F),
+    (0x2070, 0x218F),
+    (0x2460, 0x24FF),
+    (0x2776, 0x2793),
+    (0x2C00, 0x2DFF),
+    (0x2E80, 0x2FFF),
+    (0x3004, 0x3007),
+    (0x3021, 0x302F),
+    (0x3031, 0x303F),
+    (0x3040, 0xD7FF),
+    (0xF900, 0xFD3D),
+    (0xFD40, 0xFDCF),
+    (0xFDF0, 0xFE44),
+    (0xFE47, 0xFFFD),
+    (0x10000, 0x1FFFD),
+    (0x20000, 0x2FFFD),
+    (0x30000, 0x3FFFD),
+    (0x40000, 0x4FFFD),
+    (0x50000, 0x5FFFD),
+    (0x60000, 0x6FFFD),
+    (0x70000, 0x7FFFD),
+    (0x80000, 0x8FFFD),
+    (0x90000, 0x9FFFD),
+    (0xA0000, 0xAFFFD),
+    (0xB0000, 0xBFFFD),
+    (0xC0000, 0xCFFFD),
+    (0xD0000, 0xDFFFD),
+];
+
+// C11 Annex D.2, the combining characters which can not start an identifier
+const NOT_INITIAL_RANGES: [(u32, u32); 4] = [
+    (0x300, 0x36F),
+    (0x1DC0, 0x1DFF),
+    (0x20D0, 0x20FF),
+    (0xFE20, 0xFE2F),
+];
+
+fn in_ranges(c: char, ranges: &[(u32, u32)]) -> bool {
+    ranges
+        .iter()
+        .any(|&(low, high)| low <= c as u32 && c as u32 <= high)
+}
+
+// the first character of an identifier, a universal character name or a UTF-8
+// character is allowed if it is in Annex D
+pub fn is_identifier_start(c: char) -> bool {
+    c.is_ascii_alphabetic()
+        || c == '_'
+        || (in_ranges(c, &IDENTIFIER_RANGES) && !in_ranges(c, &NOT_INITIAL_RANGES))
+}
+
+pub fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || in_ranges(c, &IDENTIFIER_RANGES)
+}
+
+// the code units of the characters of a literal: the bytes of UTF-8 without a prefix
+// or with u8, UTF-16 with u, and UTF-32 with U and L
+fn encode_literal(chars: &[LiteralChar], encoding: Encoding) -> Result<Vec<u32>, String> {
+    let mut units = Vec::with_capacity(chars.len());
+    for c in chars {
+        check_unit(*c, encoding)?;
+        match *c {
+            LiteralChar::Unit(value, _) => units.push(value),
+            LiteralChar::Char(c) => match encoding {
+                Encoding::Char | Encoding::Utf8 => {
+                    units.extend(c.encode_utf8(&mut [0; 4]).bytes().map(u32::from))
                 }
-            }
-            '>' => {
-                it.next();
-                match it.peek() {
-                    Some(tmp) => match tmp {
-                        '=' => {
-                            result.push(TokType::GeOp);
-                            it.next();
-                        }
-                        '>' => {
-                            it.next();
-                            match it.peek() {
-                                Some(tmp) => match tmp {
-                                    '=' => {
-                                        result.push(TokType::RightAssign);
-                                        it.next();
-                                    }
-                                    _ => {
-                                        result.push(TokType::RightOp);
-                                    }
-                                },
-                                _ => {
-                                    result.push(TokType::RightOp);
-                                }
-                            }
-                        }
-                        _ => {
-                            result.push(TokType::Gt);
-                        }
-                    },
-                    _ => {
-                        result.push(TokType::Gt);
-                    }
+                Encoding::Utf16 => {
+                    units.extend(c.encode_utf16(&mut [0; 2]).iter().map(|&u| u32::from(u)))
                 }
-            }
-            '-' => {
-                it.next();
-                match it.peek() {
-                    Some(tmp) => match tmp {
-                        '-' => {
-                            result.push(TokType::DecOp);
-                            it.next();
-                        }
-                        '=' => {
-                            result.push(TokType::SubAssign);
-                            it.next();
-                        }
-                        '>' => {
-                            result.push(TokType::PtrOp);
-                            it.next();
-                        }
-                        _ => {
-                            result.push(TokType::Minus);
-                        }
-                    },
-                    _ => {
-                        result.push(TokType::Minus);
-                    }
+                Encoding::Wide | Encoding::Utf32 => units.push(c as u32),
+            },
+        }
+    }
+    Ok(units)
+}
+
+// an escape sequence gives one code unit, which must fit in the encoding
+fn check_unit(c: LiteralChar, encoding: Encoding) -> Result<(), String> {
+    match c {
+        LiteralChar::Unit(value, kind) if value > encoding.max_unit() => {
+            Err(format!("{} escape sequence out of range", kind))
+        }
+        _ => Ok(()),
+    }
+}
+
+// the value of a character constant, a plain char is signed like in GCC, and a
+// multi-character constant is its bytes in big-endian order, truncated to int
+fn char_constant(chars: &[LiteralChar], encoding: Encoding) -> Result<TokType, String> {
+    let units = encode_literal(chars, encoding)?;
+    let last = match units.last() {
+        Some(&last) => last,
+        None => return Err("empty character constant".to_string()),
+    };
+    if units.len() > 1 {
+        if encoding != Encoding::Char || units.len() > 4 {
+            warn!("character constant too long for its type");
+        } else {
+            warn!("multi-character character constant");
+        }
+    }
+    Ok(match encoding {
+        Encoding::Char if units.len() == 1 => {
+            TokType::IConstant(i64::from(last as u8 as i8), IntType::Int)
+        }
+        Encoding::Char => {
+            let value = units.iter().fold(0u32, |value, &u| value << 8 | u);
+            TokType::IConstant(i64::from(value as i32), IntType::Int)
+        }
+        Encoding::Wide => TokType::IConstant(i64::from(last as i32), IntType::Int),
+        // char16_t is promoted to int
+        Encoding::Utf16 | Encoding::Utf8 => TokType::IConstant(i64::from(last), IntType::Int),
+        Encoding::Utf32 => TokType::IConstant(i64::from(last), IntType::UInt),
+    })
+}
+
+// the spelling of a string literal with the code units, for the ast printer
+pub fn quote_literal(units: &[u32], encoding: Encoding) -> String {
+    let mut s = format!("{}\"", encoding.prefix());
+    for &u in units {
+        match std::char::from_u32(u) {
+            Some('"') => s.push_str("\\\""),
+            Some('\\') => s.push_str("\\\\"),
+            Some('\n') => s.push_str("\\n"),
+            Some('\t') => s.push_str("\\t"),
+            Some(c) if c == ' ' || c.is_ascii_graphic() => s.push(c),
+            _ if u <= 0o777 => s.push_str(&format!("\\{:03o}", u)),
+            _ => s.push_str(&format!("\\x{:x}", u)),
+        }
+    }
+    s.push('"');
+    s
+}
+
+use log::warn;
+use std::collections::HashMap;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+// an integer or a floating constant of the spelling of a pp-number, see C11 6.4.8
+fn number(spelling: &str) -> Result<TokType, String> {
+    let is_hex = spelling.starts_with("0x") || spelling.starts_with("0X");
+    let is_float = spelling.contains('.')
+        || if is_hex {
+            spelling.contains(['p', 'P'])
+        } else {
+            spelling.contains(['e', 'E'])
+        };
+    if is_float {
+        let (value, float_type) = parse_float(spelling)?;
+        Ok(TokType::FConstant(value, float_type))
+    } else {
+        let (value, int_type) = parse_integer(spelling)?;
+        Ok(TokType::IConstant(value as i64, int_type))
+    }
+}
+
+fn keyword(name: &str) -> Option<TokType> {
+    Some(match name {
+        "auto" => TokType::AUTO,
+        "break" => TokType::BREAK,
+        "case" => TokType::CASE,
+        "char" => TokType::CHAR,
+        "const" => TokType::CONST,
+        "continue" => TokType::CONTINUE,
+        "default" => TokType::DEFAULT,
+        "do" => TokType::DO,
+        "double" => TokType::DOUBLE,
+        "else" => TokType::ELSE,
+        "enum" => TokType::ENUM,
+        "extern" => TokType::EXTERN,
+        "float" => TokType::FLOAT,
+        "for" => TokType::FOR,
+        "goto" => TokType::GOTO,
+        "if" => TokType::IF,
+        "inline" => TokType::INLINE,
+        "int" => TokType::INT,
+        "long" => TokType::LONG,
+        "register" => TokType::REGISTER,
+        "restrict" => TokType::RESTRICT,
+        "return" => TokType::RETURN,
+        "short" => TokType::SHORT,
+        "signed" => TokType::SIGNED,
+        "sizeof" => TokType::SIZEOF,
+        "static" => TokType::STATIC,
+        "struct" => TokType::STRUCT,
+        "switch" => TokType::SWITCH,
+        "typedef" => TokType::TYPEDEF,
+        "union" => TokType::UNION,
+        "unsigned" => TokType::UNSIGNED,
+        "void" => TokType::VOID,
+        "volatile" => TokType::VOLATILE,
+        "while" => TokType::WHILE,
+        "_Alignas" => TokType::ALIGNAS,
+        "_Alignof" => TokType::ALIGNOF,
+        "_Atomic" => TokType::ATOMIC,
+        "_Bool" => TokType::BOOL,
+        "_Complex" => TokType::COMPLEX,
+        "_Generic" => TokType::GENERIC,
+        "_Imaginary" => TokType::IMAGINARY,
+        "_Noreturn" => TokType::NORETURN,
+        "_Static_assert" => TokType::StaticAssert,
+        "_Thread_local" => TokType::ThreadLocal,
+        "__func__" => TokType::FuncName,
+        _ => return None,
+    })
+}
+
+// the punctuator at the start of `bytes` and its length, the longest one, with the
+// digraphs `<:`, `:>`, `<%` and `%>`
+fn punctuator(bytes: &[u8]) -> Option<(TokType, usize)> {
+    Some(match bytes {
+        [b'.', b'.', b'.', ..] => (TokType::ELLIPSIS, 3),
+        [b'<', b'<', b'=', ..] => (TokType::LeftAssign, 3),
+        [b'>', b'>', b'=', ..] => (TokType::RightAssign, 3),
+        [b'-', b'>', ..] => (TokType::PtrOp, 2),
+        [b'+', b'+', ..] => (TokType::IncOp, 2),
+        [b'-', b'-', ..] => (TokType::DecOp, 2),
+        [b'<', b'<', ..] => (TokType::LeftOp, 2),
+        [b'>', b'>', ..] => (TokType::RightOp, 2),
+        [b'<', b'=', ..] => (TokType::LeOp, 2),
+        [b'>', b'=', ..] => (TokType::GeOp, 2),
+        [b'=', b'=', ..] => (TokType::EqOp, 2),
+        [b'!', b'=', ..] => (TokType::NeOp, 2),
+        [b'&', b'&', ..] => (TokType::AndOp, 2),
+        [b'|', b'|', ..] => (TokType::OrOp, 2),
+        [b'*', b'=', ..] => (TokType::MulAssign, 2),
+        [b'/', b'=', ..] => (TokType::DivAssign, 2),
+        [b'%', b'=', ..] => (TokType::ModAssign, 2),
+        [b'+', b'=', ..] => (TokType::AddAssign, 2),
+        [b'-', b'=', ..] => (TokType::SubAssign, 2),
+        [b'&', b'=', ..] => (TokType::AndAssign, 2),
+        [b'^', b'=', ..] => (TokType::XorAssign, 2),
+        [b'|', b'=', ..] => (TokType::OrAssign, 2),
+        [b'<', b':', ..] => (TokType::LBracket, 2),
+        [b':', b'>', ..] => (TokType::RBracket, 2),
+        [b'<', b'%', ..] => (TokType::LBrace, 2),
+        [b'%', b'>', ..] => (TokType::RBrace, 2),
+        [b'{', ..] => (TokType::LBrace, 1),
+        [b'}', ..] => (TokType::RBrace, 1),
+        [b'(', ..] => (TokType::LParen, 1),
+        [b')', ..] => (TokType::RParen, 1),
+        [b'[', ..] => (TokType::LBracket, 1),
+        [b']', ..] => (TokType::RBracket, 1),
+        [b';', ..] => (TokType::Semicolon, 1),
+        [b'=', ..] => (TokType::Assign, 1),
+        [b'<', ..] => (TokType::Lt, 1),
+        [b'>', ..] => (TokType::Gt, 1),
+        [b'-', ..] => (TokType::Minus, 1),
+        [b'~', ..] => (TokType::Tilde, 1),
+        [b'!', ..] => (TokType::Exclamation, 1),
+        [b'+', ..] => (TokType::Plus, 1),
+        [b'*', ..] => (TokType::Multi, 1),
+        [b'/', ..] => (TokType::Splash, 1),
+        [b':', ..] => (TokType::Colon, 1),
+        [b'?', ..] => (TokType::QuestionMark, 1),
+        [b',', ..] => (TokType::Comma, 1),
+        [b'.', ..] => (TokType::Dot, 1),
+        [b'&', ..] => (TokType::SingleAnd, 1),
+        [b'|', ..] => (TokType::InclusiveOr, 1),
+        [b'^', ..] => (TokType::ExclusiveOr, 1),
+        [b'%', ..] => (TokType::Mod, 1),
+        _ => return None,
+    })
+}
+
+// the UTF-8 character at the start of `bytes` and its length
+fn decode_utf8(bytes: &[u8]) -> Option<(char, usize)> {
+    let len = match bytes.first()? {
+        0x00..=0x7F => 1,
+        0xC0..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF7 => 4,
+        _ => return None,
+    };
+    let s = std::str::from_utf8(bytes.get(..len)?).ok()?;
+    s.chars().next().map(|c| (c, len))
+}
+
+// An identifier interned in an `Interner`.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub struct Symbol(pub u32);
+
+// The names of the identifiers, each one is stored once and the tokens only keep its
+// `Symbol`. The files of a program can share one interner.
+#[derive(Default, Debug)]
+pub struct Interner {
+    symbols: HashMap<Box<str>, Symbol>,
+    names: Vec<Box<str>>,
+}
+
+impl Interner {
+    pub fn new() -> Interner {
+        Interner::default()
+    }
+
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(name) {
+            return symbol;
+        }
+        let symbol = Symbol(self.names.len() as u32);
+        self.names.push(name.into());
+        self.symbols.insert(name.into(), symbol);
+        symbol
+    }
+
+    pub fn name(&self, symbol: Symbol) -> &str {
+        &self.names[symbol.0 as usize]
+    }
+
+    // the symbol of a name which is already interned
+    pub fn get(&self, name: &str) -> Option<Symbol> {
+        self.symbols.get(name).copied()
+    }
+
+    // the spelling of a token, with the name of an identifier
+    pub fn spelling(&self, kind: &TokType) -> String {
+        match kind {
+            TokType::IDENTIFIER(symbol) => self.name(*symbol).to_string(),
+            _ => kind.to_string(),
+        }
+    }
+}
+
+// A token of the `TokenStream`, which borrows the text of the input.
+#[derive(PartialEq, Clone, Debug)]
+pub enum RawKind<'a> {
+    // a keyword, a punctuator, a constant or a character constant
+    Token(TokType),
+    Identifier(Symbol),
+    // the spelling of a string literal, with its prefix and its quotes, `string_value`
+    // decodes and concatenates the adjacent ones
+    StringLiteral(&'a str),
+    // the rest of a `#pragma` line
+    Pragma(&'a str),
+    Error(String),
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct RawToken<'a> {
+    pub kind: RawKind<'a>,
+    pub span: Span,
+    pub leading_space: bool,
+    pub start_of_line: bool,
+}
+
+// A lexer over the bytes (UTF-8) of the input, which lexes a token when it is asked
+// for one. The identifiers are interned, and the string literals are borrowed.
+pub struct TokenStream<'a, 'i> {
+    input: &'a [u8],
+    pos: usize,
+    line: usize,
+    line_start: usize,
+    leading_space: bool,
+    start_of_line: bool,
+    interner: &'i mut Interner,
+}
+
+impl<'a, 'i> TokenStream<'a, 'i> {
+    pub fn new(input: &'a [u8], interner: &'i mut Interner) -> TokenStream<'a, 'i> {
+        TokenStream {
+            input,
+            pos: 0,
+            line: 1,
+            line_start: 0,
+            leading_space: false,
+            start_of_line: true,
+            interner,
+        }
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        &self.input[self.pos..]
+    }
+
+    // the text of the bytes from `start` to the current position, they are ASCII or
+    // checked to be UTF-8
+    fn text(&self, start: usize) -> &'a str {
+        std::str::from_utf8(&self.input[start..self.pos]).unwrap()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(&b) = self.input.get(self.pos) {
+            match b {
+                b'\n' => {
+                    self.line += 1;
+                    self.line_start = self.pos + 1;
+                    self.start_of_line = true;
                 }
+                b' ' | b'\t' | b'\r' | b'\x0B' | b'\x0C' => {}
+                _ => break,
             }
-            '~' => {
-                result.push(TokType::Tilde);
-                it.next();
+            self.pos += 1;
+            self.leading_space = true;
+        }
+    }
+
+    fn lex_kind(&mut self) -> Result<Option<RawKind<'a>>, String> {
+        let start = self.pos;
+        let rest = self.rest();
+        if let Some((encoding, prefix_len, quote)) = literal_prefix(rest) {
+            self.pos += prefix_len + 1;
+            self.skip_literal(quote)?;
+            let spelling = std::str::from_utf8(&self.input[start..self.pos])
+                .map_err(|_| "invalid UTF-8 in a literal".to_string())?;
+            if quote == b'"' {
+                return Ok(Some(RawKind::StringLiteral(spelling)));
             }
-            '!' => {
-                it.next();
-                match it.peek() {
-                    Some(tmp) => match tmp {
-                        '=' => {
-                            result.push(TokType::NeOp);
-                            it.next();
-                        }
-                        _ => {
-                            result.push(TokType::Exclamation);
-                        }
-                    },
-                    _ => return Err(format!("Can not peek next char")),
-                }
+            let (_, chars) = decode_literal(spelling)?;
+            return Ok(Some(RawKind::Token(char_constant(&chars, encoding)?)));
+        }
+        match rest {
+            [b'0'..=b'9', ..] | [b'.', b'0'..=b'9', ..] => {
+                self.skip_number();
+                Ok(Some(RawKind::Token(number(self.text(start))?)))
             }
-            '+' => {
-                it.next();
-                match it.peek().unwrap() {
-                    '+' => {
-                        result.push(TokType::IncOp);
-                        it.next();
-                    }
-                    '=' => {
-                        result.push(TokType::AddAssign);
-                        it.next();
-                    }
-                    _ => {
-                        result.push(TokType::Plus);
-                    }
+            [b'#', ..] | [b'%', b':', ..] => {
+                let spelling = if rest[0] == b'#' { "#" } else { "%:" };
+                self.pos += spelling.len();
+                // a directive is the first token of its line
+                if !self.start_of_line {
+                    return Err(format!("stray '{}' in program", spelling));
                 }
+                self.lex_hash_line(spelling)
             }
-            '*' => {
-                it.next();
-                match it.peek() {
-                    Some(tmp) => match tmp {
-                        '=' => {
-                            result.push(TokType::MulAssign);
-                            it.next();
-                        }
-                        _ => {
-                            result.push(TokType::Multi);
-                        }
-                    },
-                    _ => {
-                        result.push(TokType::Multi);
-                    }
+            _ => {
+                if let Some((tok, len)) = punctuator(rest) {
+                    self.pos += len;
+                    return Ok(Some(RawKind::Token(tok)));
                 }
+                self.lex_identifier().map(Some)
             }
-            '%' => {
-                it.next();
-                match it.peek() {
-                    Some(tmp) => match tmp {
-                        '=' => {
-                            result.push(TokType::ModAssign);
-                            it.next();
-                        }
-                        _ => {
-                            result.push(TokType::Mod);
-                        }
-                    },
-                    _ => {
-                        result.push(TokType::Mod);
-                    }
+        }
+    }
+
+    // up to the closing quote of a literal, or the end of the line if there is none
+    fn skip_literal(&mut self, quote: u8) -> Result<(), String> {
+        while let Some(&b) = self.input.get(self.pos) {
+            match b {
+                b'\n' => break,
+                b'\\' if self.input.get(self.pos + 1).is_some_and(|&b| b != b'\n') => self.pos += 2,
+                _ if b == quote => {
+                    self.pos += 1;
+                    return Ok(());
                 }
+                _ => self.pos += 1,
             }
-            '/' => {
-                it.next();
-                match it.peek() {
-                    Some(tmp) => match tmp {
-                        '=' => {
-                            result.push(TokType::DivAssign);
-                            it.next();
-                        }
-                        _ => {
-                            result.push(TokType::Splash);
-                        }
-                    },
-                    _ => {
-                        result.push(TokType::Splash);
-                    }
-                }
+        }
+        Err(format!(
+            "missing terminating {} character",
+            char::from(quote)
+        ))
+    }
+
+    // a pp-number, see C11 6.4.8
+    fn skip_number(&mut self) {
+        while let Some(&b) = self.input.get(self.pos) {
+            let is_exponent_sign = (b == b'+' || b == b'-')
+                && matches!(self.input[self.pos - 1], b'e' | b'E' | b'p' | b'P');
+            if b.is_ascii_alphanumeric() || b == b'_' || b == b'.' || is_exponent_sign {
+                self.pos += 1;
+            } else {
+                break;
             }
-            '&' => {
-                it.next();
-                match it.peek() {
-                    Some(tmp) => match tmp {
-                        '&' => {
-                            result.push(TokType::AndOp);
-                            it.next();
-                        }
-                        '=' => {
-                            result.push(TokType::AndAssign);
-                            it.next();
-                        }
-                        _ => {
-                            // & operator to get the address of a variable
-                            result.push(TokType::SingleAnd);
-                        }
-                    },
-                    _ => {
-                        result.push(TokType::SingleAnd);
-                    }
-                }
+        }
+    }
+
+    // the preprocessor only leaves the `#pragma` lines it does not know, pass them to
+    // the parser, and the line markers of `-E` like `# 1 "foo.h" 1` are skipped, they
+    // are `None`. The position is after the `#` or `%:`.
+    fn lex_hash_line(&mut self, spelling: &str) -> Result<Option<RawKind<'a>>, String> {
+        let start = self.pos;
+        let end = match self.rest().iter().position(|&b| b == b'\n') {
+            Some(len) => start + len,
+            None => self.input.len(),
+        };
+        let line = String::from_utf8_lossy(&self.input[start..end]);
+        let line = line.trim();
+        if line.starts_with(|c: char| c.is_ascii_digit()) {
+            self.pos = end;
+            self.leading_space = true;
+            return Ok(None);
+        }
+        let stray = || format!("stray '{}' in program", spelling);
+        match line.strip_prefix("pragma") {
+            Some(rest) if !rest.starts_with(|c: char| c.is_alphanumeric() || c == '_') => {}
+            _ => return Err(stray()),
+        }
+        let text = std::str::from_utf8(&self.input[start..end]).map_err(|_| stray())?;
+        self.pos = end;
+        Ok(Some(RawKind::Pragma(text.trim()["pragma".len()..].trim())))
+    }
+
+    // an identifier or a keyword, the universal character names are kept as the
+    // characters they name, so `café` and `café` are the same identifier
+    fn lex_identifier(&mut self) -> Result<RawKind<'a>, String> {
+        let start = self.pos;
+        while let Some(&b) = self.input.get(self.pos) {
+            if b.is_ascii_alphabetic() || b == b'_' || (b.is_ascii_digit() && self.pos > start) {
+                self.pos += 1;
+            } else {
+                break;
             }
-            '|' => {
-                it.next();
-                match it.peek() {
-                    Some(tmp) => match tmp {
-                        '|' => {
-                            result.push(TokType::OrOp);
-                            it.next();
-                        }
-                        '=' => {
-                            result.push(TokType::OrAssign);
-                            it.next();
-                        }
-                        _ => {
-                            // now don't support bitwise or, so just return Err
-                            result.push(TokType::InclusiveOr);
-                        }
-                    },
-                    _ => {
-                        result.push(TokType::InclusiveOr);
-                    }
-                }
+        }
+        match self.input.get(self.pos) {
+            Some(&b) if b == b'\\' || b >= 0x80 => {}
+            _ if self.pos > start => {
+                let name = self.text(start);
+                return Ok(match keyword(name) {
+                    Some(tok) => RawKind::Token(tok),
+                    None => RawKind::Identifier(self.interner.intern(name)),
+                });
             }
-            '^' => {
-                it.next();
-                match it.peek() {
-                    Some(tmp) => match tmp {
-                        '=' => {
-                            result.push(TokType::XorAssign);
-                            it.next();
-                        }
-                        _ => {
-                            result.push(TokType::ExclusiveOr);
-                        }
-                    },
-                    _ => {
-                        result.push(TokType::ExclusiveOr);
+            _ => {}
+        }
+        // the slow path, with the characters which are not ASCII
+        let mut name = self.text(start).to_string();
+        loop {
+            let rest = self.rest();
+            let (c, len) = match rest {
+                [b'\\', u @ b'u', ..] | [b'\\', u @ b'U', ..] => {
+                    let len = if *u == b'u' { 4 } else { 8 };
+                    let digits: String = rest[2..]
+                        .iter()
+                        .take(len)
+                        .take_while(|b| b.is_ascii_hexdigit())
+                        .map(|&b| char::from(b))
+                        .collect();
+                    self.pos += 2 + digits.len();
+                    let c = ucn_char(char::from(*u), &digits)?;
+                    let valid = if name.is_empty() {
+                        is_identifier_start(c)
+                    } else {
+                        is_identifier_char(c)
+                    };
+                    if !valid || c.is_ascii() {
+                        return Err(format!(
+                            "universal character \\{}{:02$X} is not valid in an identifier",
+                            char::from(*u),
+                            c as u32,
+                            len
+                        ));
                     }
+                    (c, 0)
                 }
+                _ => match decode_utf8(rest) {
+                    Some((c, len)) if name.is_empty() && is_identifier_start(c) => (c, len),
+                    Some((c, len)) if !name.is_empty() && is_identifier_char(c) => (c, len),
+                    _ => break,
+                },
+            };
+            name.push(c);
+            self.pos += len;
+        }
+        if name.is_empty() {
+            // not a token at all
+            let (c, len) = decode_utf8(self.rest()).unwrap_or(('\u{FFFD}', 1));
+            self.pos += len;
+            if c == '\\' {
+                return Err("unexpected character \\".to_string());
             }
-            '?' => {
-                result.push(TokType::QuestionMark);
-                it.next();
-            }
-            ':' => {
-                result.push(TokType::Colon);
-                it.next();
-            }
-            ',' => {
-                result.push(TokType::Comma);
-                it.next();
+            if c == '\u{FFFD}' {
+                return Err(format!("invalid UTF-8 byte 0x{:02x}", self.input[start]));
             }
-            ' ' | '\n' | '\t' | '\r' | '#' => {
-                // skip
-                it.next();
+            return Err(format!("stray '{}' in program", c.escape_default()));
+        }
+        Ok(RawKind::Identifier(self.interner.intern(&name)))
+    }
+}
+
+impl<'a, 'i> Iterator for TokenStream<'a, 'i> {
+    type Item = RawToken<'a>;
+
+    fn next(&mut self) -> Option<RawToken<'a>> {
+        loop {
+            self.skip_whitespace();
+            let start = self.pos;
+            if start >= self.input.len() {
+                return None;
             }
-            _ => {
-                return Err(format!("unexpected character {}", c));
+            let kind = match self.lex_kind() {
+                Ok(Some(kind)) => kind,
+                // a line marker
+                Ok(None) => continue,
+                Err(message) => RawKind::Error(message),
+            };
+            let tok = RawToken {
+                kind,
+                span: Span {
+                    offset: start,
+                    len: self.pos - start,
+                    line: self.line,
+                    col: start - self.line_start + 1,
+                    file: 0,
+                },
+                leading_space: self.leading_space,
+                start_of_line: self.start_of_line,
+            };
+            self.leading_space = false;
+            self.start_of_line = false;
+            return Some(tok);
+        }
+    }
+}
+
+// The encoding of adjacent string literals, with the errors of `string_value`, but
+// the literals are not decoded.
+fn check_strings<'s>(spellings: impl Iterator<Item = &'s str> + Clone) -> Result<Encoding, String> {
+    let mut encoding = Encoding::Char;
+    for spelling in spellings.clone() {
+        encoding = encoding.concat(literal_chars(spelling, &mut |_| {})?)?;
+    }
+    let mut fits = Ok(());
+    for spelling in spellings {
+        literal_chars(spelling, &mut |c| {
+            if fits.is_ok() {
+                fits = check_unit(c, encoding);
             }
+        })?;
+    }
+    fits.map(|_| encoding)
+}
+
+// The code units and the encoding of the string literal token of `text`, the adjacent
+// string literals and the white spaces between them.
+pub fn string_literal_value(text: &str) -> Result<(Vec<u32>, Encoding), String> {
+    let mut interner = Interner::new();
+    let spellings: Vec<&str> = TokenStream::new(text.as_bytes(), &mut interner)
+        .filter_map(|tok| match tok.kind {
+            RawKind::StringLiteral(s) => Some(s),
+            _ => None,
+        })
+        .collect();
+    string_value(&spellings)
+}
+
+// The code units and the encoding of adjacent string literals, which are
+// concatenated in translation phase 6.
+pub fn string_value(spellings: &[&str]) -> Result<(Vec<u32>, Encoding), String> {
+    let mut encoding = Encoding::Char;
+    let mut chars = Vec::new();
+    for spelling in spellings {
+        let (e, c) = decode_literal(spelling)?;
+        encoding = encoding.concat(e)?;
+        chars.extend(c);
+    }
+    Ok((encode_literal(&chars, encoding)?, encoding))
+}
+
+// The tokens of the input, or all the lexical errors, one per line.
+pub fn lex(input: &str, interner: &mut Interner) -> Result<Vec<Token>, String> {
+    let (toks, errors) = lex_with_errors(input, interner);
+    if errors.is_empty() {
+        return Ok(toks);
+    }
+    let messages: Vec<String> = errors
+        .iter()
+        .map(|e| format!("{}:{}: {}", e.span.line, e.span.col, e.message))
+        .collect();
+    Err(messages.join("\n"))
+}
+
+// The tokens of the input and the lexical errors. The lexer goes on after an error,
+// and leaves an `Error` token in its place. The identifiers are interned in `interner`.
+pub fn lex_with_errors(input: &str, interner: &mut Interner) -> (Vec<Token>, Vec<LexError>) {
+    let mut toks = Vec::new();
+    let mut errors = Vec::new();
+    let mut stream = TokenStream::new(input.as_bytes(), interner);
+    // the adjacent string literals
+    let mut strings: Vec<RawToken> = Vec::new();
+    loop {
+        let raw = stream.next();
+        if let Some(RawKind::StringLiteral(_)) = raw.as_ref().map(|tok| &tok.kind) {
+            strings.push(raw.unwrap());
+            continue;
+        }
+        if let (Some(first), Some(last)) = (strings.first(), strings.last()) {
+            let mut span = first.span;
+            span.len = last.span.offset + last.span.len - span.offset;
+            // the literals are checked here, and decoded when the parser needs them
+            let spellings = strings.iter().filter_map(|tok| match tok.kind {
+                RawKind::StringLiteral(s) => Some(s),
+                _ => None,
+            });
+            let kind = match check_strings(spellings) {
+                Ok(encoding) => TokType::StringLiteral(encoding),
+                Err(message) => {
+                    errors.push(LexError { span, message });
+                    TokType::Error
+                }
+            };
+            toks.push(Token {
+                kind,
+                span,
+                leading_space: first.leading_space,
+                start_of_line: first.start_of_line,
+            });
+            strings.clear();
         }
+        let raw = match raw {
+            Some(raw) => raw,
+            None => break,
+        };
+        let kind = match raw.kind {
+            RawKind::Token(tok) => tok,
+            RawKind::Identifier(symbol) => TokType::IDENTIFIER(symbol),
+            RawKind::Pragma(text) => TokType::Pragma(text.to_string()),
+            RawKind::Error(message) => {
+                errors.push(LexError {
+                    span: raw.span,
+                    message,
+                });
+                TokType::Error
+            }
+            RawKind::StringLiteral(_) => unreachable!(),
+        };
+        toks.push(Token {
+            kind,
+            span: raw.span,
+            leading_space: raw.leading_space,
+            start_of_line: raw.start_of_line,
+        });
     }
-    Ok(result)
+    (toks, errors)
 }
@@ -6,8 +6,68 @@ mod sema;
 mod symtable;
 
 use clap::{App, Arg};
-use log::{trace, info};
-use std::{fs, error, path::Path};
+use log::{error, info, trace, warn};
+use std::{env, fs, error, path::Path, path::PathBuf};
+
+// GCC options with one dash and a long name, clap only accepts them with two dashes
+const GCC_LONG_OPTIONS: [&str; 7] =
+    ["-iquote", "-isystem", "-include", "-imacros", "-MF", "-MT", "-fmax-include-depth"];
+const GCC_LONG_FLAGS: [&str; 7] = ["-nostdinc", "-fsyntax-only", "-M", "-MM", "-MD", "-MMD", "-MP"];
+
+// the stages `--emit` can stop after
+const EMIT_STAGES: [&str; 5] = ["tokens", "ast", "sema", "asm", "obj"];
+
+// the short options which take a value, it is the next argument if it is not attached
+const SHORT_OPTIONS: [&str; 5] = ["-o", "-d", "-D", "-U", "-I"];
+
+// turn `-iquote dir` and `-iquotedir` into `--iquote dir` for clap, only where an
+// option can be: the value of an option is kept as it is, `-o -MF` writes to `-MF`
+fn normalize_args() -> Vec<String> {
+    let mut args = env::args();
+    let mut res: Vec<String> = args.next().into_iter().collect();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            res.push(arg);
+            res.extend(args.by_ref());
+            break;
+        }
+        let takes_next = SHORT_OPTIONS.contains(&arg.as_str())
+            || arg == "--emit"
+            || GCC_LONG_OPTIONS.iter().any(|opt| arg == *opt || arg == format!("-{}", opt));
+        if GCC_LONG_FLAGS.contains(&arg.as_str()) {
+            res.push(format!("-{}", arg));
+            continue;
+        }
+        match GCC_LONG_OPTIONS.iter().find(|opt| arg.starts_with(*opt)) {
+            // `-fmax-include-depth=N`, clap takes `--fmax-include-depth=N`
+            Some(opt) if arg[opt.len()..].starts_with('=') => res.push(format!("-{}", arg)),
+            Some(opt) if arg.len() > opt.len() => {
+                res.push(format!("-{}", opt));
+                res.push(arg[opt.len()..].to_string());
+            }
+            Some(opt) => res.push(format!("-{}", opt)),
+            None => res.push(arg),
+        }
+        if takes_next {
+            res.extend(args.next());
+        }
+    }
+    res
+}
+
+// `1 syntax error`, `2 syntax errors`
+fn count_errors(count: usize, kind: &str) -> String {
+    let plural = if count == 1 { "" } else { "s" };
+    format!("{} {} error{}", count, kind, plural)
+}
+
+// log an error at `span`, with the source line and a caret under the column
+fn report_error(source_map: &cpp::SourceMap, span: &lexer::Span, message: &str) {
+    match source_map.snippet(span) {
+        Some(snippet) => error!("{}: error: {}\n{}", source_map.display_span(span), message, snippet),
+        None => error!("{}: error: {}", source_map.display_span(span), message),
+    }
+}
 
 fn main()  -> Result<(), Box<dyn error::Error>> {
     let args = App::new("Crust")
@@ -25,7 +85,168 @@ fn main()  -> Result<(), Box<dyn error::Error>> {
                 .multiple(true)
                 .help("Sets the level of verbosity"),
         )
-        .get_matches();
+        .arg(
+            Arg::with_name("d")
+                .short("d")
+                .takes_value(true)
+                .possible_values(&["M"])
+                .help("-dM: print the #define of all the macros after preprocessing, and stop"),
+        )
+        .arg(
+            Arg::with_name("E")
+                .short("E")
+                .conflicts_with_all(&["emit", "fsyntax-only"])
+                .help("Print the preprocessed source with line markers, and stop"),
+        )
+        .arg(
+            Arg::with_name("P")
+                .short("P")
+                .help("Do not print the line markers with -E"),
+        )
+        .arg(
+            Arg::with_name("o")
+                .short("o")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .value_name("file")
+                .help("Write the output to the file, `-` is the standard output"),
+        )
+        .arg(
+            Arg::with_name("emit")
+                .long("emit")
+                .takes_value(true)
+                .possible_values(&EMIT_STAGES)
+                .help("Print the output of the stage, and stop"),
+        )
+        .arg(
+            Arg::with_name("fsyntax-only")
+                .long("fsyntax-only")
+                .conflicts_with("emit")
+                .help("Only check the syntax and the semantics, print nothing"),
+        )
+        .arg(
+            Arg::with_name("M")
+                .long("M")
+                .help("Print the Makefile rule of the headers the source file includes, and stop"),
+        )
+        .arg(
+            Arg::with_name("MM")
+                .long("MM")
+                .help("Like -M, but without the system headers"),
+        )
+        .arg(
+            Arg::with_name("MD")
+                .long("MD")
+                .help("Like -M, but write the rule to a .d file and continue compiling"),
+        )
+        .arg(
+            Arg::with_name("MMD")
+                .long("MMD")
+                .help("Like -MD, but without the system headers"),
+        )
+        .arg(
+            Arg::with_name("MF")
+                .long("MF")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .value_name("file")
+                .help("Write the rule of -M and -MD to the file"),
+        )
+        .arg(
+            Arg::with_name("MT")
+                .long("MT")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("target")
+                .help("Set the target of the rule of -M and -MD"),
+        )
+        .arg(
+            Arg::with_name("MP")
+                .long("MP")
+                .help("Add a phony target for each header to the rule of -M and -MD"),
+        )
+        .arg(
+            Arg::with_name("fmax-include-depth")
+                .long("fmax-include-depth")
+                .takes_value(true)
+                .value_name("depth")
+                .help("Set how deep #include can be nested, 200 by default"),
+        )
+        .arg(
+            Arg::with_name("D")
+                .short("D")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("name[=value]")
+                .help("Define the macro, as 1 if there is no value"),
+        )
+        .arg(
+            Arg::with_name("U")
+                .short("U")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("name")
+                .help("Undefine the macro"),
+        )
+        .arg(
+            Arg::with_name("include")
+                .long("include")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("file")
+                .help("Include the file before the first line of the source file"),
+        )
+        .arg(
+            Arg::with_name("imacros")
+                .long("imacros")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("file")
+                .help("Take the macros of the file, but not its output"),
+        )
+        .arg(
+            Arg::with_name("I")
+                .short("I")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Add the directory to the include search path"),
+        )
+        .arg(
+            Arg::with_name("iquote")
+                .long("iquote")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Add the directory to the search path of #include \"...\" only"),
+        )
+        .arg(
+            Arg::with_name("isystem")
+                .long("isystem")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Add the directory to the system include search path"),
+        )
+        .arg(
+            Arg::with_name("nostdinc")
+                .long("nostdinc")
+                .help("Do not search the standard system directories for header files"),
+        )
+        .get_matches_from(normalize_args());
 
     loggerv::Logger::new()
         .verbosity(args.occurrences_of("v"))
@@ -36,28 +257,198 @@ fn main()  -> Result<(), Box<dyn error::Error>> {
         .unwrap();
 
     let files: Vec<_> = args.values_of("files").unwrap().collect();
+    let emit = args.value_of("emit");
+    // whether a stage prints its result, the output of all the files goes to `-o`
+    // -M and -MM print the rule instead of the preprocessed source, -MD and -MMD
+    // write it to a file while compiling
+    let print_deps = args.is_present("M") || args.is_present("MM");
+    let write_deps = args.is_present("MD") || args.is_present("MMD");
+    let print_output =
+        args.is_present("E") || args.is_present("d") || emit.is_some() || print_deps;
+    // the code generator is disabled, so only the stages which print write to -o, and
+    // the rule of -MD is named after it
+    if args.is_present("o") && !print_output && !write_deps {
+        let msg = "-o needs -E, -dM, -M, -MM or --emit=tokens|ast, no code is generated now";
+        return Err(msg.into());
+    }
+    if args.is_present("o") && print_output && files.len() > 1 {
+        return Err("cannot specify -o with multiple files".into());
+    }
+    if args.is_present("MF") && files.len() > 1 {
+        return Err("cannot specify -MF with multiple files".into());
+    }
+    let mut output = String::new();
+
+    let dirs = |name| -> Vec<PathBuf> {
+        match args.values_of(name) {
+            Some(values) => values.map(PathBuf::from).collect(),
+            None => Vec::new(),
+        }
+    };
+    let values = |name| -> Vec<String> {
+        match args.values_of(name) {
+            Some(values) => values.map(String::from).collect(),
+            None => Vec::new(),
+        }
+    };
+    // -D and -U are applied in the order of the command line
+    let mut macros: Vec<(usize, cpp::MacroOption)> = Vec::new();
+    if let (Some(indices), Some(values)) = (args.indices_of("D"), args.values_of("D")) {
+        let options = values.map(|def| cpp::MacroOption::Define(def.to_string()));
+        macros.extend(indices.zip(options));
+    }
+    if let (Some(indices), Some(values)) = (args.indices_of("U"), args.values_of("U")) {
+        let options = values.map(|name| cpp::MacroOption::Undef(name.to_string()));
+        macros.extend(indices.zip(options));
+    }
+    macros.sort_by_key(|(idx, _)| *idx);
+    let cpp_opts = cpp::CppOptions {
+        quote_dirs: dirs("iquote"),
+        bracket_dirs: dirs("I"),
+        system_dirs: dirs("isystem"),
+        no_std_inc: args.is_present("nostdinc"),
+        macros: macros.into_iter().map(|(_, option)| option).collect(),
+        imacros: values("imacros"),
+        includes: values("include"),
+        max_include_depth: match args.value_of("fmax-include-depth") {
+            Some(depth) => depth
+                .parse()
+                .map_err(|_| format!("invalid -fmax-include-depth={}", depth))?,
+            None => cpp::CppOptions::default().max_include_depth,
+        },
+    };
 
+    // the files share the names of their identifiers
+    let mut interner = lexer::Interner::new();
     for file in files {
         info!("Compiling: {}", file);
         let path = Path::new(file);
         let file_contents = fs::read_to_string(path)?;
 
         // 1. Preprocessing
-        let contents_after_cpp = cpp::cpp_driver(file_contents, path.to_path_buf())?;
+        // each file is a new translation unit, with its own macros
+        let mut preprocessor = cpp::Preprocessor::new(&cpp_opts);
+        let cpp_output = preprocessor.preprocess(file_contents, path.to_path_buf())?;
+        if print_deps || write_deps {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+            let dep_opts = cpp::DepOptions {
+                // the object file is the default target, the file of -o with -MD
+                targets: match args.values_of("MT") {
+                    Some(targets) => targets.map(String::from).collect(),
+                    None => match args.value_of("o") {
+                        Some(out_file) if write_deps => vec![cpp::make_quote(out_file)],
+                        _ => vec![cpp::make_quote(&format!("{}.o", stem))],
+                    },
+                },
+                no_system: args.is_present("MM") || args.is_present("MMD"),
+                phony: args.is_present("MP"),
+            };
+            let rule = cpp_output.make_rule(&dep_opts);
+            match args.value_of("MF") {
+                Some(dep_file) => fs::write(dep_file, rule)?,
+                None if print_deps => output.push_str(&rule),
+                None => {
+                    let dep_file = match args.value_of("o") {
+                        Some(out_file) => Path::new(out_file).with_extension("d"),
+                        None => PathBuf::from(format!("{}.d", stem)),
+                    };
+                    fs::write(dep_file, rule)?;
+                }
+            }
+            if print_deps {
+                continue;
+            }
+        }
+        let contents_after_cpp = cpp_output.text;
         trace!("File content after replacing PreProcessors: {:?}", contents_after_cpp);
+        if args.value_of("d") == Some("M") {
+            output.push_str(&preprocessor.dump_macros());
+            continue;
+        }
+        if args.is_present("E") {
+            if args.is_present("P") {
+                output.push_str(&contents_after_cpp);
+            } else {
+                output.push_str(&cpp_output.source_map.with_line_markers(&contents_after_cpp));
+            }
+            continue;
+        }
 
         // 2. lexing
-        let tokens = lexer::lex(&contents_after_cpp)?;
+        let (mut tokens, lex_errors) = lexer::lex_with_errors(&contents_after_cpp, &mut interner);
+        cpp_output.source_map.locate(&mut tokens);
+        if !lex_errors.is_empty() {
+            for mut e in lex_errors.iter().cloned() {
+                cpp_output.source_map.locate_span(&mut e.span);
+                report_error(&cpp_output.source_map, &e.span, &e.message);
+            }
+            return Err(format!("{}: {}", file, count_errors(lex_errors.len(), "lexical")).into());
+        }
         trace!("Tokens: {:?}", &tokens);
-
+        if emit == Some("tokens") {
+            for tok in tokens.iter() {
+                let display = cpp_output.source_map.display_span(&tok.span);
+                let kind = match &tok.kind {
+                    lexer::TokType::IDENTIFIER(symbol) => {
+                        format!("IDENTIFIER({:?})", interner.name(*symbol))
+                    }
+                    lexer::TokType::StringLiteral(encoding) => {
+                        let text = &contents_after_cpp[tok.span.offset..][..tok.span.len];
+                        format!("StringLiteral({}, {:?})", text, encoding)
+                    }
+                    kind => format!("{:?}", kind),
+                };
+                output.push_str(&format!("{}: {}\n", display, kind));
+            }
+            continue;
+        }
 
         // 3. parsing
-        let root_node = parser::parser_driver(&tokens, &file)?;
+        let (root_node, parse_errors) = parser::parse_with_errors(&contents_after_cpp, &tokens, &interner);
+        if !parse_errors.is_empty() {
+            for e in parse_errors.iter() {
+                if tokens.is_empty() {
+                    // an empty file has no position to point at
+                    error!("{}: error: {}", file, e.message);
+                } else {
+                    report_error(&cpp_output.source_map, &e.span, &e.message);
+                }
+            }
+            return Err(format!("{}: {}", file, count_errors(parse_errors.len(), "syntax")).into());
+        }
         trace!(
             "Source AST:{}",
             parser::parser_pretty_printer(&root_node, 0)
         );
+        if emit == Some("ast") {
+            output.push_str(parser::parser_pretty_printer(&root_node, 0).trim_start());
+            output.push('\n');
+            continue;
+        }
+
+        // 4. semantics analysis
+        sema::sema_driver(&root_node, file)?;
+        if emit == Some("sema") {
+            return Err("--emit=sema: the semantic analysis has no output in crust now".into());
+        }
+        if args.is_present("fsyntax-only") {
+            continue;
+        }
+
+        // 5. code generation
+        if let Some(stage) = emit {
+            return Err(
+                format!("--emit={}: the code generator is disabled in crust now", stage).into(),
+            );
+        }
+        warn!("{}: the code generator is disabled in crust now, the file is only checked", file);
     }
 
+    if print_output {
+        match args.value_of("o") {
+            Some(out_file) if out_file != "-" => fs::write(out_file, output)?,
+            _ => print!("{}", output),
+        }
+    }
     Ok(())
 }
@@ -20,27 +20,102 @@ use crate::ast::{ConstantType, NodeType, ParseNode};
 use crate::lexer;
 use crate::sema;
 use crate::symtable::{BaseType, TypeExpression};
+use std::collections::HashMap;
+
+// XXX: some uncommon situations support should be added.
+
+// The state of the parser of one translation unit, passed to all the rules. Nothing is
+// shared between two parsers, so each thread can run its own.
+struct Parser<'a> {
+    // the names of the identifiers of the tokens
+    interner: &'a lexer::Interner,
+    // the text the tokens were lexed from
+    source: &'a str,
+    // the identifiers of each scope, innermost last, true for a typedef name
+    scopes: Vec<HashMap<lexer::Symbol, bool>>,
+    // the results of the rules parsed ahead, see `Parsed`
+    memo: HashMap<(&'static str, usize), Parsed>,
+    // the furthest failure in the declaration or the statement being parsed
+    furthest: Option<Failure>,
+    // the syntax errors the parser recovered from
+    errors: Vec<ParseError>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str, interner: &'a lexer::Interner) -> Parser<'a> {
+        return Parser {
+            interner,
+            source,
+            scopes: vec![HashMap::new()],
+            memo: HashMap::new(),
+            furthest: None,
+            errors: Vec::new(),
+        };
+    }
 
-// XXX: How to handle error message properly should be improved later
-//      and some uncommon situations support should be added.
+    // the text of a token, the adjacent string literals of a StringLiteral
+    fn text(&self, tok: &lexer::Token) -> &'a str {
+        return &self.source[tok.span.offset..tok.span.offset + tok.span.len];
+    }
+}
 
 // ------------------------------------------------------------------------
 // helper function
 // ------------------------------------------------------------------------
-fn error_handler(expect: &str, toks: &lexer::TokType, pos: usize) -> String {
-    // return a detailed error message.
-    // now it could be simple, just print the token information
-    return format!("Expected `{}`, found {:?} at {}", expect, toks, pos);
+// the token at `pos` for the messages, or the end of the input
+fn found_at(toks: &[lexer::Token], pos: usize, ctx: &Parser) -> (lexer::Span, String) {
+    match toks.get(pos) {
+        Some(tok) if matches!(tok.kind, lexer::TokType::StringLiteral(_)) => {
+            return (tok.span, format!("`{}`", ctx.text(tok)));
+        }
+        Some(tok) => return (tok.span, format!("`{}`", ctx.interner.spelling(&tok.kind))),
+        None => {
+            let mut span = toks.last().map(|tok| tok.span).unwrap_or_default();
+            span.offset += span.len;
+            span.col += span.len;
+            span.len = 0;
+            return (span, "end of input".to_string());
+        }
+    }
 }
 
-fn check_tok(pos: usize, toks: &[lexer::TokType], expect: &lexer::TokType) -> Result<(), String> {
-    check_pos(pos, toks.len())?;
+// stands for the end of the input, a pragma is never an operator
+static END_OF_INPUT: lexer::TokType = lexer::TokType::Pragma(String::new());
+
+fn kind_at(toks: &[lexer::Token], pos: usize) -> &lexer::TokType {
+    return toks.get(pos).map_or(&END_OF_INPUT, |tok| &tok.kind);
+}
+
+fn error_handler(expect: &str, toks: &[lexer::Token], pos: usize, ctx: &mut Parser) -> String {
+    let (span, found) = found_at(toks, pos, ctx);
+    ctx.expected_at(span, &found, expect);
+    return format!(
+        "Expected {}, found {} at {}:{}",
+        expect, found, span.line, span.col
+    );
+}
 
-    if &toks[pos] != expect {
-        return Err(format!("Expected: {:?}, found {:?}", expect, toks[pos]));
+// like error_handler, but `expect` replaces what the alternatives which failed at `pos`
+// expected, e.g. an expression instead of all the tokens an expression can start with
+fn error_instead(expect: &str, toks: &[lexer::Token], pos: usize, ctx: &mut Parser) -> String {
+    let (span, _) = found_at(toks, pos, ctx);
+    match &mut ctx.furthest {
+        Some(failure) if failure.span.offset == span.offset => failure.expected.clear(),
+        _ => {}
     }
+    return error_handler(expect, toks, pos, ctx);
+}
 
-    return Ok(());
+fn check_tok(
+    pos: usize,
+    toks: &[lexer::Token],
+    expect: &lexer::TokType,
+    ctx: &mut Parser,
+) -> Result<(), String> {
+    match toks.get(pos) {
+        Some(tok) if &tok.kind == expect => return Ok(()),
+        _ => return Err(error_handler(&format!("`{}`", expect), toks, pos, ctx)),
+    }
 }
 
 fn check_pos(pos: usize, toks_len: usize) -> Result<(), String> {
@@ -50,17 +125,278 @@ fn check_pos(pos: usize, toks_len: usize) -> Result<(), String> {
     return Ok(());
 }
 
-fn p_identifier(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+// ------------------------------------------------------------------------
+// syntax errors
+// ------------------------------------------------------------------------
+// A syntax error, at the token the parser could not go on from.
+#[derive(PartialEq, Clone, Debug)]
+pub struct ParseError {
+    pub span: lexer::Span,
+    pub message: String,
+}
+
+// The parser tries the alternatives of a rule one by one, so the error of the last one
+// is seldom the right one. The error is the furthest token where an alternative failed,
+// with all the things the alternatives expected there.
+struct Failure {
+    span: lexer::Span,
+    found: String,
+    expected: Vec<String>,
+}
+
+impl Parser<'_> {
+    fn expected_at(&mut self, span: lexer::Span, found: &str, expect: &str) {
+        match &self.furthest {
+            Some(failure) if failure.span.offset > span.offset => return,
+            Some(failure) if failure.span.offset == span.offset => {}
+            _ => {
+                self.furthest = Some(Failure {
+                    span,
+                    found: found.to_string(),
+                    expected: Vec::new(),
+                })
+            }
+        }
+        let failure = self.furthest.as_mut().unwrap();
+        // `a, b or c` is kept as three alternatives, to be joined with the others
+        for expect in expect.split(", ").flat_map(|e| e.split(" or ")) {
+            if !failure.expected.iter().any(|e| e == expect) {
+                failure.expected.push(expect.to_string());
+            }
+        }
+    }
+
+    // before a declaration or a statement
+    fn clear_failure(&mut self) {
+        self.furthest = None;
+    }
+
+    // reports the furthest failure of the declaration or the statement at `pos`, which
+    // should be `what`, and skips to its end
+    fn recover(&mut self, toks: &[lexer::Token], pos: usize, what: &str) -> usize {
+        let (span, found) = found_at(toks, pos, self);
+        let error = match self.furthest.take() {
+            Some(failure) if failure.span.offset > span.offset => ParseError {
+                span: failure.span,
+                message: format!(
+                    "expected {}, found {}",
+                    join_expected(&failure.expected),
+                    failure.found
+                ),
+            },
+            // nothing matched its first token
+            _ => ParseError {
+                span,
+                message: format!("expected {}, found {}", what, found),
+            },
+        };
+        // a part of the input can be parsed again after it failed
+        if !self.errors.iter().any(|e| e.span.offset == error.span.offset) {
+            self.errors.push(error);
+        }
+        return synchronize(toks, pos);
+    }
+}
+
+// `a, b or c`
+fn join_expected(expected: &[String]) -> String {
+    match expected.split_last() {
+        Some((last, [])) => return last.to_string(),
+        Some((last, rest)) => return format!("{} or {}", rest.join(", "), last),
+        None => return String::new(),
+    }
+}
+
+// the position after the `;` or the block (and its `;`) which ends the declaration or the
+// statement at `pos`, or of the `}` which ends the enclosing block
+fn synchronize(toks: &[lexer::Token], pos: usize) -> usize {
+    let (mut braces, mut parens) = (0, 0);
+    let mut end = pos;
+    while let Some(tok) = toks.get(end) {
+        match tok.kind {
+            lexer::TokType::LBrace => braces += 1,
+            lexer::TokType::RBrace if braces == 0 => return end.max(pos + 1),
+            lexer::TokType::RBrace => {
+                braces -= 1;
+                parens = 0;
+                if braces == 0 {
+                    // the `;` of `struct S { ... };` or `int a[] = { ... };`
+                    if kind_at(toks, end + 1) == &lexer::TokType::Semicolon {
+                        return end + 2;
+                    }
+                    return end + 1;
+                }
+            }
+            lexer::TokType::LParen | lexer::TokType::LBracket => parens += 1,
+            lexer::TokType::RParen | lexer::TokType::RBracket if parens > 0 => parens -= 1,
+            lexer::TokType::Semicolon if braces == 0 && parens == 0 => return end + 1,
+            _ => {}
+        }
+        end += 1;
+    }
+    return end;
+}
+
+// ------------------------------------------------------------------------
+// typedef names
+// ------------------------------------------------------------------------
+// typedef names and ordinary identifiers share one name space, an identifier is a
+// type specifier only if its innermost declaration is a typedef (C11 6.2.1, 6.7.8).
+impl<'a> Parser<'a> {
+    fn is_typedef_name(&self, symbol: lexer::Symbol) -> bool {
+        return self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&symbol).copied())
+            .unwrap_or(false);
+    }
+
+    // a name which is not interned is in no token, so it is never looked up
+    fn declare(&mut self, name: &str, is_typedef: bool) {
+        if let (Some(scope), Some(symbol)) = (self.scopes.last_mut(), self.interner.get(name)) {
+            scope.insert(symbol, is_typedef);
+        }
+    }
+
+    // parses in a new block scope, which is left also when the parser fails in it
+    fn in_scope<T>(&mut self, parse: impl FnOnce(&mut Parser<'a>) -> T) -> T {
+        self.scopes.push(HashMap::new());
+        let parsed = parse(self);
+        self.scopes.pop();
+        return parsed;
+    }
+}
+
+// the identifier declared by a declarator
+fn declarator_name(node: &ParseNode) -> Option<&str> {
+    match &node.entry {
+        NodeType::Identifier(name) => Some(name),
+        NodeType::Declarator => declarator_name(node.child.last()?),
+        NodeType::DirectDeclarator => declarator_name(node.child.first()?),
+        _ => None,
+    }
+}
+
+// the parameters declared by the declarator of a function definition
+fn parameter_names(declarator: &ParseNode) -> Vec<String> {
+    let direct = match declarator.child.last() {
+        Some(node) => node,
+        None => return Vec::new(),
+    };
+    match direct.child.first() {
+        Some(node) if node.entry == NodeType::Declarator => return parameter_names(node),
+        _ => {}
+    }
+    let params = direct
+        .child
+        .get(1)
+        .and_then(|post_list| {
+            post_list
+                .child
+                .iter()
+                .find(|post| post.entry == NodeType::DirectDeclaratorPost(lexer::TokType::LParen))
+        })
+        .and_then(|post| post.child.first());
+    let mut names = Vec::new();
+    if let Some(params) = params {
+        let list = match params.entry {
+            NodeType::ParameterTypeList(_) => params.child.first().map_or(&[][..], |l| &l.child),
+            _ => &params.child[..],
+        };
+        for param in list {
+            if let Some(name) = param.child.get(1).and_then(declarator_name) {
+                names.push(name.to_string());
+            } else if let NodeType::Identifier(name) = &param.entry {
+                names.push(name.to_string());
+            }
+        }
+    }
+    return names;
+}
+
+// the enumeration constants defined in the specifiers of a declaration
+fn enumerator_names(node: &ParseNode, names: &mut Vec<String>) {
+    if node.entry == NodeType::Enumerator {
+        if let Some(NodeType::EnumerationConstant(name)) = node.child.first().map(|c| &c.entry) {
+            names.push(name.to_string());
+        }
+    }
+    for child in node.child.iter() {
+        enumerator_names(child, names);
+    }
+}
+
+// declares the identifiers of a declaration in the current scope
+fn declare_declaration(declaration: &ParseNode, ctx: &mut Parser) {
+    let specifiers = match declaration.child.first() {
+        Some(node) if node.entry == NodeType::DeclarationSpecifiers => node,
+        _ => return,
+    };
+    let mut is_typedef = false;
+    let mut node = specifiers;
+    loop {
+        if let Some(NodeType::TypeSpecifier(Some(lexer::TokType::TYPEDEF))) =
+            node.child.first().map(|c| &c.entry)
+        {
+            is_typedef = true;
+        }
+        match node.child.get(1) {
+            Some(next) => node = next,
+            None => break,
+        }
+    }
+    let mut names = Vec::new();
+    enumerator_names(specifiers, &mut names);
+    for name in names {
+        ctx.declare(&name, false);
+    }
+    if let Some(list) = declaration.child.get(1) {
+        for init_declarator in list.child.iter() {
+            if let Some(name) = init_declarator.child.first().and_then(declarator_name) {
+                ctx.declare(name, is_typedef);
+            }
+        }
+    }
+}
+
+// ------------------------------------------------------------------------
+// memoised rules
+// ------------------------------------------------------------------------
+// An alternative which fails after a rule matched leaves the result, by rule and
+// position, to the next alternative, which starts with the same rule at the same
+// position: `a + b` is not `unary_expression assignment_operator ...`, and the
+// conditional_expression it is starts with the same `a`. Parsing it again at every
+// level made `((((x))))` cost 2^depth. The result is moved, not cloned, so the
+// entry is used once.
+type Parsed = Result<(ParseNode, usize), String>;
+
+impl Parser<'_> {
+    fn memoise(&mut self, rule: &'static str, pos: usize, parsed: Parsed) {
+        self.memo.insert((rule, pos), parsed);
+    }
+
+    fn recall(&mut self, rule: &'static str, pos: usize) -> Option<Parsed> {
+        return self.memo.remove(&(rule, pos));
+    }
+}
+
+fn p_identifier(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
-    match &toks[pos] {
+    match &toks[pos].kind {
         lexer::TokType::IDENTIFIER(val) => {
-            let mut cur_node = ParseNode::new(NodeType::Identifier(val.to_string()));
-            cur_node.type_exp = TypeExpression::new_val(BaseType::Identifier(val.to_string()));
+            let name = ctx.interner.name(*val);
+            let mut cur_node = ParseNode::new(NodeType::Identifier(name.to_string()));
+            cur_node.type_exp = TypeExpression::new_val(BaseType::Identifier(name.to_string()));
             return Ok((cur_node, pos + 1));
         }
         _ => {
-            return Err(error_handler("identifier", &toks[pos], pos));
+            return Err(error_handler("an identifier", toks, pos, ctx));
         }
     }
 }
@@ -73,31 +409,41 @@ fn p_identifier(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize
 // 	| generic_selection
 // 	;
 
-fn p_primary_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_primary_expression(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
     let mut cur_node = ParseNode::new(NodeType::PrimaryExpression);
-    if let Ok((child_node, new_pos)) = p_identifier(toks, pos) {
+    if let lexer::TokType::IDENTIFIER(name) = &toks[pos].kind {
+        if ctx.is_typedef_name(*name) {
+            // a typedef name is a type, like in `(T)x`
+            return Err(error_handler("an expression", toks, pos, ctx));
+        }
+    }
+    if let Ok((child_node, new_pos)) = p_identifier(toks, pos, ctx) {
         cur_node.type_exp = child_node.type_exp.clone();
         cur_node.child.push(child_node);
         return Ok((cur_node, new_pos));
-    } else if let Ok((child_node, new_pos)) = p_constant(toks, pos) {
+    } else if let Ok((child_node, new_pos)) = p_constant(toks, pos, ctx) {
         cur_node.type_exp = child_node.type_exp.clone();
         cur_node.child.push(child_node);
         return Ok((cur_node, new_pos));
-    } else if let Ok((child_node, new_pos)) = p_string(toks, pos) {
+    } else if let Ok((child_node, new_pos)) = p_string(toks, pos, ctx) {
         cur_node.type_exp = child_node.type_exp.clone();
         cur_node.child.push(child_node);
         return Ok((cur_node, new_pos));
-    } else if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::LParen) {
+    } else if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::LParen, ctx) {
         let pos = pos + 1;
-        let (child_node, pos) = p_expression(toks, pos)?;
+        let (child_node, pos) = p_expression(toks, pos, ctx)?;
         cur_node.type_exp = child_node.type_exp.clone();
         cur_node.child.push(child_node);
-        check_tok(pos, &toks, &lexer::TokType::RParen)?;
+        check_tok(pos, &toks, &lexer::TokType::RParen, ctx)?;
         let pos = pos + 1;
         return Ok((cur_node, pos));
-    } else if let Ok((child_node, new_pos)) = p_generic_selection(toks, pos) {
+    } else if let Ok((child_node, new_pos)) = p_generic_selection(toks, pos, ctx) {
         cur_node.type_exp = child_node.type_exp.clone();
         cur_node.child.push(child_node);
         return Ok((cur_node, new_pos));
@@ -111,19 +457,29 @@ fn p_primary_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNod
 // 	| FConstant
 // 	| EnumerationConstant	/* after it has been defined as such */
 // 	;
-fn p_constant(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_constant(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
-    match &toks[pos] {
-        lexer::TokType::IConstant(i_val) => {
+    match &toks[pos].kind {
+        lexer::TokType::IConstant(i_val, i_type) => {
             let mut cur_node = ParseNode::new(NodeType::Constant(ConstantType::I64(*i_val)));
-            // cause if the value was assigned to int, we can easily cast long to int.
-            cur_node.type_exp = TypeExpression::new_val(BaseType::Long);
+            cur_node.type_exp = int_type_exp(*i_type);
             Ok((cur_node, pos + 1))
         }
-        lexer::TokType::FConstant(f_val) => {
+        lexer::TokType::FConstant(f_val, f_type) => {
             let mut cur_node = ParseNode::new(NodeType::Constant(ConstantType::F64(*f_val)));
-            cur_node.type_exp = TypeExpression::new_val(BaseType::Double);
+            cur_node.type_exp = match f_type {
+                lexer::FloatType::Float => TypeExpression::new_val(BaseType::Float),
+                lexer::FloatType::Double => TypeExpression::new_val(BaseType::Double),
+                lexer::FloatType::LongDouble => TypeExpression {
+                    val: vec![BaseType::Long, BaseType::Double],
+                    child: Vec::new(),
+                },
+            };
             Ok((cur_node, pos + 1))
         }
         lexer::TokType::EnumerationConstant(e_val) => {
@@ -133,7 +489,23 @@ fn p_constant(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize),
             cur_node.type_exp = TypeExpression::new_val(BaseType::Long);
             Ok((cur_node, pos + 1))
         }
-        _ => Err(error_handler("constant", &toks[pos], pos)),
+        _ => Err(error_handler("a constant", toks, pos, ctx)),
+    }
+}
+
+// the type of an integer constant, written like the type specifiers, e.g. `unsigned long`
+fn int_type_exp(i_type: lexer::IntType) -> TypeExpression {
+    let val = match i_type {
+        lexer::IntType::Int => vec![BaseType::Int],
+        lexer::IntType::UInt => vec![BaseType::Unsigned, BaseType::Int],
+        lexer::IntType::Long => vec![BaseType::Long],
+        lexer::IntType::ULong => vec![BaseType::Unsigned, BaseType::Long],
+        lexer::IntType::LongLong => vec![BaseType::Long, BaseType::Long],
+        lexer::IntType::ULongLong => vec![BaseType::Unsigned, BaseType::Long, BaseType::Long],
+    };
+    TypeExpression {
+        val,
+        child: Vec::new(),
     }
 }
 
@@ -142,18 +514,20 @@ fn p_constant(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize),
 // 	;
 // TODO: should judge whether a identifier is a enumeration_constant in semantics_analyzer
 fn p_enumeration_constant(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
-    match &toks[pos] {
-        lexer::TokType::IDENTIFIER(name) => {
+    match &toks[pos].kind {
+        lexer::TokType::IDENTIFIER(symbol) => {
+            let name = ctx.interner.name(*symbol);
             let mut cur_node = ParseNode::new(NodeType::EnumerationConstant(name.to_string()));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Identifier(name.to_string()));
             return Ok((cur_node, pos + 1));
         }
         _ => {
-            return Err(error_handler("identifier", &toks[pos], pos));
+            return Err(error_handler("an identifier", toks, pos, ctx));
         }
     }
 }
@@ -162,15 +536,28 @@ fn p_enumeration_constant(
 // 	: StringLiteral
 // 	| FuncName
 // 	;
-fn p_string(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_string(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
-    match &toks[pos] {
-        lexer::TokType::StringLiteral(v, _tag) => {
-            let mut cur_node = ParseNode::new(NodeType::STRING(v.to_string()));
-            let len = v.len();
-            let mut t_exp = TypeExpression::new_val(BaseType::Array(len));
-            t_exp.val.push(BaseType::Char);
+    match &toks[pos].kind {
+        lexer::TokType::StringLiteral(_) => {
+            let (units, encoding) = &lexer::string_literal_value(ctx.text(&toks[pos]))?;
+            let mut cur_node =
+                ParseNode::new(NodeType::STRING(lexer::quote_literal(units, *encoding)));
+            // the array has a terminating null
+            let mut t_exp = TypeExpression::new_val(BaseType::Array(units.len() + 1));
+            match encoding {
+                lexer::Encoding::Char | lexer::Encoding::Utf8 => t_exp.val.push(BaseType::Char),
+                lexer::Encoding::Wide => t_exp.val.push(BaseType::Int),
+                lexer::Encoding::Utf16 => {
+                    t_exp.val.extend(vec![BaseType::Unsigned, BaseType::Short])
+                }
+                lexer::Encoding::Utf32 => t_exp.val.extend(vec![BaseType::Unsigned, BaseType::Int]),
+            }
             cur_node.type_exp = t_exp;
             return Ok((cur_node, pos + 1));
         }
@@ -184,7 +571,7 @@ fn p_string(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), S
             return Ok((cur_node, pos + 1));
         }
         _ => {
-            return Err(error_handler("String literal", &toks[pos], pos));
+            return Err(error_handler("a string literal", toks, pos, ctx));
         }
     }
 }
@@ -193,29 +580,33 @@ fn p_string(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), S
 // 	: GENERIC '(' assignment_expression ',' generic_assoc_list ')'
 // 	;
 // TODO: Add type system for this kind of node
-fn p_generic_selection(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_generic_selection(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
     let mut cur_node = ParseNode::new(NodeType::GenericSelection);
 
-    if toks[pos] != lexer::TokType::GENERIC {
-        return Err(error_handler("__Generic", &toks[pos], pos));
+    if toks[pos].kind != lexer::TokType::GENERIC {
+        return Err(error_handler("`_Generic`", toks, pos, ctx));
     }
     let pos = pos + 1;
 
-    check_tok(pos, &toks, &lexer::TokType::LParen)?;
+    check_tok(pos, &toks, &lexer::TokType::LParen, ctx)?;
 
     let pos = pos + 1;
     check_pos(pos, toks.len())?;
-    let (child_node, pos) = p_assignment_expression(toks, pos)?;
+    let (child_node, pos) = p_assignment_expression(toks, pos, ctx)?;
     cur_node.child.push(child_node);
 
-    check_tok(pos, &toks, &lexer::TokType::Comma)?;
+    check_tok(pos, &toks, &lexer::TokType::Comma, ctx)?;
     let pos = pos + 1;
-    let (child_node, pos) = p_generic_assoc_list(toks, pos)?;
+    let (child_node, pos) = p_generic_assoc_list(toks, pos, ctx)?;
     cur_node.child.push(child_node);
 
-    check_tok(pos, &toks, &lexer::TokType::RParen)?;
+    check_tok(pos, &toks, &lexer::TokType::RParen, ctx)?;
     let pos = pos + 1;
 
     return Ok((cur_node, pos));
@@ -228,21 +619,25 @@ fn p_generic_selection(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode
 // EBNF:
 // -> generic_association { ',' generic_association }
 // TODO: Add type system for this kind of node
-fn p_generic_assoc_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_generic_assoc_list(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
     let mut cur_node = ParseNode::new(NodeType::GenericAssocList);
 
-    let (child_node, pos) = p_generic_association(toks, pos)?; // if error, then out
+    let (child_node, pos) = p_generic_association(toks, pos, ctx)?; // if error, then out
 
     cur_node.child.push(child_node);
 
     // let mut back_pos = pos;
     let mut pos = pos;
-    while let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Comma) {
+    while let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Comma, ctx) {
         let back_pos = pos;
         pos = pos + 1;
-        match p_generic_association(toks, pos) {
+        match p_generic_association(toks, pos, ctx) {
             Ok((child_node, tmp)) => {
                 cur_node.child.push(child_node);
                 pos = tmp;
@@ -262,8 +657,9 @@ fn p_generic_assoc_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNod
 // 	| DEFAULT ':' assignment_expression
 // TODO: Add type system for this kind of node
 fn p_generic_association(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     if pos >= toks.len() {
         return Err(format!("out of token index"));
@@ -271,21 +667,21 @@ fn p_generic_association(
 
     let mut cur_node = ParseNode::new(NodeType::GenericAssociation);
     let mut pos = pos;
-    if let Ok((child_node, tmp_pos)) = p_type_name(toks, pos) {
+    if let Ok((child_node, tmp_pos)) = p_type_name(toks, pos, ctx) {
         pos = tmp_pos;
         cur_node.child.push(child_node);
-    } else if toks[pos] == lexer::TokType::DEFAULT {
+    } else if toks[pos].kind == lexer::TokType::DEFAULT {
         pos = pos + 1;
     } else {
         return Err(format!(
-            "Can't find proper type name or default, found {:?} at {}",
-            toks[pos], pos
+            "Can't find proper type name or default, found {:?} at {}:{}",
+            toks[pos].kind, toks[pos].span.line, toks[pos].span.col
         ));
     }
 
-    check_tok(pos, &toks, &lexer::TokType::Colon)?;
+    check_tok(pos, &toks, &lexer::TokType::Colon, ctx)?;
     let pos = pos + 1;
-    let (child_node, pos) = p_assignment_expression(toks, pos)?;
+    let (child_node, pos) = p_assignment_expression(toks, pos, ctx)?;
     cur_node.child.push(child_node);
     return Ok((cur_node, pos));
 }
@@ -313,11 +709,15 @@ fn p_generic_association(
 // postfix_expression:
 //  pre { postfix_expression_post }
 // TODO: Need carefully review
-fn p_postfix_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_postfix_expression(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node = ParseNode::new(NodeType::PostfixExpression);
 
-    if let Ok((child_node, pos)) = p_primary_expression(toks, pos) {
+    if let Ok((child_node, pos)) = p_primary_expression(toks, pos, ctx) {
         let pre_type = child_node.type_exp.clone();
 
         cur_node.type_exp.child.push(child_node.type_exp.clone());
@@ -325,7 +725,7 @@ fn p_postfix_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNod
         let mut pos = pos;
         let mut inc = 0;
         loop {
-            if let Ok((child_node, tmp_pos)) = p_postfix_expression_post(toks, pos) {
+            if let Ok((child_node, tmp_pos)) = p_postfix_expression_post(toks, pos, ctx) {
                 inc = inc + 1;
                 cur_node.child.push(child_node);
                 pos = tmp_pos;
@@ -338,27 +738,27 @@ fn p_postfix_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNod
             cur_node.type_exp = pre_type;
         }
         return Ok((cur_node, pos));
-    } else if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::LParen) {
+    } else if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::LParen, ctx) {
         let pos = pos + 1;
-        let (child_node, pos) = p_type_name(toks, pos)?;
+        let (child_node, pos) = p_type_name(toks, pos, ctx)?;
         cur_node.type_exp.child.push(child_node.type_exp.clone());
         cur_node.child.push(child_node);
 
-        check_tok(pos, &toks, &lexer::TokType::RParen)?;
+        check_tok(pos, &toks, &lexer::TokType::RParen, ctx)?;
         let pos = pos + 1;
 
-        check_tok(pos, &toks, &lexer::TokType::LBrace)?;
+        check_tok(pos, &toks, &lexer::TokType::LBrace, ctx)?;
         let pos = pos + 1;
 
-        let (child_node, pos) = p_initializer_list(toks, pos)?;
+        let (child_node, pos) = p_initializer_list(toks, pos, ctx)?;
         cur_node.type_exp.child.push(child_node.type_exp.clone());
         cur_node.child.push(child_node);
-        if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RBrace) {
+        if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RBrace, ctx) {
             let pos = pos + 1;
 
             let mut pos = pos;
             loop {
-                if let Ok((child_node, tmp_pos)) = p_postfix_expression_post(toks, pos) {
+                if let Ok((child_node, tmp_pos)) = p_postfix_expression_post(toks, pos, ctx) {
                     cur_node.type_exp.child.push(child_node.type_exp.clone());
                     cur_node.child.push(child_node);
                     pos = tmp_pos;
@@ -368,14 +768,14 @@ fn p_postfix_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNod
             }
             return Ok((cur_node, pos));
         } else {
-            check_tok(pos, &toks, &lexer::TokType::Comma)?;
+            check_tok(pos, &toks, &lexer::TokType::Comma, ctx)?;
             let pos = pos + 1;
-            check_tok(pos, &toks, &lexer::TokType::RBrace)?;
+            check_tok(pos, &toks, &lexer::TokType::RBrace, ctx)?;
             let pos = pos + 1;
 
             let mut pos = pos;
             loop {
-                if let Ok((child_node, tmp_pos)) = p_postfix_expression_post(toks, pos) {
+                if let Ok((child_node, tmp_pos)) = p_postfix_expression_post(toks, pos, ctx) {
                     cur_node.type_exp.child.push(child_node.type_exp.clone());
                     cur_node.child.push(child_node);
                     pos = tmp_pos;
@@ -401,51 +801,55 @@ fn p_postfix_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNod
 // TODO: Need carefully review
 // TODO: Add type system for this kind of node
 fn p_postfix_expression_post(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
-    match &toks[pos] {
+    match &toks[pos].kind {
         lexer::TokType::LBracket => {
-            let mut cur_node = ParseNode::new(NodeType::PostfixExpressionPost(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::PostfixExpressionPost(toks[pos].kind.clone()));
             let pos = pos + 1;
-            let (child_node, pos) = p_expression(toks, pos)?;
+            let (child_node, pos) = p_expression(toks, pos, ctx)?;
             cur_node.type_exp = child_node.type_exp.clone();
             cur_node.child.push(child_node);
-            check_tok(pos, &toks, &lexer::TokType::RBracket)?;
+            check_tok(pos, &toks, &lexer::TokType::RBracket, ctx)?;
             let pos = pos + 1;
             return Ok((cur_node, pos));
         }
         lexer::TokType::LParen => {
-            let mut cur_node = ParseNode::new(NodeType::PostfixExpressionPost(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::PostfixExpressionPost(toks[pos].kind.clone()));
             let pos = pos + 1;
-            if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RParen) {
+            if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RParen, ctx) {
                 let pos = pos + 1;
                 return Ok((cur_node, pos));
             } else {
-                let (child_node, pos) = p_argument_expression_list(toks, pos)?;
+                let (child_node, pos) = p_argument_expression_list(toks, pos, ctx)?;
                 cur_node.type_exp = child_node.type_exp.clone();
                 cur_node.child.push(child_node);
-                check_tok(pos, &toks, &lexer::TokType::RParen)?;
+                check_tok(pos, &toks, &lexer::TokType::RParen, ctx)?;
                 let pos = pos + 1;
                 return Ok((cur_node, pos));
             }
         }
         lexer::TokType::Dot | lexer::TokType::PtrOp => {
-            let mut cur_node = ParseNode::new(NodeType::PostfixExpressionPost(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::PostfixExpressionPost(toks[pos].kind.clone()));
             let pos = pos + 1;
-            let (child_node, pos) = p_identifier(toks, pos)?;
+            let (child_node, pos) = p_identifier(toks, pos, ctx)?;
             cur_node.child.push(child_node);
             return Ok((cur_node, pos));
         }
         lexer::TokType::IncOp | lexer::TokType::DecOp => {
-            let cur_node = ParseNode::new(NodeType::PostfixExpressionPost(toks[pos].clone()));
+            let cur_node = ParseNode::new(NodeType::PostfixExpressionPost(toks[pos].kind.clone()));
             let pos = pos + 1;
             return Ok((cur_node, pos));
         }
         _ => {
-            return Err(format!("{:?} at {} is a postfix operator", toks[pos], pos));
+            return Err(format!(
+                "{:?} at {}:{} is a postfix operator",
+                toks[pos].kind, toks[pos].span.line, toks[pos].span.col
+            ));
         }
     }
 }
@@ -456,14 +860,15 @@ fn p_postfix_expression_post(
 // 	;
 // -> assignment_expression { ',' assignment_expression }
 fn p_argument_expression_list(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
     let mut cur_node = ParseNode::new(NodeType::ArgumentExpressionList);
 
-    let (child_node, pos) = p_assignment_expression(toks, pos)?; // if error, then out
+    let (child_node, pos) = p_assignment_expression(toks, pos, ctx)?; // if error, then out
     let pre_type = child_node.type_exp.clone();
 
     cur_node.type_exp.child.push(child_node.type_exp.clone());
@@ -472,10 +877,10 @@ fn p_argument_expression_list(
     let mut inc = 0;
     let mut pos = pos;
     loop {
-        if let Err(_) = check_tok(pos, &toks, &lexer::TokType::Comma) {
+        if let Err(_) = check_tok(pos, &toks, &lexer::TokType::Comma, ctx) {
             break;
         }
-        match p_assignment_expression(toks, pos + 1) {
+        match p_assignment_expression(toks, pos + 1, ctx) {
             Ok((child_node, tmp)) => {
                 inc = inc + 1;
                 cur_node.type_exp.child.push(child_node.type_exp.clone());
@@ -503,29 +908,42 @@ fn p_argument_expression_list(
 // 	| SIZEOF '(' type_name ')'
 // 	| ALIGNOF '(' type_name ')'
 // 	;
-fn p_unary_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_unary_expression(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
+    if let Some(parsed) = ctx.recall("unary_expression", pos) {
+        return parsed;
+    }
 
-    match toks[pos] {
+    match toks[pos].kind {
         lexer::TokType::IncOp | lexer::TokType::DecOp => {
-            let mut cur_node = ParseNode::new(NodeType::UnaryExpression(Some(toks[pos].clone())));
+            let mut cur_node = ParseNode::new(NodeType::UnaryExpression(Some(toks[pos].kind.clone())));
             let pos = pos + 1;
-            let (child_node, pos) = p_unary_expression(toks, pos)?;
+            let (child_node, pos) = p_unary_expression(toks, pos, ctx)?;
             cur_node.type_exp = child_node.type_exp.clone();
             cur_node.child.push(child_node);
             return Ok((cur_node, pos));
         }
         lexer::TokType::SIZEOF => {
             // assign the return type of sizeof() to size_t
+            let mut cur_node = ParseNode::new(NodeType::UnaryExpression(Some(toks[pos].kind.clone())));
             let pos = pos + 1;
-            let mut cur_node = ParseNode::new(NodeType::UnaryExpression(Some(toks[pos].clone())));
-            if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::LParen) {
-                let (child_node, pos) = p_type_name(toks, pos)?;
+            // `sizeof (T)` is a type name only if T is a type, `sizeof (x)` is an expression
+            let type_name = check_tok(pos, toks, &lexer::TokType::LParen, ctx)
+                .and_then(|_| p_type_name(toks, pos + 1, ctx))
+                .and_then(|(child_node, pos)| {
+                    check_tok(pos, toks, &lexer::TokType::RParen, ctx)?;
+                    Ok((child_node, pos + 1))
+                });
+            if let Ok((child_node, pos)) = type_name {
                 cur_node.type_exp = TypeExpression::new_val(BaseType::SizeT);
                 cur_node.child.push(child_node);
                 return Ok((cur_node, pos));
             } else {
-                let (child_node, pos) = p_unary_expression(toks, pos)?;
+                let (child_node, pos) = p_unary_expression(toks, pos, ctx)?;
                 cur_node.type_exp = TypeExpression::new_val(BaseType::SizeT);
                 cur_node.child.push(child_node);
                 return Ok((cur_node, pos));
@@ -533,22 +951,24 @@ fn p_unary_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
         }
         lexer::TokType::ALIGNOF => {
             // should return type size_t
-            let mut cur_node = ParseNode::new(NodeType::UnaryExpression(Some(toks[pos].clone())));
+            let mut cur_node = ParseNode::new(NodeType::UnaryExpression(Some(toks[pos].kind.clone())));
             let pos = pos + 1;
-            if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::LParen) {
+            if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::LParen, ctx) {
+                let pos = pos + 1;
+                let (child_node, pos) = p_type_name(toks, pos, ctx)?;
+                check_tok(pos, toks, &lexer::TokType::RParen, ctx)?;
                 let pos = pos + 1;
-                let (child_node, pos) = p_type_name(toks, pos)?;
                 cur_node.type_exp = TypeExpression::new_val(BaseType::SizeT);
                 cur_node.child.push(child_node);
                 return Ok((cur_node, pos));
             } else {
-                return Err(error_handler("(", &toks[pos], pos));
+                return Err(error_handler("`(`", toks, pos, ctx));
             }
         }
         _ => {
             // postfix_expression
             // unary_operator cast_expression
-            if let Ok((child_node, pos)) = p_unary_operator(toks, pos) {
+            if let Ok((child_node, pos)) = p_unary_operator(toks, pos, ctx) {
                 let mut cur_node = ParseNode::new(NodeType::UnaryExpression(None));
                 let unary_op = if let NodeType::UnaryOperator(op) = child_node.entry.clone() {
                     op
@@ -560,7 +980,7 @@ fn p_unary_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
                 };
 
                 cur_node.child.push(child_node);
-                let (child_node, pos) = p_cast_expression(toks, pos)?;
+                let (child_node, pos) = p_cast_expression(toks, pos, ctx)?;
                 match unary_op {
                     lexer::TokType::SingleAnd => {
                         cur_node.type_exp = TypeExpression::new_val(BaseType::Pointer);
@@ -577,13 +997,13 @@ fn p_unary_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
                 }
                 cur_node.child.push(child_node);
                 return Ok((cur_node, pos));
-            } else if let Ok((child_node, pos)) = p_postfix_expression(toks, pos) {
+            } else if let Ok((child_node, pos)) = p_postfix_expression(toks, pos, ctx) {
                 let mut cur_node = ParseNode::new(NodeType::UnaryExpression(None));
                 cur_node.type_exp = child_node.type_exp.clone();
                 cur_node.child.push(child_node);
                 return Ok((cur_node, pos));
             } else {
-                return Err(format!("Can't parse unary_expression"));
+                return Err(error_instead("an expression", toks, pos, ctx));
             }
         }
     }
@@ -597,10 +1017,14 @@ fn p_unary_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
 // 	| '~'
 // 	| '!'
 // 	;
-fn p_unary_operator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_unary_operator(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     // need to match
-    match &toks[pos] {
+    match &toks[pos].kind {
         lexer::TokType::Minus|
         lexer::TokType::SingleAnd| // '&', different with '&&' as TokType::And
         lexer::TokType::Multi|
@@ -608,10 +1032,10 @@ fn p_unary_operator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
         lexer::TokType::Tilde |
         lexer::TokType::Plus => {
             // don't have type, just care about the operator type
-            return Ok((ParseNode::new(NodeType::UnaryOperator(toks[pos].clone())), pos + 1));
+            return Ok((ParseNode::new(NodeType::UnaryOperator(toks[pos].kind.clone())), pos + 1));
         }
         _ => {
-            return Err(error_handler("unary_operator", &toks[pos], pos));
+            return Err(error_handler("a unary operator", toks, pos, ctx));
         }
     }
 }
@@ -620,23 +1044,28 @@ fn p_unary_operator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
 // 	: unary_expression
 // 	| '(' type_name ')' cast_expression
 // 	;
-fn p_cast_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_cast_expression(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
     let mut cur_node = ParseNode::new(NodeType::CastExpression);
-    if let Ok((child_node, pos)) = p_unary_expression(toks, pos) {
+    if let Ok((child_node, pos)) = p_unary_expression(toks, pos, ctx) {
         cur_node.type_exp = child_node.type_exp.clone();
         cur_node.child.push(child_node);
         return Ok((cur_node, pos));
-    } else if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::LParen) {
-        let (child_node, pos) = p_type_name(toks, pos + 1)?;
+    } else if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::LParen, ctx) {
+        let (child_node, pos) = p_type_name(toks, pos + 1, ctx)?;
         let to_type = child_node.type_exp.clone();
 
         cur_node.child.push(child_node);
 
-        check_tok(pos, &toks, &lexer::TokType::RParen)?;
+        check_tok(pos, &toks, &lexer::TokType::RParen, ctx)?;
+        let pos = pos + 1;
 
-        let (child_node, pos) = p_cast_expression(toks, pos)?;
+        let (child_node, pos) = p_cast_expression(toks, pos, ctx)?;
         let from_type = child_node.type_exp.clone();
 
         if sema::judge_cast(&to_type, &from_type) == false {
@@ -650,7 +1079,7 @@ fn p_cast_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
         cur_node.child.push(child_node);
         return Ok((cur_node, pos));
     } else {
-        return Err(format!("Error parse cast_expression"));
+        return Err(error_instead("an expression", toks, pos, ctx));
     }
 }
 
@@ -662,17 +1091,18 @@ fn p_cast_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
 // 	;
 //   cast_expression { ('*' | '/' | '%') cast_expression }
 fn p_multiplicative_expression(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node = ParseNode::new(NodeType::MultiplicativeExpression);
     // exp -> multiplicative_expression
     let mut pos = pos;
-    let (child_node, tmp_pos) = p_cast_expression(toks, pos)?;
+    let (child_node, tmp_pos) = p_cast_expression(toks, pos, ctx)?;
     let mut l_type = child_node.type_exp.clone();
     pos = tmp_pos;
-    let mut tok = &toks[pos];
+    let mut tok = kind_at(toks, pos);
     if *tok != lexer::TokType::Mod
         && *tok != lexer::TokType::Multi
         && *tok != lexer::TokType::Splash
@@ -691,7 +1121,7 @@ fn p_multiplicative_expression(
         let mut bincur_node = ParseNode::new(NodeType::BinaryExpression(tok.clone()));
         pos = pos + 1;
         let op = tok.clone();
-        let (next_child_node, tmp_pos) = p_cast_expression(toks, pos)?;
+        let (next_child_node, tmp_pos) = p_cast_expression(toks, pos, ctx)?;
         let r_type = next_child_node.type_exp.clone();
 
         pos = tmp_pos;
@@ -707,7 +1137,7 @@ fn p_multiplicative_expression(
         }
         child_node = bincur_node;
         l_type = child_node.type_exp.clone();
-        tok = &toks[pos];
+        tok = kind_at(toks, pos);
     }
     cur_node.type_exp = child_node.type_exp.clone();
     cur_node.child.push(child_node);
@@ -717,18 +1147,19 @@ fn p_multiplicative_expression(
 // 	: multiplicative_expression { ("+" | "-") multiplicative_expression }
 // 	;
 fn p_additive_expression(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
     let mut cur_node = ParseNode::new(NodeType::AdditiveExpression);
     // exp -> multiplicative_expression
     let mut pos = pos;
-    let (child_node, tmp_pos) = p_multiplicative_expression(toks, pos)?;
+    let (child_node, tmp_pos) = p_multiplicative_expression(toks, pos, ctx)?;
     let mut l_type = child_node.type_exp.clone();
     pos = tmp_pos;
-    let mut tok = &toks[pos];
+    let mut tok = kind_at(toks, pos);
     if *tok != lexer::TokType::Plus && *tok != lexer::TokType::Minus {
         cur_node.type_exp = l_type;
         cur_node.child.push(child_node);
@@ -742,7 +1173,7 @@ fn p_additive_expression(
         let mut bincur_node = ParseNode::new(NodeType::BinaryExpression(tok.clone()));
         pos = pos + 1;
         let op = tok.clone();
-        let (next_child_node, tmp_pos) = p_multiplicative_expression(toks, pos)?;
+        let (next_child_node, tmp_pos) = p_multiplicative_expression(toks, pos, ctx)?;
         let r_type = next_child_node.type_exp.clone();
         pos = tmp_pos;
         bincur_node.child.push(child_node);
@@ -757,7 +1188,7 @@ fn p_additive_expression(
         }
         child_node = bincur_node;
         l_type = child_node.type_exp.clone();
-        tok = &toks[pos];
+        tok = kind_at(toks, pos);
     }
     cur_node.type_exp = child_node.type_exp.clone();
     cur_node.child.push(child_node);
@@ -769,14 +1200,18 @@ fn p_additive_expression(
 // 	| shift_expression RightOp additive_expression
 // 	;
 // -> additive_expression { (LeftOp | RightOp) additive_expression }
-fn p_shift_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_shift_expression(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
     let mut cur_node = ParseNode::new(NodeType::ShiftExpression);
     // exp -> additive_expression
-    let (child_node, pos) = p_additive_expression(toks, pos)?;
+    let (child_node, pos) = p_additive_expression(toks, pos, ctx)?;
     let mut l_type = child_node.type_exp.clone();
-    let mut tok = &toks[pos];
+    let mut tok = kind_at(toks, pos);
     if *tok != lexer::TokType::LeftOp && *tok != lexer::TokType::RightOp {
         cur_node.type_exp = l_type;
         cur_node.child.push(child_node);
@@ -790,7 +1225,7 @@ fn p_shift_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
         let mut bincur_node = ParseNode::new(NodeType::BinaryExpression(tok.clone()));
         pos = pos + 1;
         let op = tok.clone();
-        let (next_child_node, tmp_pos) = p_additive_expression(toks, pos)?;
+        let (next_child_node, tmp_pos) = p_additive_expression(toks, pos, ctx)?;
         let r_type = next_child_node.type_exp.clone();
         pos = tmp_pos;
         bincur_node.child.push(child_node);
@@ -805,7 +1240,7 @@ fn p_shift_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
         }
         child_node = bincur_node;
         l_type = child_node.type_exp.clone();
-        tok = &toks[pos];
+        tok = kind_at(toks, pos);
     }
     cur_node.type_exp = child_node.type_exp.clone();
     cur_node.child.push(child_node);
@@ -820,16 +1255,17 @@ fn p_shift_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
 // 	;
 // -> shift_expression { ('<' | '>' | LeOp | GeOp) shift_expression }
 fn p_relational_expression(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
     let mut cur_node = ParseNode::new(NodeType::RelationalExpression);
     // exp -> shift_expression
-    let (child_node, pos) = p_shift_expression(toks, pos)?;
+    let (child_node, pos) = p_shift_expression(toks, pos, ctx)?;
     let mut l_type = child_node.type_exp.clone();
-    let mut tok = &toks[pos];
+    let mut tok = kind_at(toks, pos);
     if *tok != lexer::TokType::Lt
         && *tok != lexer::TokType::Gt
         && *tok != lexer::TokType::GeOp
@@ -850,7 +1286,7 @@ fn p_relational_expression(
         let mut bincur_node = ParseNode::new(NodeType::BinaryExpression(tok.clone()));
         pos = pos + 1;
         let op = tok.clone();
-        let (next_child_node, tmp_pos) = p_shift_expression(toks, pos)?;
+        let (next_child_node, tmp_pos) = p_shift_expression(toks, pos, ctx)?;
         let r_type = next_child_node.type_exp.clone();
         pos = tmp_pos;
         bincur_node.child.push(child_node);
@@ -865,7 +1301,7 @@ fn p_relational_expression(
         }
         child_node = bincur_node;
         l_type = child_node.type_exp.clone();
-        tok = &toks[pos];
+        tok = kind_at(toks, pos);
     }
     cur_node.type_exp = child_node.type_exp.clone();
     cur_node.child.push(child_node);
@@ -879,16 +1315,17 @@ fn p_relational_expression(
 // 	;
 // -> relational_expression { (EqOp | NeOp) relational_expression }
 fn p_equality_expression(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
     let mut cur_node = ParseNode::new(NodeType::EqualityExpression);
     // exp -> relational_expression
-    let (child_node, pos) = p_relational_expression(toks, pos)?;
+    let (child_node, pos) = p_relational_expression(toks, pos, ctx)?;
     let mut l_type = child_node.type_exp.clone();
-    let mut tok = &toks[pos];
+    let mut tok = kind_at(toks, pos);
     if *tok != lexer::TokType::EqOp && *tok != lexer::TokType::NeOp {
         cur_node.type_exp = l_type;
         cur_node.child.push(child_node);
@@ -901,7 +1338,7 @@ fn p_equality_expression(
         let mut bincur_node = ParseNode::new(NodeType::BinaryExpression(tok.clone()));
         pos = pos + 1;
         let op = tok.clone();
-        let (next_child_node, tmp_pos) = p_relational_expression(toks, pos)?;
+        let (next_child_node, tmp_pos) = p_relational_expression(toks, pos, ctx)?;
         let r_type = next_child_node.type_exp.clone();
         pos = tmp_pos;
         bincur_node.child.push(child_node);
@@ -916,7 +1353,7 @@ fn p_equality_expression(
         }
         child_node = bincur_node;
         l_type = child_node.type_exp.clone();
-        tok = &toks[pos];
+        tok = kind_at(toks, pos);
     }
     cur_node.type_exp = child_node.type_exp.clone();
     cur_node.child.push(child_node);
@@ -929,14 +1366,18 @@ fn p_equality_expression(
 // 	;
 //  -> equality_expression { '&' equality_expression }
 // XXX:
-fn p_and_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_and_expression(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
     let mut cur_node = ParseNode::new(NodeType::AndExpression);
     // exp -> equality_expression
-    let (child_node, pos) = p_equality_expression(toks, pos)?;
+    let (child_node, pos) = p_equality_expression(toks, pos, ctx)?;
     let mut l_type = child_node.type_exp.clone();
-    let mut tok = &toks[pos];
+    let mut tok = kind_at(toks, pos);
     if *tok != lexer::TokType::SingleAnd {
         cur_node.type_exp = l_type;
         cur_node.child.push(child_node);
@@ -949,7 +1390,7 @@ fn p_and_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
         let mut bincur_node = ParseNode::new(NodeType::BinaryExpression(tok.clone()));
         pos = pos + 1;
         let op = tok.clone();
-        let (next_child_node, tmp_pos) = p_equality_expression(toks, pos)?;
+        let (next_child_node, tmp_pos) = p_equality_expression(toks, pos, ctx)?;
         let r_type = next_child_node.type_exp.clone();
         pos = tmp_pos;
         bincur_node.child.push(child_node);
@@ -964,7 +1405,7 @@ fn p_and_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
         }
         child_node = bincur_node;
         l_type = child_node.type_exp.clone();
-        tok = &toks[pos];
+        tok = kind_at(toks, pos);
     }
     cur_node.type_exp = child_node.type_exp.clone();
     cur_node.child.push(child_node);
@@ -977,16 +1418,17 @@ fn p_and_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
 // 	;
 //  -> and_expression { '^' and_expression }
 fn p_exclusive_or_expression(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
     let mut cur_node = ParseNode::new(NodeType::ExclusiveOrExpression);
     // exp -> and_expression
-    let (child_node, pos) = p_and_expression(toks, pos)?;
+    let (child_node, pos) = p_and_expression(toks, pos, ctx)?;
     let mut l_type = child_node.type_exp.clone();
-    let mut tok = &toks[pos];
+    let mut tok = kind_at(toks, pos);
     if *tok != lexer::TokType::ExclusiveOr {
         cur_node.type_exp = l_type;
         cur_node.child.push(child_node);
@@ -999,7 +1441,7 @@ fn p_exclusive_or_expression(
         let mut bincur_node = ParseNode::new(NodeType::BinaryExpression(tok.clone()));
         pos = pos + 1;
         let op = tok.clone();
-        let (next_child_node, tmp_pos) = p_and_expression(toks, pos)?;
+        let (next_child_node, tmp_pos) = p_and_expression(toks, pos, ctx)?;
         let r_type = next_child_node.type_exp.clone();
         pos = tmp_pos;
         bincur_node.child.push(child_node);
@@ -1014,7 +1456,7 @@ fn p_exclusive_or_expression(
         }
         child_node = bincur_node;
         l_type = child_node.type_exp.clone();
-        tok = &toks[pos];
+        tok = kind_at(toks, pos);
     }
     cur_node.type_exp = child_node.type_exp.clone();
     cur_node.child.push(child_node);
@@ -1027,16 +1469,17 @@ fn p_exclusive_or_expression(
 // 	;
 //  -> exclusive_or_expression { '|' exclusive_or_expression }
 fn p_inclusive_or_expression(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
     let mut cur_node = ParseNode::new(NodeType::InclusiveOrExpression);
     // exp -> exclusive_or_expression
-    let (child_node, pos) = p_exclusive_or_expression(toks, pos)?;
+    let (child_node, pos) = p_exclusive_or_expression(toks, pos, ctx)?;
     let mut l_type = child_node.type_exp.clone();
-    let mut tok = &toks[pos];
+    let mut tok = kind_at(toks, pos);
     if *tok != lexer::TokType::InclusiveOr {
         cur_node.type_exp = l_type;
         cur_node.child.push(child_node);
@@ -1049,7 +1492,7 @@ fn p_inclusive_or_expression(
         let mut bincur_node = ParseNode::new(NodeType::BinaryExpression(tok.clone()));
         pos = pos + 1;
         let op = tok.clone();
-        let (next_child_node, tmp_pos) = p_exclusive_or_expression(toks, pos)?;
+        let (next_child_node, tmp_pos) = p_exclusive_or_expression(toks, pos, ctx)?;
         let r_type = next_child_node.type_exp.clone();
         pos = tmp_pos;
         bincur_node.child.push(child_node);
@@ -1064,7 +1507,7 @@ fn p_inclusive_or_expression(
         }
         child_node = bincur_node;
         l_type = child_node.type_exp.clone();
-        tok = &toks[pos];
+        tok = kind_at(toks, pos);
     }
     cur_node.type_exp = child_node.type_exp.clone();
     cur_node.child.push(child_node);
@@ -1077,16 +1520,17 @@ fn p_inclusive_or_expression(
 // 	;
 //  -> inclusive_or_expression { AndOp inclusive_or_expression }
 fn p_logical_and_expression(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
     let mut cur_node = ParseNode::new(NodeType::LogicalAndExpression);
     // exp -> inclusive_or_expression
-    let (child_node, pos) = p_inclusive_or_expression(toks, pos)?;
+    let (child_node, pos) = p_inclusive_or_expression(toks, pos, ctx)?;
     let mut l_type = child_node.type_exp.clone();
-    let mut tok = &toks[pos];
+    let mut tok = kind_at(toks, pos);
     if *tok != lexer::TokType::AndOp {
         cur_node.type_exp = l_type;
         cur_node.child.push(child_node);
@@ -1099,7 +1543,7 @@ fn p_logical_and_expression(
         let mut bincur_node = ParseNode::new(NodeType::BinaryExpression(tok.clone()));
         pos = pos + 1;
         let op = tok.clone();
-        let (next_child_node, tmp_pos) = p_inclusive_or_expression(toks, pos)?;
+        let (next_child_node, tmp_pos) = p_inclusive_or_expression(toks, pos, ctx)?;
         let r_type = next_child_node.type_exp.clone();
         pos = tmp_pos;
         bincur_node.child.push(child_node);
@@ -1114,7 +1558,7 @@ fn p_logical_and_expression(
         }
         child_node = bincur_node;
         l_type = child_node.type_exp.clone();
-        tok = &toks[pos];
+        tok = kind_at(toks, pos);
     }
     cur_node.type_exp = child_node.type_exp.clone();
     cur_node.child.push(child_node);
@@ -1127,16 +1571,17 @@ fn p_logical_and_expression(
 // 	;
 //  -> logical_and_expression { OrOp logical_and_expression }
 fn p_logical_or_expression(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
     let mut cur_node = ParseNode::new(NodeType::LogicalOrExpression);
     // exp -> logical_and_expression
-    let (child_node, pos) = p_logical_and_expression(toks, pos)?;
+    let (child_node, pos) = p_logical_and_expression(toks, pos, ctx)?;
     let mut l_type = child_node.type_exp.clone();
-    let mut tok = &toks[pos];
+    let mut tok = kind_at(toks, pos);
     if *tok != lexer::TokType::OrOp {
         cur_node.type_exp = l_type;
         cur_node.child.push(child_node);
@@ -1149,7 +1594,7 @@ fn p_logical_or_expression(
         let mut bincur_node = ParseNode::new(NodeType::BinaryExpression(tok.clone()));
         pos = pos + 1;
         let op = tok.clone();
-        let (next_child_node, tmp_pos) = p_logical_and_expression(toks, pos)?;
+        let (next_child_node, tmp_pos) = p_logical_and_expression(toks, pos, ctx)?;
         let r_type = next_child_node.type_exp.clone();
         pos = tmp_pos;
         bincur_node.child.push(child_node);
@@ -1164,7 +1609,7 @@ fn p_logical_or_expression(
         }
         child_node = bincur_node;
         l_type = child_node.type_exp.clone();
-        tok = &toks[pos];
+        tok = kind_at(toks, pos);
     }
     cur_node.type_exp = child_node.type_exp.clone();
     cur_node.child.push(child_node);
@@ -1176,8 +1621,9 @@ fn p_logical_or_expression(
 // 	| logical_or_expression '?' expression ':' conditional_expression
 // 	;
 fn p_conditional_expression(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     // XXX: should make sure expression and conditional_expression are the same type.
     //      the final conditional expression type would be expression type,
@@ -1186,9 +1632,12 @@ fn p_conditional_expression(
     check_pos(pos, toks.len())?;
 
     let mut cur_node = ParseNode::new(NodeType::ConditionalExpression);
-    if let Ok((child_node, pos)) = p_logical_or_expression(toks, pos) {
+    if let Ok((child_node, pos)) = p_logical_or_expression(toks, pos, ctx) {
         cur_node.type_exp = child_node.type_exp.clone();
-        if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::QuestionMark) {
+        // the binary operators of all the levels stopped here too
+        let (span, found) = found_at(toks, pos, ctx);
+        ctx.expected_at(span, &found, "an operator");
+        if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::QuestionMark, ctx) {
             // first judge logical_or_expression is IConstant.
             if sema::judge_type_same(
                 &child_node.type_exp,
@@ -1217,13 +1666,13 @@ fn p_conditional_expression(
             }
             cur_node.child.push(child_node);
             let pos = pos + 1;
-            let (child_node, pos) = p_expression(toks, pos)?;
+            let (child_node, pos) = p_expression(toks, pos, ctx)?;
             let l_type = child_node.type_exp.clone();
 
             cur_node.child.push(child_node);
-            check_tok(pos, &toks, &lexer::TokType::Colon)?;
+            check_tok(pos, &toks, &lexer::TokType::Colon, ctx)?;
             let pos = pos + 1;
-            let (child_node, pos) = p_conditional_expression(toks, pos)?;
+            let (child_node, pos) = p_conditional_expression(toks, pos, ctx)?;
             let r_type = child_node.type_exp.clone();
 
             // TODO: actually they don't need to have same type, but need to be able to convert to the same type.
@@ -1251,40 +1700,38 @@ fn p_conditional_expression(
 // 	;
 
 fn p_assignment_expression(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node = ParseNode::new(NodeType::AssignmentExpression);
-    if let Ok((child_node1, pos1)) = p_unary_expression(toks, pos) {
-        if let Ok((child_node2, pos2)) = p_assignment_operator(toks, pos1) {
-            if let Ok((child_node3, pos3)) = p_assignment_expression(toks, pos2) {
-                let l_type = child_node1.type_exp.clone();
-                let r_type = child_node3.type_exp.clone();
-                cur_node.child.push(child_node1);
-                cur_node.child.push(child_node2);
-                cur_node.child.push(child_node3);
-                let res_type = sema::implicit_type_cast(&l_type, &r_type)?;
-                cur_node.type_exp = res_type.clone();
-                return Ok((cur_node, pos3));
-            } else {
-                let (child_node, pos) = p_conditional_expression(toks, pos)?;
-                cur_node.type_exp = child_node.type_exp.clone();
-                cur_node.child.push(child_node);
-                return Ok((cur_node, pos));
+    match p_unary_expression(toks, pos, ctx) {
+        Ok((child_node1, pos1)) => {
+            // a missing assignment operator is not a failure, the conditional_expression
+            // says what else can follow
+            if is_assignment_operator(kind_at(toks, pos1)) {
+                let (child_node2, pos2) = p_assignment_operator(toks, pos1, ctx)?;
+                if let Ok((child_node3, pos3)) = p_assignment_expression(toks, pos2, ctx) {
+                    let l_type = child_node1.type_exp.clone();
+                    let r_type = child_node3.type_exp.clone();
+                    cur_node.child.push(child_node1);
+                    cur_node.child.push(child_node2);
+                    cur_node.child.push(child_node3);
+                    let res_type = sema::implicit_type_cast(&l_type, &r_type)?;
+                    cur_node.type_exp = res_type.clone();
+                    return Ok((cur_node, pos3));
+                }
             }
-        } else {
-            let (child_node, pos) = p_conditional_expression(toks, pos)?;
-            cur_node.type_exp = child_node.type_exp.clone();
-            cur_node.child.push(child_node);
-            return Ok((cur_node, pos));
+            // the conditional_expression starts with this unary_expression
+            ctx.memoise("unary_expression", pos, Ok((child_node1, pos1)));
         }
-    } else {
-        let (child_node, pos) = p_conditional_expression(toks, pos)?;
-        cur_node.type_exp = child_node.type_exp.clone();
-        cur_node.child.push(child_node);
-        return Ok((cur_node, pos));
+        Err(e) => ctx.memoise("unary_expression", pos, Err(e)),
     }
+    let (child_node, pos) = p_conditional_expression(toks, pos, ctx)?;
+    cur_node.type_exp = child_node.type_exp.clone();
+    cur_node.child.push(child_node);
+    return Ok((cur_node, pos));
 }
 
 // assignment_operator
@@ -1301,12 +1748,23 @@ fn p_assignment_expression(
 // 	| OrAssign
 // 	;
 fn p_assignment_operator(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
-    match &toks[pos] {
+    if is_assignment_operator(&toks[pos].kind) {
+        return Ok((
+            ParseNode::new(NodeType::AssignmentOperator(toks[pos].kind.clone())),
+            pos + 1,
+        ));
+    }
+    return Err(error_handler("an assignment operator", toks, pos, ctx));
+}
+
+fn is_assignment_operator(kind: &lexer::TokType) -> bool {
+    match kind {
         lexer::TokType::Assign
         | lexer::TokType::MulAssign
         | lexer::TokType::DivAssign
@@ -1317,15 +1775,8 @@ fn p_assignment_operator(
         | lexer::TokType::RightAssign
         | lexer::TokType::AndAssign
         | lexer::TokType::XorAssign
-        | lexer::TokType::OrAssign => {
-            return Ok((
-                ParseNode::new(NodeType::AssignmentOperator(toks[pos].clone())),
-                pos + 1,
-            ));
-        }
-        _ => {
-            return Err(error_handler("Assignment operator", &toks[pos], pos));
-        }
+        | lexer::TokType::OrAssign => return true,
+        _ => return false,
     }
 }
 
@@ -1334,25 +1785,29 @@ fn p_assignment_operator(
 // 	| expression ',' assignment_expression
 // 	;
 //  -> assignment_expression { ',' assignment_expression }
-fn p_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_expression(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
     let mut cur_node: ParseNode = ParseNode::new(NodeType::Expression);
 
-    let (child_node, pos) = p_assignment_expression(toks, pos)?; // if error, then out
+    let (child_node, pos) = p_assignment_expression(toks, pos, ctx)?; // if error, then out
     let pre_type = child_node.type_exp.clone();
     cur_node.type_exp.child.push(child_node.type_exp.clone());
     cur_node.child.push(child_node);
     let mut inc = 0;
     let mut pos: usize = pos;
     loop {
-        if let Err(_) = check_tok(pos, &toks, &lexer::TokType::Comma) {
+        if let Err(_) = check_tok(pos, &toks, &lexer::TokType::Comma, ctx) {
             break;
         } else {
             pos = pos + 1;
         }
 
-        match p_assignment_expression(toks, pos) {
+        match p_assignment_expression(toks, pos, ctx) {
             Ok((child_node, tmp_pos)) => {
                 inc += 1;
                 // pick the right most assignment_expression's type as its type.
@@ -1376,13 +1831,14 @@ fn p_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize
 // 	: conditional_expression	/* with constraints */
 // 	;
 fn p_constant_expression(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node: ParseNode = ParseNode::new(NodeType::ConstantExpression);
 
-    let (child_node, pos) = p_conditional_expression(toks, pos)?;
+    let (child_node, pos) = p_conditional_expression(toks, pos, ctx)?;
     cur_node.type_exp = child_node.type_exp.clone();
     cur_node.child.push(child_node);
 
@@ -1393,30 +1849,36 @@ fn p_constant_expression(
 // 	| declaration_specifiers init_declarator_list ';'
 // 	| static_assert_declaration
 // 	;
-fn p_declaration(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_declaration(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node = ParseNode::new(NodeType::Declaration);
-    if let Ok((child_node, pos)) = p_declaration_specifiers(toks, pos) {
-        if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Semicolon) {
+    if let Ok((child_node, pos)) = p_declaration_specifiers(toks, pos, ctx) {
+        if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Semicolon, ctx) {
             cur_node.type_exp = child_node.type_exp.clone();
             cur_node.child.push(child_node);
+            declare_declaration(&cur_node, ctx);
             return Ok((cur_node, pos + 1));
         } else {
             cur_node.type_exp.child.push(child_node.type_exp.clone());
             cur_node.child.push(child_node);
 
-            let (child_node, pos) = p_init_declarator_list(toks, pos)?;
+            let (child_node, pos) = p_init_declarator_list(toks, pos, ctx)?;
             cur_node.type_exp.child.push(child_node.type_exp.clone());
             cur_node.child.push(child_node);
 
-            if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Semicolon) {
+            if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Semicolon, ctx) {
                 let pos = pos + 1;
+                declare_declaration(&cur_node, ctx);
                 return Ok((cur_node, pos));
             } else {
-                return Err(error_handler(";", &toks[pos], pos));
+                return Err(error_handler("`;`", toks, pos, ctx));
             }
         }
-    } else if let Ok((child_node, pos)) = p_static_assert_declaration(toks, pos) {
+    } else if let Ok((child_node, pos)) = p_static_assert_declaration(toks, pos, ctx) {
         cur_node.type_exp = child_node.type_exp.clone();
         cur_node.child.push(child_node);
         return Ok((cur_node, pos));
@@ -1438,17 +1900,29 @@ fn p_declaration(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usiz
 // 	| alignment_specifier
 // 	;
 fn p_declaration_specifiers(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
+    return p_declaration_specifiers_after(toks, pos, false, ctx);
+}
+
+// has_type: a type specifier is before them, then an identifier is not a typedef name
+// but the declarator, like `T` in `typedef char T; void f() { int T; }`
+fn p_declaration_specifiers_after(
+    toks: &[lexer::Token],
+    pos: usize,
+    has_type: bool,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
     let mut cur_node = ParseNode::new(NodeType::DeclarationSpecifiers);
 
-    if let Ok((child_node, pos)) = p_storage_class_specifier(toks, pos) {
+    if let Ok((child_node, pos)) = p_storage_class_specifier(toks, pos, ctx) {
         let pre_type = child_node.type_exp.clone();
         cur_node.child.push(child_node);
-        if let Ok((child_node, pos)) = p_declaration_specifiers(toks, pos) {
+        if let Ok((child_node, pos)) = p_declaration_specifiers_after(toks, pos, has_type, ctx) {
             cur_node.type_exp.child.push(pre_type);
             cur_node.type_exp.child.push(child_node.type_exp.clone());
             cur_node.child.push(child_node);
@@ -1457,10 +1931,10 @@ fn p_declaration_specifiers(
             cur_node.type_exp = pre_type;
             return Ok((cur_node, pos));
         }
-    } else if let Ok((child_node, pos)) = p_type_specifier(toks, pos) {
+    } else if let Ok((child_node, pos)) = p_type_specifier_after(toks, pos, has_type, ctx) {
         let pre_type = child_node.type_exp.clone();
         cur_node.child.push(child_node);
-        if let Ok((child_node, pos)) = p_declaration_specifiers(toks, pos) {
+        if let Ok((child_node, pos)) = p_declaration_specifiers_after(toks, pos, true, ctx) {
             cur_node.type_exp.child.push(pre_type);
             cur_node.type_exp.child.push(child_node.type_exp.clone());
             cur_node.child.push(child_node);
@@ -1469,10 +1943,10 @@ fn p_declaration_specifiers(
             cur_node.type_exp = pre_type;
             return Ok((cur_node, pos));
         }
-    } else if let Ok((child_node, pos)) = p_type_qualifier(toks, pos) {
+    } else if let Ok((child_node, pos)) = p_type_qualifier(toks, pos, ctx) {
         let pre_type = child_node.type_exp.clone();
         cur_node.child.push(child_node);
-        if let Ok((child_node, pos)) = p_declaration_specifiers(toks, pos) {
+        if let Ok((child_node, pos)) = p_declaration_specifiers_after(toks, pos, has_type, ctx) {
             cur_node.type_exp.child.push(pre_type);
             cur_node.type_exp.child.push(child_node.type_exp.clone());
             cur_node.child.push(child_node);
@@ -1481,10 +1955,10 @@ fn p_declaration_specifiers(
             cur_node.type_exp = pre_type;
             return Ok((cur_node, pos));
         }
-    } else if let Ok((child_node, pos)) = p_function_specifier(toks, pos) {
+    } else if let Ok((child_node, pos)) = p_function_specifier(toks, pos, ctx) {
         let pre_type = child_node.type_exp.clone();
         cur_node.child.push(child_node);
-        if let Ok((child_node, pos)) = p_declaration_specifiers(toks, pos) {
+        if let Ok((child_node, pos)) = p_declaration_specifiers_after(toks, pos, has_type, ctx) {
             cur_node.type_exp.child.push(pre_type);
             cur_node.type_exp.child.push(child_node.type_exp.clone());
             cur_node.child.push(child_node);
@@ -1493,10 +1967,10 @@ fn p_declaration_specifiers(
             cur_node.type_exp = pre_type;
             return Ok((cur_node, pos));
         }
-    } else if let Ok((child_node, pos)) = p_alignment_specifier(toks, pos) {
+    } else if let Ok((child_node, pos)) = p_alignment_specifier(toks, pos, ctx) {
         let pre_type = child_node.type_exp.clone();
         cur_node.child.push(child_node);
-        if let Ok((child_node, pos)) = p_declaration_specifiers(toks, pos) {
+        if let Ok((child_node, pos)) = p_declaration_specifiers_after(toks, pos, has_type, ctx) {
             cur_node.type_exp.child.push(pre_type);
             cur_node.type_exp.child.push(child_node.type_exp.clone());
             cur_node.child.push(child_node);
@@ -1517,14 +1991,15 @@ fn p_declaration_specifiers(
 //  -> init_declarator { ',' init_declarator }
 
 fn p_init_declarator_list(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
     let mut cur_node: ParseNode = ParseNode::new(NodeType::InitDeclaratorList);
 
-    let (child_node, pos) = p_init_declarator(toks, pos)?; // if error, then out
+    let (child_node, pos) = p_init_declarator(toks, pos, ctx)?; // if error, then out
     let pre_type = child_node.type_exp.clone();
     let mut inc = 0;
 
@@ -1533,13 +2008,13 @@ fn p_init_declarator_list(
 
     let mut pos: usize = pos;
     loop {
-        if let Err(_) = check_tok(pos, &toks, &lexer::TokType::Comma) {
+        if let Err(_) = check_tok(pos, &toks, &lexer::TokType::Comma, ctx) {
             break;
         } else {
             pos = pos + 1;
         }
 
-        match p_init_declarator(toks, pos) {
+        match p_init_declarator(toks, pos, ctx) {
             Ok((child_node, tmp_pos)) => {
                 inc += 1;
 
@@ -1565,16 +2040,20 @@ fn p_init_declarator_list(
 // 	: declarator '=' initializer
 // 	| declarator
 // 	;
-fn p_init_declarator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_init_declarator(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node = ParseNode::new(NodeType::InitDeclarator);
 
-    if let Ok((child_node, pos)) = p_declarator(toks, pos) {
+    if let Ok((child_node, pos)) = p_declarator(toks, pos, ctx) {
         let pre_type = child_node.type_exp.clone();
         cur_node.child.push(child_node);
-        if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Assign) {
+        if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Assign, ctx) {
             let pos = pos + 1;
-            let (child_node, pos) = p_initializer(toks, pos)?;
+            let (child_node, pos) = p_initializer(toks, pos, ctx)?;
 
             if sema::judge_type_same(&pre_type, &child_node.type_exp) {
                 // ok
@@ -1604,42 +2083,45 @@ fn p_init_declarator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
 // 	| REGISTER
 // 	;
 fn p_storage_class_specifier(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
-    match &toks[pos] {
+    match &toks[pos].kind {
         lexer::TokType::TYPEDEF => {
-            return Err(format!("Typedef is not supported in crust now"));
+            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
+            cur_node.type_exp = TypeExpression::new_val(BaseType::Typedef);
+            return Ok((cur_node, pos + 1));
         }
         lexer::TokType::EXTERN => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Extern);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::STATIC => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Static);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::ThreadLocal => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::ThreadLocal);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::AUTO => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Auto);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::REGISTER => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Register);
             return Ok((cur_node, pos + 1));
         }
         _ => {
-            return Err(error_handler("storage_class_specifier", &toks[pos], pos));
+            return Err(error_handler("a storage class specifier", toks, pos, ctx));
         }
     }
 }
@@ -1662,86 +2144,90 @@ fn p_storage_class_specifier(
 // 	| enum_specifier
 // 	| TypedefName		/* after it has been defined as such */
 // 	;
-fn p_type_specifier(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    match &toks[pos] {
+fn p_type_specifier(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
+    match &toks[pos].kind {
         lexer::TokType::VOID => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Void);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::CHAR => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Char);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::SHORT => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Short);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::INT => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Int);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::LONG => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Long);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::FLOAT => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Float);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::DOUBLE => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Double);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::SIGNED => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Signed);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::UNSIGNED => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Unsigned);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::BOOL => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Bool);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::COMPLEX => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Complex);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::IMAGINARY => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Imaginary);
             return Ok((cur_node, pos + 1));
         }
-        lexer::TokType::TypedefName => {
-            // XXX: now can not handle typedef
-            return Err(format!("Typedef is not supported in crust now"));
-            // let cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
-            // cur_node.type_exp = TypeExpression::new_val(BaseType::Typedef);
-            // return Ok((cur_node, pos + 1));
+        lexer::TokType::IDENTIFIER(symbol) if ctx.is_typedef_name(*symbol) => {
+            let name = ctx.interner.name(*symbol);
+            let tok = lexer::TokType::TypedefName(name.to_string());
+            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(tok)));
+            cur_node.type_exp = TypeExpression::new_val(BaseType::TypedefName(name.to_string()));
+            return Ok((cur_node, pos + 1));
         }
         _ => {
             let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(None));
-            if let Ok((child_node, pos)) = p_atomic_type_specifier(toks, pos) {
+            if let Ok((child_node, pos)) = p_atomic_type_specifier(toks, pos, ctx) {
                 cur_node.type_exp = child_node.type_exp.clone();
                 cur_node.child.push(child_node);
                 return Ok((cur_node, pos));
-            } else if let Ok((child_node, pos)) = p_struct_or_union_specifier(toks, pos) {
+            } else if let Ok((child_node, pos)) = p_struct_or_union_specifier(toks, pos, ctx) {
                 cur_node.type_exp = child_node.type_exp.clone();
                 cur_node.child.push(child_node);
                 return Ok((cur_node, pos));
-            } else if let Ok((child_node, pos)) = p_enum_specifier(toks, pos) {
+            } else if let Ok((child_node, pos)) = p_enum_specifier(toks, pos, ctx) {
                 cur_node.type_exp = child_node.type_exp.clone();
                 cur_node.child.push(child_node);
                 return Ok((cur_node, pos));
@@ -1752,45 +2238,63 @@ fn p_type_specifier(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
     }
 }
 
+fn p_type_specifier_after(
+    toks: &[lexer::Token],
+    pos: usize,
+    has_type: bool,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks.len())?;
+    match &toks[pos].kind {
+        lexer::TokType::IDENTIFIER(_) if has_type => {
+            return Err(error_handler("a type specifier", toks, pos, ctx));
+        }
+        _ => {
+            return p_type_specifier(toks, pos, ctx);
+        }
+    }
+}
+
 // struct_or_union_specifier
 // 	: struct_or_union '{' struct_declaration_list '}'
 // 	| struct_or_union IDENTIFIER '{' struct_declaration_list '}'
 // 	| struct_or_union IDENTIFIER
 // 	;
 fn p_struct_or_union_specifier(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node = ParseNode::new(NodeType::StructOrUnionSpecifier);
-    let (child_node, pos) = p_struct_or_union(toks, pos)?;
+    let (child_node, pos) = p_struct_or_union(toks, pos, ctx)?;
     cur_node.type_exp.child.push(child_node.type_exp.clone());
     cur_node.child.push(child_node);
 
-    if let Ok((c, pos)) = p_identifier(toks, pos) {
+    if let Ok((c, pos)) = p_identifier(toks, pos, ctx) {
         cur_node.type_exp.child.push(c.type_exp.clone());
         cur_node.child.push(c);
-        if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::LBrace) {
+        if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::LBrace, ctx) {
             let pos = pos + 1;
 
-            let (child_node, pos) = p_struct_declaration_list(toks, pos)?;
+            let (child_node, pos) = p_struct_declaration_list(toks, pos, ctx)?;
             cur_node.type_exp.child.push(child_node.type_exp.clone());
             cur_node.child.push(child_node);
-            check_tok(pos, &toks, &lexer::TokType::RBrace)?;
+            check_tok(pos, &toks, &lexer::TokType::RBrace, ctx)?;
             let pos = pos + 1;
             return Ok((cur_node, pos));
         } else {
             return Ok((cur_node, pos));
         }
     } else {
-        check_tok(pos, &toks, &lexer::TokType::LBrace)?;
+        check_tok(pos, &toks, &lexer::TokType::LBrace, ctx)?;
         let pos = pos + 1;
 
-        let (c, pos) = p_struct_declaration_list(toks, pos)?;
+        let (c, pos) = p_struct_declaration_list(toks, pos, ctx)?;
         cur_node.type_exp.child.push(c.type_exp.clone());
         cur_node.child.push(c);
 
-        check_tok(pos, &toks, &lexer::TokType::RParen)?;
+        check_tok(pos, toks, &lexer::TokType::RBrace, ctx)?;
         let pos = pos + 1;
 
         return Ok((cur_node, pos));
@@ -1801,23 +2305,27 @@ fn p_struct_or_union_specifier(
 // 	: STRUCT
 // 	| UNION
 // 	;
-fn p_struct_or_union(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_struct_or_union(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
-    match &toks[pos] {
+    match &toks[pos].kind {
         lexer::TokType::STRUCT => {
-            let mut cur_node = ParseNode::new(NodeType::StructOrUnion(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::StructOrUnion(toks[pos].kind.clone()));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Struct);
 
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::UNION => {
-            let mut cur_node = ParseNode::new(NodeType::StructOrUnion(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::StructOrUnion(toks[pos].kind.clone()));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Union);
 
             return Ok((cur_node, pos + 1));
         }
         _ => {
-            return Err(error_handler("struct or union", &toks[pos], pos));
+            return Err(error_handler("`struct` or `union`", toks, pos, ctx));
         }
     }
 }
@@ -1827,20 +2335,21 @@ fn p_struct_or_union(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
 // 	;
 //  -> struct_declaration { struct_declaration }
 fn p_struct_declaration_list(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node: ParseNode = ParseNode::new(NodeType::StructDeclarationList);
 
-    let (child_node, pos) = p_struct_declaration(toks, pos)?;
+    let (child_node, pos) = p_struct_declaration(toks, pos, ctx)?;
     let pre_type = child_node.type_exp.clone();
     let mut inc = 0;
 
     cur_node.type_exp.child.push(child_node.type_exp.clone());
     cur_node.child.push(child_node);
     let mut pos: usize = pos;
-    while let Ok((child_node, tmp_pos)) = p_struct_declaration(toks, pos) {
+    while let Ok((child_node, tmp_pos)) = p_struct_declaration(toks, pos, ctx) {
         inc += 1;
         cur_node.type_exp.child.push(child_node.type_exp.clone());
         cur_node.child.push(child_node);
@@ -1856,31 +2365,35 @@ fn p_struct_declaration_list(
 // 	| specifier_qualifier_list struct_declarator_list ';'
 // 	| static_assert_declaration
 // 	;
-fn p_struct_declaration(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_struct_declaration(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node = ParseNode::new(NodeType::StructDeclaration);
-    if let Ok((child_node, pos)) = p_specifier_qualifier_list(toks, pos) {
+    if let Ok((child_node, pos)) = p_specifier_qualifier_list(toks, pos, ctx) {
         let pre_type = child_node.type_exp.clone();
         cur_node.child.push(child_node);
 
-        if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Semicolon) {
+        if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Semicolon, ctx) {
             let pos = pos + 1;
             cur_node.type_exp = pre_type;
             return Ok((cur_node, pos));
         }
 
-        let (child_node, pos) = p_struct_declarator_list(toks, pos)?;
+        let (child_node, pos) = p_struct_declarator_list(toks, pos, ctx)?;
         cur_node.type_exp.child.push(pre_type);
         cur_node.type_exp.child.push(child_node.type_exp.clone());
         cur_node.child.push(child_node);
 
-        if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Semicolon) {
+        if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Semicolon, ctx) {
             let pos = pos + 1;
             return Ok((cur_node, pos));
         } else {
-            return Err(error_handler(";", &toks[pos], pos));
+            return Err(error_handler("`;`", toks, pos, ctx));
         }
-    } else if let Ok((child_node, pos)) = p_static_assert_declaration(toks, pos) {
+    } else if let Ok((child_node, pos)) = p_static_assert_declaration(toks, pos, ctx) {
         cur_node.type_exp = child_node.type_exp.clone();
         cur_node.child.push(child_node);
         return Ok((cur_node, pos));
@@ -1895,15 +2408,25 @@ fn p_struct_declaration(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNod
 // 	| type_qualifier
 // 	;
 fn p_specifier_qualifier_list(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
+    return p_specifier_qualifier_list_after(toks, pos, false, ctx);
+}
+
+fn p_specifier_qualifier_list_after(
+    toks: &[lexer::Token],
+    pos: usize,
+    has_type: bool,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node = ParseNode::new(NodeType::SpecifierQualifier);
-    if let Ok((child_node, pos)) = p_type_specifier(toks, pos) {
+    if let Ok((child_node, pos)) = p_type_specifier_after(toks, pos, has_type, ctx) {
         let pre_type = child_node.type_exp.clone();
         cur_node.child.push(child_node);
-        if let Ok((child_node, pos)) = p_specifier_qualifier_list(toks, pos) {
+        if let Ok((child_node, pos)) = p_specifier_qualifier_list_after(toks, pos, true, ctx) {
             cur_node.type_exp.child.push(pre_type);
             cur_node.type_exp.child.push(child_node.type_exp.clone());
             cur_node.child.push(child_node);
@@ -1912,10 +2435,10 @@ fn p_specifier_qualifier_list(
             cur_node.type_exp = pre_type;
             return Ok((cur_node, pos));
         }
-    } else if let Ok((child_node, pos)) = p_type_qualifier(toks, pos) {
+    } else if let Ok((child_node, pos)) = p_type_qualifier(toks, pos, ctx) {
         let pre_type = child_node.type_exp.clone();
         cur_node.child.push(child_node);
-        if let Ok((child_node, pos)) = p_specifier_qualifier_list(toks, pos) {
+        if let Ok((child_node, pos)) = p_specifier_qualifier_list_after(toks, pos, has_type, ctx) {
             cur_node.type_exp.child.push(pre_type);
             cur_node.type_exp.child.push(child_node.type_exp.clone());
             cur_node.child.push(child_node);
@@ -1934,14 +2457,15 @@ fn p_specifier_qualifier_list(
 // 	;
 //  -> struct_declarator { ',' struct_declarator }
 fn p_struct_declarator_list(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
     let mut cur_node: ParseNode = ParseNode::new(NodeType::StructDeclaratorList);
 
-    let (child_node, pos) = p_struct_declarator(toks, pos)?; // if error, then out
+    let (child_node, pos) = p_struct_declarator(toks, pos, ctx)?; // if error, then out
     let pre_type = child_node.type_exp.clone();
     let mut inc = 0;
 
@@ -1950,13 +2474,13 @@ fn p_struct_declarator_list(
 
     let mut pos: usize = pos;
     loop {
-        if let Err(_) = check_tok(pos, &toks, &lexer::TokType::Comma) {
+        if let Err(_) = check_tok(pos, &toks, &lexer::TokType::Comma, ctx) {
             break;
         } else {
             pos = pos + 1;
         }
 
-        match p_struct_declarator(toks, pos) {
+        match p_struct_declarator(toks, pos, ctx) {
             Ok((child_node, tmp_pos)) => {
                 inc += 1;
                 cur_node.type_exp.child.push(child_node.type_exp.clone());
@@ -1980,23 +2504,27 @@ fn p_struct_declarator_list(
 // 	| declarator ':' constant_expression
 // 	| declarator
 // 	;
-fn p_struct_declarator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_struct_declarator(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node = ParseNode::new(NodeType::StructDeclarator);
-    if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Colon) {
+    if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Colon, ctx) {
         let pos = pos + 1;
-        let (child_node, pos) = p_constant_expression(toks, pos)?;
+        let (child_node, pos) = p_constant_expression(toks, pos, ctx)?;
         cur_node.type_exp = child_node.type_exp.clone();
         cur_node.child.push(child_node);
         return Ok((cur_node, pos));
     } else {
-        let (child_node, pos) = p_declarator(toks, pos)?;
+        let (child_node, pos) = p_declarator(toks, pos, ctx)?;
         let pre_type = child_node.type_exp.clone();
 
         cur_node.type_exp.child.push(child_node.type_exp.clone());
         cur_node.child.push(child_node);
-        if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Colon) {
-            let (child_node, pos) = p_constant_expression(toks, pos)?;
+        if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Colon, ctx) {
+            let (child_node, pos) = p_constant_expression(toks, pos, ctx)?;
             cur_node.type_exp.child.push(child_node.type_exp.clone());
             cur_node.child.push(child_node);
             return Ok((cur_node, pos));
@@ -2015,61 +2543,69 @@ fn p_struct_declarator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode
 // 	| ENUM IDENTIFIER
 // 	;
 // TODO: Add type system
-fn p_enum_specifier(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_enum_specifier(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
-    check_tok(pos, &toks, &lexer::TokType::ENUM)?;
+    check_tok(pos, &toks, &lexer::TokType::ENUM, ctx)?;
     let pos = pos + 1;
-    if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::LBrace) {
+    if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::LBrace, ctx) {
         let mut cur_node = ParseNode::new(NodeType::EnumSpecifier(None));
-        let (child_node, pos) = p_enumerator_list(toks, pos)?;
+        let pos = pos + 1;
+        let (child_node, pos) = p_enumerator_list(toks, pos, ctx)?;
         cur_node.child.push(child_node);
 
-        if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RBrace) {
+        if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RBrace, ctx) {
             let pos = pos + 1;
             return Ok((cur_node, pos));
         }
 
-        if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Comma) {
+        if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Comma, ctx) {
             let pos = pos + 1;
-            if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RBrace) {
+            if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RBrace, ctx) {
                 let pos = pos + 1;
                 return Ok((cur_node, pos));
             } else {
-                return Err(error_handler("}", &toks[pos], pos));
+                return Err(error_handler("`}`", toks, pos, ctx));
             }
         } else {
-            return Err(error_handler("}", &toks[pos], pos));
+            return Err(error_handler("`}`", toks, pos, ctx));
         }
     } else {
-        match &toks[pos] {
-            lexer::TokType::IDENTIFIER(name) => {
+        match kind_at(toks, pos) {
+            lexer::TokType::IDENTIFIER(symbol) => {
+                let name = ctx.interner.name(*symbol);
                 let mut cur_node = ParseNode::new(NodeType::EnumSpecifier(Some(name.to_string())));
                 let pos = pos + 1;
-                if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::LBrace) {
-                    let (child_node, pos) = p_enumerator_list(toks, pos)?;
+                if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::LBrace, ctx) {
+                    let pos = pos + 1;
+                    let (child_node, pos) = p_enumerator_list(toks, pos, ctx)?;
                     cur_node.child.push(child_node);
-                    if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RBrace) {
+                    if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RBrace, ctx) {
                         let pos = pos + 1;
                         return Ok((cur_node, pos));
                     }
 
-                    if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Comma) {
+                    if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Comma, ctx) {
                         let pos = pos + 1;
-                        if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RBrace) {
+                        if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RBrace, ctx) {
                             let pos = pos + 1;
                             return Ok((cur_node, pos));
                         } else {
-                            return Err(error_handler("}", &toks[pos], pos));
+                            return Err(error_handler("`}`", toks, pos, ctx));
                         }
                     } else {
-                        return Err(error_handler("}", &toks[pos], pos));
+                        return Err(error_handler("`}`", toks, pos, ctx));
                     }
                 } else {
-                    return Err(error_handler("}", &toks[pos], pos));
+                    // ENUM IDENTIFIER
+                    return Ok((cur_node, pos));
                 }
             }
             _ => {
-                return Err(error_handler("`{` or identifier", &toks[pos], pos));
+                return Err(error_handler("`{` or an identifier", toks, pos, ctx));
             }
         }
     }
@@ -2080,22 +2616,26 @@ fn p_enum_specifier(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
 // 	| enumerator_list ',' enumerator
 // 	;
 //  -> enumerator { ',' enumerator }
-fn p_enumerator_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_enumerator_list(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
     let mut cur_node: ParseNode = ParseNode::new(NodeType::EnumeratorList);
-    let (child_node, pos) = p_enumerator(toks, pos)?; // if error, then out
+    let (child_node, pos) = p_enumerator(toks, pos, ctx)?; // if error, then out
     cur_node.type_exp.child.push(child_node.type_exp.clone());
     cur_node.child.push(child_node);
     let mut pos: usize = pos;
     loop {
-        if let Err(_) = check_tok(pos, &toks, &lexer::TokType::Comma) {
+        if let Err(_) = check_tok(pos, &toks, &lexer::TokType::Comma, ctx) {
             break;
         } else {
             pos = pos + 1;
         }
 
-        match p_enumerator(toks, pos) {
+        match p_enumerator(toks, pos, ctx) {
             Ok((child_node, tmp_pos)) => {
                 cur_node.type_exp.child.push(child_node.type_exp.clone());
                 cur_node.child.push(child_node);
@@ -2114,17 +2654,21 @@ fn p_enumerator_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
 // 	: enumeration_constant '=' constant_expression
 // 	| enumeration_constant
 // 	;
-fn p_enumerator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_enumerator(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node = ParseNode::new(NodeType::Enumerator);
-    let (child_node, pos) = p_enumeration_constant(toks, pos)?;
+    let (child_node, pos) = p_enumeration_constant(toks, pos, ctx)?;
     let pre_type = child_node.type_exp.clone();
     cur_node.child.push(child_node);
 
-    if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Assign) {
+    if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Assign, ctx) {
         cur_node.type_exp.child.push(pre_type);
         let pos = pos + 1;
-        let (child_node, pos) = p_constant_expression(toks, pos)?;
+        let (child_node, pos) = p_constant_expression(toks, pos, ctx)?;
         // cause enum is guaranted to be enough to hold `int`, so ignore `char`
         if sema::judge_type_same(
             &child_node.type_exp,
@@ -2160,24 +2704,25 @@ fn p_enumerator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize
 // 	: ATOMIC '(' type_name ')'
 // 	;
 fn p_atomic_type_specifier(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node = ParseNode::new(NodeType::AtomicTypeSpecifier);
 
-    check_tok(pos, &toks, &lexer::TokType::ATOMIC)?;
+    check_tok(pos, &toks, &lexer::TokType::ATOMIC, ctx)?;
     let pos = pos + 1;
     cur_node.type_exp = TypeExpression::new_val(BaseType::Atomic);
 
-    check_tok(pos, &toks, &lexer::TokType::LParen)?;
+    check_tok(pos, &toks, &lexer::TokType::LParen, ctx)?;
     let pos = pos + 1;
 
-    let (child_node, pos) = p_type_name(toks, pos)?;
+    let (child_node, pos) = p_type_name(toks, pos, ctx)?;
     cur_node.type_exp.child.push(child_node.type_exp.clone());
     cur_node.child.push(child_node);
 
-    check_tok(pos, &toks, &lexer::TokType::RParen)?;
+    check_tok(pos, &toks, &lexer::TokType::RParen, ctx)?;
     let pos = pos + 1;
 
     return Ok((cur_node, pos));
@@ -2188,35 +2733,35 @@ fn p_atomic_type_specifier(
 // 	| VOLATILE
 // 	| ATOMIC
 // 	;
-fn p_type_qualifier(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_type_qualifier(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
-    match &toks[pos] {
+    match &toks[pos].kind {
         lexer::TokType::CONST => {
-            let mut cur_node = ParseNode::new(NodeType::TypeQualifier(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::TypeQualifier(toks[pos].kind.clone()));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Const);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::RESTRICT => {
-            let mut cur_node = ParseNode::new(NodeType::TypeQualifier(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::TypeQualifier(toks[pos].kind.clone()));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Restrict);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::VOLATILE => {
-            let mut cur_node = ParseNode::new(NodeType::TypeQualifier(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::TypeQualifier(toks[pos].kind.clone()));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Volatile);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::ATOMIC => {
-            let mut cur_node = ParseNode::new(NodeType::TypeQualifier(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::TypeQualifier(toks[pos].kind.clone()));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Atomic);
             return Ok((cur_node, pos + 1));
         }
         _ => {
-            return Err(error_handler(
-                "[const, restricted, volatile, atomic]",
-                &toks[pos],
-                pos,
-            ));
+            return Err(error_handler("a type qualifier", toks, pos, ctx));
         }
     }
 }
@@ -2224,22 +2769,26 @@ fn p_type_qualifier(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
 // 	: INLINE
 // 	| NORETURN
 // 	;
-fn p_function_specifier(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_function_specifier(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
-    match &toks[pos] {
+    match &toks[pos].kind {
         lexer::TokType::INLINE => {
-            let mut cur_node = ParseNode::new(NodeType::FunctionSpecifier(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::FunctionSpecifier(toks[pos].kind.clone()));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Inline);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::NORETURN => {
-            let mut cur_node = ParseNode::new(NodeType::FunctionSpecifier(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::FunctionSpecifier(toks[pos].kind.clone()));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Noreturn);
             return Ok((cur_node, pos + 1));
         }
         _ => {
-            return Err(error_handler("[inline, noreturn]", &toks[pos], pos));
+            return Err(error_handler("`inline` or `_Noreturn`", toks, pos, ctx));
         }
     }
 }
@@ -2249,30 +2798,31 @@ fn p_function_specifier(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNod
 // 	;
 // XXX: now just return type non expression
 fn p_alignment_specifier(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
-    check_tok(pos, &toks, &lexer::TokType::ALIGNAS)?;
+    check_tok(pos, &toks, &lexer::TokType::ALIGNAS, ctx)?;
     let pos = pos + 1;
 
-    check_tok(pos, &toks, &lexer::TokType::LParen)?;
+    check_tok(pos, &toks, &lexer::TokType::LParen, ctx)?;
     let pos = pos + 1;
 
     let mut cur_node = ParseNode::new(NodeType::AlignmentSpecifier);
     let mut pos = pos;
-    if let Ok((child_node, tmp_pos)) = p_type_name(toks, pos) {
+    if let Ok((child_node, tmp_pos)) = p_type_name(toks, pos, ctx) {
         cur_node.child.push(child_node);
         pos = tmp_pos;
-    } else if let Ok((child_node, tmp_pos)) = p_constant_expression(toks, pos) {
+    } else if let Ok((child_node, tmp_pos)) = p_constant_expression(toks, pos, ctx) {
         cur_node.child.push(child_node);
         pos = tmp_pos;
     } else {
         return Err(format!("Error parse alignment_specifier"));
     }
 
-    check_tok(pos, &toks, &lexer::TokType::RParen)?;
+    check_tok(pos, &toks, &lexer::TokType::RParen, ctx)?;
     let pos = pos + 1;
     cur_node.type_exp = TypeExpression::new_val(BaseType::NoneExpression);
     return Ok((cur_node, pos));
@@ -2281,17 +2831,21 @@ fn p_alignment_specifier(
 // 	: pointer direct_declarator
 // 	| direct_declarator
 // 	;
-fn p_declarator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_declarator(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node = ParseNode::new(NodeType::Declarator);
-    if let Ok((child_node, pos)) = p_direct_declarator(toks, pos) {
+    if let Ok((child_node, pos)) = p_direct_declarator(toks, pos, ctx) {
         cur_node.type_exp = child_node.type_exp.clone();
         cur_node.child.push(child_node);
         return Ok((cur_node, pos));
-    } else if let Ok((child_node, pos)) = p_pointer(toks, pos) {
+    } else if let Ok((child_node, pos)) = p_pointer(toks, pos, ctx) {
         cur_node.type_exp.child.push(child_node.type_exp.clone());
         cur_node.child.push(child_node);
-        let (child_node, pos) = p_direct_declarator(toks, pos)?;
+        let (child_node, pos) = p_direct_declarator(toks, pos, ctx)?;
         cur_node.type_exp.child.push(child_node.type_exp.clone());
         cur_node.child.push(child_node);
         return Ok((cur_node, pos));
@@ -2321,20 +2875,24 @@ fn p_declarator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize
 /// I combine all the postfix together in one ParseNode
 /// so if this root node has two child, then it has postfix,
 /// otherwise just IDENTIFIER or '(' declarator ')'
-fn p_direct_declarator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_direct_declarator(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node = ParseNode::new(NodeType::DirectDeclarator);
     let mut pos = pos;
 
     let pre_type;
 
-    if let Ok((child_node, tmp_pos)) = p_identifier(toks, pos) {
+    if let Ok((child_node, tmp_pos)) = p_identifier(toks, pos, ctx) {
         pre_type = child_node.type_exp.clone();
         cur_node.child.push(child_node);
         pos = tmp_pos;
-    } else if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::LParen) {
+    } else if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::LParen, ctx) {
         let tmp_pos = pos + 1;
-        let (child_node, tmp_pos) = p_declarator(toks, tmp_pos)?;
+        let (child_node, tmp_pos) = p_declarator(toks, tmp_pos, ctx)?;
         pre_type = child_node.type_exp.clone();
         cur_node.child.push(child_node);
         pos = tmp_pos;
@@ -2342,7 +2900,7 @@ fn p_direct_declarator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode
         return Err(format!("Error parse direct_declarator"));
     }
 
-    if let Ok((child_node, pos)) = p_direct_declarator_post_list(toks, pos) {
+    if let Ok((child_node, pos)) = p_direct_declarator_post_list(toks, pos, ctx) {
         cur_node.type_exp.child.push(pre_type);
         cur_node.type_exp.child.push(child_node.type_exp.clone());
         cur_node.child.push(child_node);
@@ -2356,19 +2914,20 @@ fn p_direct_declarator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode
 // direct_declarator_post_list
 // : direct_declarator_post { direct_declarator_post }
 fn p_direct_declarator_post_list(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node: ParseNode = ParseNode::new(NodeType::DirectDeclaratorPostList);
-    let (child_node, pos) = p_direct_declarator_post(toks, pos)?;
+    let (child_node, pos) = p_direct_declarator_post(toks, pos, ctx)?;
     let pre_type = child_node.type_exp.clone();
     let mut inc = 0;
 
     cur_node.type_exp.child.push(child_node.type_exp.clone());
     cur_node.child.push(child_node);
     let mut pos: usize = pos;
-    while let Ok((child_node, tmp_pos)) = p_direct_declarator_post(toks, pos) {
+    while let Ok((child_node, tmp_pos)) = p_direct_declarator_post(toks, pos, ctx) {
         inc += 1;
         cur_node.type_exp.child.push(child_node.type_exp.clone());
         cur_node.child.push(child_node);
@@ -2396,49 +2955,50 @@ fn p_direct_declarator_post_list(
 // 	| '[' type_qualifier_list assignment_expression ']'
 // 	| '[' type_qualifier_list ']'
 fn p_direct_declarator_post(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
-    match &toks[pos] {
+    match &toks[pos].kind {
         lexer::TokType::LParen => {
-            let mut cur_node = ParseNode::new(NodeType::DirectDeclaratorPost(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::DirectDeclaratorPost(toks[pos].kind.clone()));
             let pos = pos + 1;
-            if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RParen) {
+            if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RParen, ctx) {
                 let pos = pos + 1;
                 return Ok((cur_node, pos));
-            } else if let Ok((child_node, pos)) = p_parameter_type_list(toks, pos) {
+            } else if let Ok((child_node, pos)) = p_parameter_type_list(toks, pos, ctx) {
                 cur_node.type_exp = child_node.type_exp.clone();
                 cur_node.child.push(child_node);
-                check_tok(pos, &toks, &lexer::TokType::RParen)?;
+                check_tok(pos, &toks, &lexer::TokType::RParen, ctx)?;
                 let pos = pos + 1;
                 return Ok((cur_node, pos));
             } else {
-                let (child_node, pos) = p_identifier_list(toks, pos)?;
+                let (child_node, pos) = p_identifier_list(toks, pos, ctx)?;
                 cur_node.type_exp = child_node.type_exp.clone();
                 cur_node.child.push(child_node);
-                check_tok(pos, &toks, &lexer::TokType::RParen)?;
+                check_tok(pos, &toks, &lexer::TokType::RParen, ctx)?;
                 let pos = pos + 1;
                 return Ok((cur_node, pos));
             }
         }
         lexer::TokType::LBracket => {
-            let mut cur_node = ParseNode::new(NodeType::DirectDeclaratorPost(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::DirectDeclaratorPost(toks[pos].kind.clone()));
             let pos = pos + 1;
-            if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RBracket) {
+            if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RBracket, ctx) {
                 let pos = pos + 1;
                 return Ok((cur_node, pos));
             } else {
-                let (child_node, pos) = p_assignment_expression(toks, pos)?;
+                let (child_node, pos) = p_assignment_expression(toks, pos, ctx)?;
                 cur_node.type_exp = child_node.type_exp.clone();
                 cur_node.child.push(child_node);
-                check_tok(pos, &toks, &lexer::TokType::RBracket)?;
+                check_tok(pos, &toks, &lexer::TokType::RBracket, ctx)?;
                 let pos = pos + 1;
                 return Ok((cur_node, pos));
             }
         }
         _ => {
-            return Err(error_handler("[ or (", &toks[pos], pos));
+            return Err(error_handler("`[` or `(`", toks, pos, ctx));
         }
     }
 }
@@ -2448,23 +3008,27 @@ fn p_direct_declarator_post(
 // 	| '*' pointer
 // 	| '*'
 // 	;
-fn p_pointer(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_pointer(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node = ParseNode::new(NodeType::Pointer);
-    check_tok(pos, &toks, &lexer::TokType::Multi)?;
+    check_tok(pos, &toks, &lexer::TokType::Multi, ctx)?;
     cur_node.type_exp = TypeExpression::new_val(BaseType::Pointer);
     let pos = pos + 1;
-    if let Ok((child_node, pos)) = p_type_qualifier_list(toks, pos) {
+    if let Ok((child_node, pos)) = p_type_qualifier_list(toks, pos, ctx) {
         cur_node.type_exp.child.push(child_node.type_exp.clone());
         cur_node.child.push(child_node);
-        if let Ok((child_node, pos)) = p_pointer(toks, pos) {
+        if let Ok((child_node, pos)) = p_pointer(toks, pos, ctx) {
             cur_node.type_exp.child.push(child_node.type_exp.clone());
             cur_node.child.push(child_node);
             return Ok((cur_node, pos));
         } else {
             return Ok((cur_node, pos));
         }
-    } else if let Ok((child_node, pos)) = p_pointer(toks, pos) {
+    } else if let Ok((child_node, pos)) = p_pointer(toks, pos, ctx) {
         cur_node.type_exp.child.push(child_node.type_exp.clone());
         cur_node.child.push(child_node);
         return Ok((cur_node, pos));
@@ -2479,16 +3043,17 @@ fn p_pointer(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize),
 // 	;
 //  -> type_qualifier { type_qualifier }
 fn p_type_qualifier_list(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node: ParseNode = ParseNode::new(NodeType::TypeQualifierList);
-    let (child_node, pos) = p_type_qualifier(toks, pos)?;
+    let (child_node, pos) = p_type_qualifier(toks, pos, ctx)?;
     cur_node.type_exp.child.push(child_node.type_exp.clone());
     cur_node.child.push(child_node);
     let mut pos: usize = pos;
-    while let Ok((child_node, tmp_pos)) = p_type_qualifier(toks, pos) {
+    while let Ok((child_node, tmp_pos)) = p_type_qualifier(toks, pos, ctx) {
         cur_node.type_exp.child.push(child_node.type_exp.clone());
         cur_node.child.push(child_node);
         pos = tmp_pos;
@@ -2500,17 +3065,18 @@ fn p_type_qualifier_list(
 // 	| parameter_list
 // 	;
 fn p_parameter_type_list(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node = ParseNode::new(NodeType::ParameterTypeList(false)); // no extra variable
-    let (child_node, pos) = p_parameter_list(toks, pos)?;
+    let (child_node, pos) = p_parameter_list(toks, pos, ctx)?;
     cur_node.type_exp = child_node.type_exp.clone();
     cur_node.child.push(child_node);
-    if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Comma) {
+    if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Comma, ctx) {
         let pos = pos + 1;
-        check_tok(pos, &toks, &lexer::TokType::ELLIPSIS)?;
+        check_tok(pos, &toks, &lexer::TokType::ELLIPSIS, ctx)?;
         cur_node.entry = NodeType::ParameterTypeList(true);
         // XXX: VaList in node.type_exp.val
         cur_node.type_exp.val.push(BaseType::VaList);
@@ -2525,22 +3091,26 @@ fn p_parameter_type_list(
 // 	| parameter_list ',' parameter_declaration
 // 	;
 //  -> parameter_declaration { ',' parameter_declaration }
-fn p_parameter_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_parameter_list(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
     let mut cur_node: ParseNode = ParseNode::new(NodeType::ParameterList);
-    let (child_node, pos) = p_parameter_declaration(toks, pos)?; // if error, then out
+    let (child_node, pos) = p_parameter_declaration(toks, pos, ctx)?; // if error, then out
     cur_node.type_exp.child.push(child_node.type_exp.clone());
     cur_node.child.push(child_node);
     let mut pos: usize = pos;
     loop {
-        if let Err(_) = check_tok(pos, &toks, &lexer::TokType::Comma) {
+        if let Err(_) = check_tok(pos, &toks, &lexer::TokType::Comma, ctx) {
             break;
         } else {
             pos = pos + 1;
         }
 
-        match p_parameter_declaration(toks, pos) {
+        match p_parameter_declaration(toks, pos, ctx) {
             Ok((child_node, tmp_pos)) => {
                 cur_node.type_exp.child.push(child_node.type_exp.clone());
                 cur_node.child.push(child_node);
@@ -2561,21 +3131,22 @@ fn p_parameter_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
 // 	| declaration_specifiers
 // 	;
 fn p_parameter_declaration(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node = ParseNode::new(NodeType::ParameterDeclaration);
-    let (c, pos) = p_declaration_specifiers(toks, pos)?;
+    let (c, pos) = p_declaration_specifiers(toks, pos, ctx)?;
     let declaration_specifiers_type = c.type_exp.clone();
 
     cur_node.child.push(c);
-    if let Ok((c, pos)) = p_declarator(toks, pos) {
+    if let Ok((c, pos)) = p_declarator(toks, pos, ctx) {
         cur_node.type_exp.child.push(declaration_specifiers_type);
         cur_node.type_exp.child.push(c.type_exp.clone());
         cur_node.child.push(c);
         return Ok((cur_node, pos));
-    } else if let Ok((c, pos)) = p_abstract_declarator(toks, pos) {
+    } else if let Ok((c, pos)) = p_abstract_declarator(toks, pos, ctx) {
         cur_node.type_exp.child.push(declaration_specifiers_type);
         cur_node.type_exp.child.push(c.type_exp.clone());
         cur_node.child.push(c);
@@ -2591,11 +3162,15 @@ fn p_parameter_declaration(
 // 	| identifier_list ',' IDENTIFIER
 // 	;
 //  -> IDENTIFIER { ',' IDENTIFIER }
-fn p_identifier_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_identifier_list(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
     let mut cur_node: ParseNode = ParseNode::new(NodeType::IdentifierList);
-    let (child_node, pos) = p_identifier(toks, pos)?; // if error, then out
+    let (child_node, pos) = p_identifier(toks, pos, ctx)?; // if error, then out
     let pre_type = child_node.type_exp.clone();
     let mut inc = 0;
 
@@ -2603,13 +3178,13 @@ fn p_identifier_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
     cur_node.child.push(child_node);
     let mut pos: usize = pos;
     loop {
-        if let Err(_) = check_tok(pos, &toks, &lexer::TokType::Comma) {
+        if let Err(_) = check_tok(pos, &toks, &lexer::TokType::Comma, ctx) {
             break;
         } else {
             pos = pos + 1;
         }
 
-        match p_identifier(toks, pos) {
+        match p_identifier(toks, pos, ctx) {
             Ok((child_node, tmp_pos)) => {
                 inc += 1;
                 cur_node.type_exp.child.push(child_node.type_exp.clone());
@@ -2632,14 +3207,18 @@ fn p_identifier_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
 // 	: specifier_qualifier_list abstract_declarator
 // 	| specifier_qualifier_list
 // 	;
-fn p_type_name(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_type_name(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node = ParseNode::new(NodeType::TypeName);
-    let (child_node, pos) = p_specifier_qualifier_list(toks, pos)?;
+    let (child_node, pos) = p_specifier_qualifier_list(toks, pos, ctx)?;
     let specifier_qualifier_list_type = child_node.type_exp.clone();
     cur_node.child.push(child_node);
 
-    if let Ok((child_node, pos)) = p_abstract_declarator(toks, pos) {
+    if let Ok((child_node, pos)) = p_abstract_declarator(toks, pos, ctx) {
         cur_node.type_exp.child.push(specifier_qualifier_list_type);
         cur_node.type_exp.child.push(child_node.type_exp.clone());
         cur_node.child.push(child_node);
@@ -2656,24 +3235,25 @@ fn p_type_name(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize)
 // 	| direct_abstract_declarator
 // 	;
 fn p_abstract_declarator(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
     let mut cur_node = ParseNode::new(NodeType::AbstractDeclarator);
 
-    if let Ok((child_node, pos)) = p_pointer(toks, pos) {
+    if let Ok((child_node, pos)) = p_pointer(toks, pos, ctx) {
         cur_node.child.push(child_node);
         cur_node.type_exp = TypeExpression::new_val(BaseType::Pointer);
-        if let Ok((child_node, pos)) = p_direct_abstract_declarator(toks, pos) {
+        if let Ok((child_node, pos)) = p_direct_abstract_declarator(toks, pos, ctx) {
             cur_node.type_exp.child.push(child_node.type_exp.clone());
             cur_node.child.push(child_node);
             return Ok((cur_node, pos));
         } else {
             return Ok((cur_node, pos));
         }
-    } else if let Ok((child_node, pos)) = p_direct_abstract_declarator(toks, pos) {
+    } else if let Ok((child_node, pos)) = p_direct_abstract_declarator(toks, pos, ctx) {
         cur_node.type_exp = child_node.type_exp.clone();
         cur_node.child.push(child_node);
         return Ok((cur_node, pos));
@@ -2709,18 +3289,19 @@ fn p_abstract_declarator(
 // direct_abstract_declarator_block { direct_abstract_declarator_block }
 
 fn p_direct_abstract_declarator(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node: ParseNode = ParseNode::new(NodeType::DirectAbstractDeclarator);
-    let (child_node, pos) = p_direct_abstract_declarator_block(toks, pos)?;
+    let (child_node, pos) = p_direct_abstract_declarator_block(toks, pos, ctx)?;
     let pre_type = child_node.type_exp.clone();
     let mut inc = 0;
     cur_node.type_exp.child.push(child_node.type_exp.clone());
     cur_node.child.push(child_node);
     let mut pos: usize = pos;
-    while let Ok((child_node, tmp_pos)) = p_direct_abstract_declarator_block(toks, pos) {
+    while let Ok((child_node, tmp_pos)) = p_direct_abstract_declarator_block(toks, pos, ctx) {
         inc += 1;
         cur_node.type_exp.child.push(child_node.type_exp.clone());
         cur_node.child.push(child_node);
@@ -2747,30 +3328,31 @@ fn p_direct_abstract_declarator(
 // 	| '[' type_qualifier_list assignment_expression ']'
 // 	| '[' type_qualifier_list ']'
 fn p_direct_abstract_declarator_block(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
-    match &toks[pos] {
+    match &toks[pos].kind {
         lexer::TokType::LParen => {
             let mut cur_node =
-                ParseNode::new(NodeType::DirectAbstractDeclaratorBlock(toks[pos].clone()));
+                ParseNode::new(NodeType::DirectAbstractDeclaratorBlock(toks[pos].kind.clone()));
             let pos = pos + 1;
-            if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RParen) {
+            if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RParen, ctx) {
                 let pos = pos + 1;
                 return Ok((cur_node, pos));
             } else {
-                if let Ok((child_node, pos)) = p_abstract_declarator(toks, pos) {
+                if let Ok((child_node, pos)) = p_abstract_declarator(toks, pos, ctx) {
                     cur_node.type_exp = child_node.type_exp.clone();
                     cur_node.child.push(child_node);
-                    check_tok(pos, &toks, &lexer::TokType::RParen)?;
+                    check_tok(pos, &toks, &lexer::TokType::RParen, ctx)?;
                     let pos = pos + 1;
                     return Ok((cur_node, pos));
                 } else {
-                    let (child_node, pos) = p_parameter_type_list(toks, pos)?;
+                    let (child_node, pos) = p_parameter_type_list(toks, pos, ctx)?;
                     cur_node.type_exp = child_node.type_exp.clone();
                     cur_node.child.push(child_node);
-                    check_tok(pos, &toks, &lexer::TokType::RParen)?;
+                    check_tok(pos, &toks, &lexer::TokType::RParen, ctx)?;
                     let pos = pos + 1;
                     return Ok((cur_node, pos));
                 }
@@ -2778,22 +3360,22 @@ fn p_direct_abstract_declarator_block(
         }
         lexer::TokType::LBracket => {
             let mut cur_node =
-                ParseNode::new(NodeType::DirectAbstractDeclaratorBlock(toks[pos].clone()));
+                ParseNode::new(NodeType::DirectAbstractDeclaratorBlock(toks[pos].kind.clone()));
             let pos = pos + 1;
-            if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RBracket) {
+            if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RBracket, ctx) {
                 let pos = pos + 1;
                 return Ok((cur_node, pos));
             } else {
-                let (child_node, pos) = p_assignment_expression(toks, pos)?;
+                let (child_node, pos) = p_assignment_expression(toks, pos, ctx)?;
                 cur_node.type_exp = child_node.type_exp.clone();
                 cur_node.child.push(child_node);
-                check_tok(pos, &toks, &lexer::TokType::RBracket)?;
+                check_tok(pos, &toks, &lexer::TokType::RBracket, ctx)?;
                 let pos = pos + 1;
                 return Ok((cur_node, pos));
             }
         }
         _ => {
-            return Err(error_handler("( or [", &toks[pos], pos));
+            return Err(error_handler("`(` or `[`", toks, pos, ctx));
         }
     }
 }
@@ -2803,28 +3385,32 @@ fn p_direct_abstract_declarator_block(
 // 	| '{' initializer_list ',' '}'
 // 	| assignment_expression
 // 	;
-fn p_initializer(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_initializer(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node = ParseNode::new(NodeType::Initializer);
 
-    if let Ok((child_node, pos)) = p_assignment_expression(toks, pos) {
+    if let Ok((child_node, pos)) = p_assignment_expression(toks, pos, ctx) {
         cur_node.type_exp = child_node.type_exp.clone();
         cur_node.child.push(child_node);
         return Ok((cur_node, pos));
     } else {
-        check_tok(pos, &toks, &lexer::TokType::LBrace)?;
+        check_tok(pos, &toks, &lexer::TokType::LBrace, ctx)?;
         let pos = pos + 1;
 
-        let (child_node, pos) = p_initializer_list(toks, pos)?;
+        let (child_node, pos) = p_initializer_list(toks, pos, ctx)?;
         cur_node.type_exp = child_node.type_exp.clone();
         cur_node.child.push(child_node);
 
-        if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Comma) {
+        if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Comma, ctx) {
             let pos = pos + 1;
-            check_tok(pos, &toks, &lexer::TokType::RBrace)?;
+            check_tok(pos, &toks, &lexer::TokType::RBrace, ctx)?;
             return Ok((cur_node, pos));
         }
-        check_tok(pos, &toks, &lexer::TokType::RBrace)?;
+        check_tok(pos, &toks, &lexer::TokType::RBrace, ctx)?;
         let pos = pos + 1;
         return Ok((cur_node, pos));
     }
@@ -2838,7 +3424,11 @@ fn p_initializer(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usiz
 // -> pre {',' pre}
 // XXX: designation initializer should get type(initializer) as its type
 //      but need to add judge function to judge whether it's ok to assign
-fn p_initializer_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_initializer_list(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
     let mut pre_type;
@@ -2846,14 +3436,14 @@ fn p_initializer_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
     let mut inc = 0;
     let mut cur_node: ParseNode = ParseNode::new(NodeType::InitializerList);
     let mut pos = pos;
-    if let Ok((child_node, tmp_pos)) = p_initializer(toks, pos) {
+    if let Ok((child_node, tmp_pos)) = p_initializer(toks, pos, ctx) {
         pos = tmp_pos;
         pre_type = child_node.type_exp.clone();
         cur_node.child.push(child_node);
-    } else if let Ok((child_node, tmp_pos)) = p_designation(toks, pos) {
+    } else if let Ok((child_node, tmp_pos)) = p_designation(toks, pos, ctx) {
         pos = tmp_pos;
         cur_node.child.push(child_node);
-        let (child_node, tmp_pos) = p_initializer(toks, pos)?;
+        let (child_node, tmp_pos) = p_initializer(toks, pos, ctx)?;
         pre_type = child_node.type_exp.clone();
         pos = tmp_pos;
         cur_node.child.push(child_node);
@@ -2863,20 +3453,20 @@ fn p_initializer_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
     cur_node.type_exp.child.push(pre_type.clone());
 
     loop {
-        if let Err(_) = check_tok(pos, &toks, &lexer::TokType::Comma) {
+        if let Err(_) = check_tok(pos, &toks, &lexer::TokType::Comma, ctx) {
             break;
         } else {
             pos = pos + 1;
         }
         inc = inc + 1;
-        if let Ok((child_node, tmp_pos)) = p_initializer(toks, pos) {
+        if let Ok((child_node, tmp_pos)) = p_initializer(toks, pos, ctx) {
             pre_type = child_node.type_exp.clone();
             cur_node.child.push(child_node);
             pos = tmp_pos;
-        } else if let Ok((child_node, tmp_pos)) = p_designation(toks, pos) {
+        } else if let Ok((child_node, tmp_pos)) = p_designation(toks, pos, ctx) {
             pos = tmp_pos;
             cur_node.child.push(child_node);
-            let (child_node, tmp_pos) = p_initializer(toks, pos)?;
+            let (child_node, tmp_pos) = p_initializer(toks, pos, ctx)?;
             pre_type = child_node.type_exp.clone();
             cur_node.child.push(child_node);
             pos = tmp_pos;
@@ -2897,13 +3487,17 @@ fn p_initializer_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
 // designation
 // 	: designator_list '='
 // 	;
-fn p_designation(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_designation(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node = ParseNode::new(NodeType::Designation);
-    let (child_node, pos) = p_designator_list(toks, pos)?;
+    let (child_node, pos) = p_designator_list(toks, pos, ctx)?;
     cur_node.type_exp = child_node.type_exp.clone();
     cur_node.child.push(child_node);
-    check_tok(pos, &toks, &lexer::TokType::Assign)?;
+    check_tok(pos, &toks, &lexer::TokType::Assign, ctx)?;
     let pos = pos + 1;
     return Ok((cur_node, pos));
 }
@@ -2912,17 +3506,21 @@ fn p_designation(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usiz
 // 	| designator_list designator
 // 	;
 //  -> designator { designator }
-fn p_designator_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_designator_list(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node: ParseNode = ParseNode::new(NodeType::DesignatorList);
-    let (child_node, pos) = p_designator(toks, pos)?;
+    let (child_node, pos) = p_designator(toks, pos, ctx)?;
     let pre_type = child_node.type_exp.clone();
     let mut inc = 0;
 
     cur_node.type_exp.child.push(child_node.type_exp.clone());
     cur_node.child.push(child_node);
     let mut pos: usize = pos;
-    while let Ok((child_node, tmp_pos)) = p_designator(toks, pos) {
+    while let Ok((child_node, tmp_pos)) = p_designator(toks, pos, ctx) {
         inc += 1;
         cur_node.type_exp.child.push(child_node.type_exp.clone());
         cur_node.child.push(child_node);
@@ -2940,21 +3538,25 @@ fn p_designator_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
 // 	: '[' constant_expression ']'
 // 	| '.' IDENTIFIER
 // 	;
-fn p_designator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_designator(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node = ParseNode::new(NodeType::Designator);
-    if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::LBracket) {
+    if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::LBracket, ctx) {
         let pos = pos + 1;
-        let (child_node, pos) = p_constant_expression(toks, pos)?;
+        let (child_node, pos) = p_constant_expression(toks, pos, ctx)?;
         cur_node.type_exp = child_node.type_exp.clone();
         cur_node.child.push(child_node);
-        check_tok(pos, &toks, &lexer::TokType::RBracket)?;
+        check_tok(pos, &toks, &lexer::TokType::RBracket, ctx)?;
         let pos = pos + 1;
         return Ok((cur_node, pos));
     } else {
-        check_tok(pos, &toks, &lexer::TokType::Dot)?;
+        check_tok(pos, &toks, &lexer::TokType::Dot, ctx)?;
         let pos = pos + 1;
-        let (child_node, pos) = p_identifier(toks, pos)?;
+        let (child_node, pos) = p_identifier(toks, pos, ctx)?;
         cur_node.type_exp = child_node.type_exp.clone();
         cur_node.child.push(child_node);
         return Ok((cur_node, pos));
@@ -2965,28 +3567,29 @@ fn p_designator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize
 // 	: StaticAssert '(' constant_expression ',' StringLiteral ')' ';'
 // 	;
 fn p_static_assert_declaration(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
-    check_tok(pos, &toks, &lexer::TokType::StaticAssert)?;
+    check_tok(pos, &toks, &lexer::TokType::StaticAssert, ctx)?;
     let pos = pos + 1;
 
-    check_tok(pos, &toks, &lexer::TokType::LParen)?;
+    check_tok(pos, &toks, &lexer::TokType::LParen, ctx)?;
     let pos = pos + 1;
     let mut cur_node = ParseNode::new(NodeType::StaticAssertDeclaration);
-    let (child_node, pos) = p_constant_expression(toks, pos)?;
+    let (child_node, pos) = p_constant_expression(toks, pos, ctx)?;
     cur_node.child.push(child_node);
-    check_tok(pos, &toks, &lexer::TokType::Comma)?;
+    check_tok(pos, &toks, &lexer::TokType::Comma, ctx)?;
     let pos = pos + 1;
 
-    let (child_node, pos) = p_string(toks, pos)?;
+    let (child_node, pos) = p_string(toks, pos, ctx)?;
     cur_node.child.push(child_node);
 
-    check_tok(pos, &toks, &lexer::TokType::RParen)?;
+    check_tok(pos, &toks, &lexer::TokType::RParen, ctx)?;
     let pos = pos + 1;
-    check_tok(pos, &toks, &lexer::TokType::Semicolon)?;
+    check_tok(pos, &toks, &lexer::TokType::Semicolon, ctx)?;
     let pos = pos + 1;
     cur_node.type_exp = TypeExpression::new_val(BaseType::NoneExpression);
     return Ok((cur_node, pos));
@@ -3000,30 +3603,34 @@ fn p_static_assert_declaration(
 // 	| iteration_statement
 // 	| jump_statement
 // 	;
-fn p_statement(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_statement(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node = ParseNode::new(NodeType::Statement);
-    if let Ok((child_node, pos)) = p_labeled_statement(toks, pos) {
+    if let Ok((child_node, pos)) = p_labeled_statement(toks, pos, ctx) {
         cur_node.type_exp = child_node.type_exp.clone();
         cur_node.child.push(child_node);
         return Ok((cur_node, pos));
-    } else if let Ok((child_node, pos)) = p_compound_statement(toks, pos) {
+    } else if let Ok((child_node, pos)) = p_compound_statement(toks, pos, ctx) {
         cur_node.type_exp = child_node.type_exp.clone();
         cur_node.child.push(child_node);
         return Ok((cur_node, pos));
-    } else if let Ok((child_node, pos)) = p_expression_statement(toks, pos) {
+    } else if let Ok((child_node, pos)) = p_expression_statement(toks, pos, ctx) {
         cur_node.type_exp = child_node.type_exp.clone();
         cur_node.child.push(child_node);
         return Ok((cur_node, pos));
-    } else if let Ok((child_node, pos)) = p_selection_statement(toks, pos) {
+    } else if let Ok((child_node, pos)) = p_selection_statement(toks, pos, ctx) {
         cur_node.type_exp = child_node.type_exp.clone();
         cur_node.child.push(child_node);
         return Ok((cur_node, pos));
-    } else if let Ok((child_node, pos)) = p_iteration_statement(toks, pos) {
+    } else if let Ok((child_node, pos)) = p_iteration_statement(toks, pos, ctx) {
         cur_node.type_exp = child_node.type_exp.clone();
         cur_node.child.push(child_node);
         return Ok((cur_node, pos));
-    } else if let Ok((child_node, pos)) = p_jump_statement(toks, pos) {
+    } else if let Ok((child_node, pos)) = p_jump_statement(toks, pos, ctx) {
         cur_node.type_exp = child_node.type_exp.clone();
         cur_node.child.push(child_node);
         return Ok((cur_node, pos));
@@ -3036,16 +3643,20 @@ fn p_statement(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize)
 // 	| CASE constant_expression ':' statement
 // 	| DEFAULT ':' statement
 // 	;
-fn p_labeled_statement(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_labeled_statement(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node = ParseNode::new(NodeType::LabeledStatement("".to_string()));
-    match &toks[pos] {
+    match &toks[pos].kind {
         lexer::TokType::IDENTIFIER(s) => {
-            cur_node.entry = NodeType::LabeledStatement(s.to_string());
+            cur_node.entry = NodeType::LabeledStatement(ctx.interner.name(*s).to_string());
             let pos = pos + 1;
-            check_tok(pos, &toks, &lexer::TokType::Colon)?;
+            check_tok(pos, &toks, &lexer::TokType::Colon, ctx)?;
             let pos = pos + 1;
-            let (child_node, pos) = p_statement(toks, pos)?;
+            let (child_node, pos) = p_statement(toks, pos, ctx)?;
             cur_node.type_exp = TypeExpression::new_val(BaseType::NoneExpression);
             cur_node.child.push(child_node);
             return Ok((cur_node, pos));
@@ -3053,11 +3664,11 @@ fn p_labeled_statement(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode
         lexer::TokType::CASE => {
             cur_node.entry = NodeType::LabeledStatement("case".to_string());
             let pos = pos + 1;
-            let (child_node, pos) = p_constant_expression(toks, pos)?;
+            let (child_node, pos) = p_constant_expression(toks, pos, ctx)?;
             cur_node.child.push(child_node);
-            check_tok(pos, &toks, &lexer::TokType::Colon)?;
+            check_tok(pos, &toks, &lexer::TokType::Colon, ctx)?;
             let pos = pos + 1;
-            let (child_node, pos) = p_statement(toks, pos)?;
+            let (child_node, pos) = p_statement(toks, pos, ctx)?;
             cur_node.child.push(child_node);
             cur_node.type_exp = TypeExpression::new_val(BaseType::NoneExpression);
             return Ok((cur_node, pos));
@@ -3065,15 +3676,15 @@ fn p_labeled_statement(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode
         lexer::TokType::DEFAULT => {
             cur_node.entry = NodeType::LabeledStatement("default".to_string());
             let pos = pos + 1;
-            check_tok(pos, &toks, &lexer::TokType::Colon)?;
+            check_tok(pos, &toks, &lexer::TokType::Colon, ctx)?;
             let pos = pos + 1;
-            let (child_node, pos) = p_statement(toks, pos)?;
+            let (child_node, pos) = p_statement(toks, pos, ctx)?;
             cur_node.child.push(child_node);
             cur_node.type_exp = TypeExpression::new_val(BaseType::NoneExpression);
             return Ok((cur_node, pos));
         }
         _ => {
-            return Err(error_handler("label", &toks[pos], pos));
+            return Err(error_handler("a label", toks, pos, ctx));
         }
     }
 }
@@ -3082,47 +3693,68 @@ fn p_labeled_statement(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode
 // 	: '{' '}'
 // 	| '{'  block_item_list '}'
 // 	;
-fn p_compound_statement(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_compound_statement(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node = ParseNode::new(NodeType::CompoundStatement);
-    check_tok(pos, &toks, &lexer::TokType::LBrace)?;
+    check_tok(pos, &toks, &lexer::TokType::LBrace, ctx)?;
     let pos = pos + 1;
-    if let Ok((child_node, pos)) = p_block_item_list(toks, pos) {
-        cur_node.type_exp = child_node.type_exp.clone();
-        cur_node.child.push(child_node);
-        check_tok(pos, &toks, &lexer::TokType::RBrace)?;
-        let pos = pos + 1;
-        return Ok((cur_node, pos));
-    } else {
-        check_tok(pos, &toks, &lexer::TokType::RBrace)?;
-        let pos = pos + 1;
-        cur_node.type_exp = TypeExpression::new_val(BaseType::NoneExpression);
-        return Ok((cur_node, pos));
-    }
+    // the block is a scope
+    return ctx.in_scope(|ctx| {
+        if let Ok((child_node, pos)) = p_block_item_list(toks, pos, ctx) {
+            cur_node.type_exp = child_node.type_exp.clone();
+            cur_node.child.push(child_node);
+            check_tok(pos, &toks, &lexer::TokType::RBrace, ctx)?;
+            let pos = pos + 1;
+            return Ok((cur_node, pos));
+        } else {
+            check_tok(pos, &toks, &lexer::TokType::RBrace, ctx)?;
+            let pos = pos + 1;
+            cur_node.type_exp = TypeExpression::new_val(BaseType::NoneExpression);
+            return Ok((cur_node, pos));
+        }
+    });
 }
 // block_item_list
 // 	: block_item
 // 	| block_item_list block_item
 // 	;
 //  -> block_item { block_item }
-fn p_block_item_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+// the parser goes on after a syntax error, from the end of the statement
+fn p_block_item_list(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node: ParseNode = ParseNode::new(NodeType::BlockItemList);
-    let (child_node, pos) = p_block_item(toks, pos)?;
-    let pre_type = child_node.type_exp.clone();
-    let mut inc = 0;
-    cur_node.type_exp.child.push(child_node.type_exp.clone());
-    cur_node.child.push(child_node);
+    let start = pos;
     let mut pos: usize = pos;
-    while let Ok((child_node, tmp_pos)) = p_block_item(toks, pos) {
-        inc += 1;
-        cur_node.type_exp.child.push(child_node.type_exp.clone());
-        cur_node.child.push(child_node);
-        pos = tmp_pos;
+    loop {
+        match toks.get(pos) {
+            Some(tok) if tok.kind != lexer::TokType::RBrace => {}
+            _ => break,
+        }
+        ctx.clear_failure();
+        match p_block_item(toks, pos, ctx) {
+            Ok((child_node, tmp_pos)) => {
+                cur_node.type_exp.child.push(child_node.type_exp.clone());
+                cur_node.child.push(child_node);
+                pos = tmp_pos;
+            }
+            Err(_) => pos = ctx.recover(toks, pos, "a declaration or a statement"),
+        }
     }
 
-    if inc == 0 {
-        cur_node.type_exp = pre_type;
+    // the items which had syntax errors are not in the list
+    if pos == start {
+        return Err(error_handler("a declaration or a statement", toks, pos, ctx));
+    }
+    if cur_node.child.len() == 1 {
+        cur_node.type_exp = cur_node.type_exp.child.remove(0);
     }
     return Ok((cur_node, pos));
 }
@@ -3130,15 +3762,24 @@ fn p_block_item_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
 // block_item
 // 	: declaration
 // 	| statement
+// 	| pragma
 // 	;
-fn p_block_item(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_block_item(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node = ParseNode::new(NodeType::BlockItem);
-    if let Ok((child_node, pos)) = p_declaration(toks, pos) {
+    if let Ok((child_node, pos)) = p_pragma(toks, pos, ctx) {
+        cur_node.type_exp = child_node.type_exp.clone();
+        cur_node.child.push(child_node);
+        return Ok((cur_node, pos));
+    } else if let Ok((child_node, pos)) = p_declaration(toks, pos, ctx) {
         cur_node.type_exp = child_node.type_exp.clone();
         cur_node.child.push(child_node);
         return Ok((cur_node, pos));
-    } else if let Ok((child_node, pos)) = p_statement(toks, pos) {
+    } else if let Ok((child_node, pos)) = p_statement(toks, pos, ctx) {
         cur_node.type_exp = child_node.type_exp.clone();
         cur_node.child.push(child_node);
         return Ok((cur_node, pos));
@@ -3152,20 +3793,21 @@ fn p_block_item(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize
 // 	| expression ';'
 // 	;
 fn p_expression_statement(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node = ParseNode::new(NodeType::ExpressionStatement);
-    if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Semicolon) {
+    if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Semicolon, ctx) {
         let pos = pos + 1;
         cur_node.type_exp = TypeExpression::new_val(BaseType::NoneExpression);
         return Ok((cur_node, pos));
     } else {
-        let (child_node, pos) = p_expression(toks, pos)?;
+        let (child_node, pos) = p_expression(toks, pos, ctx)?;
         cur_node.type_exp = child_node.type_exp.clone();
         cur_node.child.push(child_node);
-        check_tok(pos, &toks, &lexer::TokType::Semicolon)?;
+        check_tok(pos, &toks, &lexer::TokType::Semicolon, ctx)?;
         let pos = pos + 1;
         return Ok((cur_node, pos));
     }
@@ -3177,30 +3819,31 @@ fn p_expression_statement(
 // 	| SWITCH '(' expression ')' statement
 // 	;
 fn p_selection_statement(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
-    match &toks[pos] {
+    match &toks[pos].kind {
         lexer::TokType::IF => {
-            let mut cur_node = ParseNode::new(NodeType::SelectionStatement(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::SelectionStatement(toks[pos].kind.clone()));
             let pos = pos + 1;
 
-            check_tok(pos, &toks, &lexer::TokType::LParen)?;
+            check_tok(pos, &toks, &lexer::TokType::LParen, ctx)?;
             let pos = pos + 1;
 
-            let (child_node, pos) = p_expression(toks, pos)?;
+            let (child_node, pos) = p_expression(toks, pos, ctx)?;
             cur_node.child.push(child_node);
 
-            check_tok(pos, &toks, &lexer::TokType::RParen)?;
+            check_tok(pos, &toks, &lexer::TokType::RParen, ctx)?;
             let pos = pos + 1;
 
-            let (child_node, pos) = p_statement(toks, pos)?;
+            let (child_node, pos) = p_statement(toks, pos, ctx)?;
             cur_node.child.push(child_node);
 
-            if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::ELSE) {
+            if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::ELSE, ctx) {
                 let pos = pos + 1;
-                let (child_node, pos) = p_statement(toks, pos)?;
+                let (child_node, pos) = p_statement(toks, pos, ctx)?;
                 cur_node.child.push(child_node);
                 cur_node.type_exp = TypeExpression::new_val(BaseType::NoneExpression);
                 return Ok((cur_node, pos));
@@ -3210,24 +3853,24 @@ fn p_selection_statement(
             }
         }
         lexer::TokType::SWITCH => {
-            let mut cur_node = ParseNode::new(NodeType::SelectionStatement(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::SelectionStatement(toks[pos].kind.clone()));
             let pos = pos + 1;
 
-            check_tok(pos, &toks, &lexer::TokType::LParen)?;
+            check_tok(pos, &toks, &lexer::TokType::LParen, ctx)?;
             let pos = pos + 1;
-            let (child_node, pos) = p_expression(toks, pos)?;
+            let (child_node, pos) = p_expression(toks, pos, ctx)?;
             cur_node.child.push(child_node);
 
-            check_tok(pos, &toks, &lexer::TokType::RParen)?;
+            check_tok(pos, &toks, &lexer::TokType::RParen, ctx)?;
             let pos = pos + 1;
-            let (child_node, pos) = p_statement(toks, pos)?;
+            let (child_node, pos) = p_statement(toks, pos, ctx)?;
             cur_node.child.push(child_node);
 
             cur_node.type_exp = TypeExpression::new_val(BaseType::NoneExpression);
             return Ok((cur_node, pos));
         }
         _ => {
-            return Err(error_handler("[if, switch]", &toks[pos], pos));
+            return Err(error_handler("`if` or `switch`", toks, pos, ctx));
         }
     }
 }
@@ -3241,50 +3884,51 @@ fn p_selection_statement(
 // 	| FOR '(' declaration expression_statement expression ')' statement
 // 	;
 fn p_iteration_statement(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
-    match &toks[pos] {
+    match &toks[pos].kind {
         lexer::TokType::WHILE => {
             // 	: WHILE '(' expression ')' statement
-            let mut cur_node = ParseNode::new(NodeType::IterationStatement(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::IterationStatement(toks[pos].kind.clone()));
             let pos = pos + 1;
 
-            check_tok(pos, &toks, &lexer::TokType::LParen)?;
+            check_tok(pos, &toks, &lexer::TokType::LParen, ctx)?;
             let pos = pos + 1;
 
-            let (child_node, pos) = p_expression(toks, pos)?;
+            let (child_node, pos) = p_expression(toks, pos, ctx)?;
             cur_node.child.push(child_node);
 
-            check_tok(pos, &toks, &lexer::TokType::RParen)?;
+            check_tok(pos, &toks, &lexer::TokType::RParen, ctx)?;
             let pos = pos + 1;
 
-            let (child_node, pos) = p_statement(toks, pos)?;
+            let (child_node, pos) = p_statement(toks, pos, ctx)?;
             cur_node.child.push(child_node);
 
             cur_node.type_exp = TypeExpression::new_val(BaseType::NoneExpression);
             return Ok((cur_node, pos));
         }
         lexer::TokType::DO => {
-            let mut cur_node = ParseNode::new(NodeType::IterationStatement(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::IterationStatement(toks[pos].kind.clone()));
             let pos = pos + 1;
-            let (child_node, pos) = p_statement(toks, pos)?;
+            let (child_node, pos) = p_statement(toks, pos, ctx)?;
             cur_node.child.push(child_node);
 
-            check_tok(pos, &toks, &lexer::TokType::WHILE)?;
+            check_tok(pos, &toks, &lexer::TokType::WHILE, ctx)?;
             let pos = pos + 1;
 
-            check_tok(pos, &toks, &lexer::TokType::LParen)?;
+            check_tok(pos, &toks, &lexer::TokType::LParen, ctx)?;
             let pos = pos + 1;
 
-            let (child_node, pos) = p_expression(toks, pos)?;
+            let (child_node, pos) = p_expression(toks, pos, ctx)?;
             cur_node.child.push(child_node);
 
-            check_tok(pos, &toks, &lexer::TokType::RParen)?;
+            check_tok(pos, &toks, &lexer::TokType::RParen, ctx)?;
             let pos = pos + 1;
 
-            check_tok(pos, &toks, &lexer::TokType::Semicolon)?;
+            check_tok(pos, &toks, &lexer::TokType::Semicolon, ctx)?;
             let pos = pos + 1;
 
             cur_node.type_exp = TypeExpression::new_val(BaseType::NoneExpression);
@@ -3295,67 +3939,70 @@ fn p_iteration_statement(
             // 	| FOR '(' expression_statement expression_statement expression ')' statement
             // 	| FOR '(' declaration expression_statement ')' statement
             // 	| FOR '(' declaration expression_statement expression ')' statement
-            let mut cur_node = ParseNode::new(NodeType::IterationStatement(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::IterationStatement(toks[pos].kind.clone()));
             let pos = pos + 1;
-            check_tok(pos, &toks, &lexer::TokType::LParen)?;
+            check_tok(pos, &toks, &lexer::TokType::LParen, ctx)?;
             let pos = pos + 1;
-            if let Ok((child_node, pos)) = p_expression_statement(toks, pos) {
-                cur_node.child.push(child_node);
-                let (child_node, pos) = p_expression_statement(toks, pos)?;
-                cur_node.child.push(child_node);
-                if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RParen) {
-                    // 	| FOR '(' expression_statement expression_statement ')' statement
-                    let pos = pos + 1;
-                    let (child_node, pos) = p_statement(toks, pos)?;
+            // the declaration of the loop is in its own scope
+            return ctx.in_scope(|ctx| {
+                if let Ok((child_node, pos)) = p_expression_statement(toks, pos, ctx) {
                     cur_node.child.push(child_node);
-                    cur_node.type_exp = TypeExpression::new_val(BaseType::NoneExpression);
-                    return Ok((cur_node, pos));
-                } else {
-                    // 	| FOR '(' expression_statement expression_statement expression ')' statement
-                    let (child_node, pos) = p_expression(toks, pos)?;
+                    let (child_node, pos) = p_expression_statement(toks, pos, ctx)?;
                     cur_node.child.push(child_node);
+                    if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RParen, ctx) {
+                        // 	| FOR '(' expression_statement expression_statement ')' statement
+                        let pos = pos + 1;
+                        let (child_node, pos) = p_statement(toks, pos, ctx)?;
+                        cur_node.child.push(child_node);
+                        cur_node.type_exp = TypeExpression::new_val(BaseType::NoneExpression);
+                        return Ok((cur_node, pos));
+                    } else {
+                        // 	| FOR '(' expression_statement expression_statement expression ')' statement
+                        let (child_node, pos) = p_expression(toks, pos, ctx)?;
+                        cur_node.child.push(child_node);
 
-                    check_tok(pos, &toks, &lexer::TokType::RParen)?;
-                    let pos = pos + 1;
-
-                    let (child_node, pos) = p_statement(toks, pos)?;
-                    cur_node.child.push(child_node);
+                        check_tok(pos, &toks, &lexer::TokType::RParen, ctx)?;
+                        let pos = pos + 1;
 
-                    cur_node.type_exp = TypeExpression::new_val(BaseType::NoneExpression);
-                    return Ok((cur_node, pos));
-                }
-            } else if let Ok((child_node, pos)) = p_declaration(toks, pos) {
-                cur_node.child.push(child_node);
-                let (child_node, pos) = p_expression_statement(toks, pos)?;
-                cur_node.child.push(child_node);
-                if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RParen) {
-                    // 	| FOR '(' declaration expression_statement ')' statement
-                    let pos = pos + 1;
+                        let (child_node, pos) = p_statement(toks, pos, ctx)?;
+                        cur_node.child.push(child_node);
 
-                    let (child_node, pos) = p_statement(toks, pos)?;
+                        cur_node.type_exp = TypeExpression::new_val(BaseType::NoneExpression);
+                        return Ok((cur_node, pos));
+                    }
+                } else if let Ok((child_node, pos)) = p_declaration(toks, pos, ctx) {
                     cur_node.child.push(child_node);
-                    cur_node.type_exp = TypeExpression::new_val(BaseType::NoneExpression);
-                    return Ok((cur_node, pos));
-                } else {
-                    // 	| FOR '(' declaration expression_statement expression ')' statement
-                    let (child_node, pos) = p_expression(toks, pos)?;
+                    let (child_node, pos) = p_expression_statement(toks, pos, ctx)?;
                     cur_node.child.push(child_node);
+                    if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RParen, ctx) {
+                        // 	| FOR '(' declaration expression_statement ')' statement
+                        let pos = pos + 1;
 
-                    check_tok(pos, &toks, &lexer::TokType::RParen)?;
-                    let pos = pos + 1;
+                        let (child_node, pos) = p_statement(toks, pos, ctx)?;
+                        cur_node.child.push(child_node);
+                        cur_node.type_exp = TypeExpression::new_val(BaseType::NoneExpression);
+                        return Ok((cur_node, pos));
+                    } else {
+                        // 	| FOR '(' declaration expression_statement expression ')' statement
+                        let (child_node, pos) = p_expression(toks, pos, ctx)?;
+                        cur_node.child.push(child_node);
 
-                    let (child_node, pos) = p_statement(toks, pos)?;
-                    cur_node.child.push(child_node);
+                        check_tok(pos, &toks, &lexer::TokType::RParen, ctx)?;
+                        let pos = pos + 1;
 
-                    cur_node.type_exp = TypeExpression::new_val(BaseType::NoneExpression);
-                    return Ok((cur_node, pos));
+                        let (child_node, pos) = p_statement(toks, pos, ctx)?;
+                        cur_node.child.push(child_node);
+
+                        cur_node.type_exp = TypeExpression::new_val(BaseType::NoneExpression);
+                        return Ok((cur_node, pos));
+                    }
+                } else {
+                    return Err(format!("Error parse For"));
                 }
-            } else {
-                return Err(format!("Error parse For"));
-            }
+            });
         }
         _ => {
-            return Err(error_handler("[while, do, for]", &toks[pos], pos));
+            return Err(error_handler("`while`, `do` or `for`", toks, pos, ctx));
         }
     }
 }
@@ -3367,27 +4014,31 @@ fn p_iteration_statement(
 // 	| RETURN ';'
 // 	| RETURN expression ';'
 // 	;
-fn p_jump_statement(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_jump_statement(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
 
-    match &toks[pos] {
+    match &toks[pos].kind {
         lexer::TokType::GOTO => {
             let pos = pos + 1;
             check_pos(pos, toks.len())?;
-            match &toks[pos] {
+            match &toks[pos].kind {
                 lexer::TokType::IDENTIFIER(var) => {
                     let mut cur_node = ParseNode::new(NodeType::JumpStatement(
                         "goto".to_string(),
-                        Some(var.to_string()),
+                        Some(ctx.interner.name(*var).to_string()),
                     ));
                     let pos = pos + 1;
-                    check_tok(pos, &toks, &lexer::TokType::Semicolon)?;
+                    check_tok(pos, &toks, &lexer::TokType::Semicolon, ctx)?;
                     let pos = pos + 1;
                     cur_node.type_exp = TypeExpression::new_val(BaseType::NoneExpression);
                     return Ok((cur_node, pos));
                 }
                 _ => {
-                    return Err(error_handler("identifier for goto ", &toks[pos], pos));
+                    return Err(error_handler("an identifier", toks, pos, ctx));
                 }
             }
         }
@@ -3395,7 +4046,7 @@ fn p_jump_statement(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
             let mut cur_node =
                 ParseNode::new(NodeType::JumpStatement("continue".to_string(), None));
             let pos = pos + 1;
-            check_tok(pos, &toks, &lexer::TokType::Semicolon)?;
+            check_tok(pos, &toks, &lexer::TokType::Semicolon, ctx)?;
             let pos = pos + 1;
             cur_node.type_exp = TypeExpression::new_val(BaseType::NoneExpression);
             return Ok((cur_node, pos));
@@ -3403,14 +4054,14 @@ fn p_jump_statement(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
         lexer::TokType::BREAK => {
             let mut cur_node = ParseNode::new(NodeType::JumpStatement("break".to_string(), None));
             let pos = pos + 1;
-            check_tok(pos, &toks, &lexer::TokType::Semicolon)?;
+            check_tok(pos, &toks, &lexer::TokType::Semicolon, ctx)?;
             let pos = pos + 1;
             cur_node.type_exp = TypeExpression::new_val(BaseType::NoneExpression);
             return Ok((cur_node, pos));
         }
         lexer::TokType::RETURN => {
             let pos = pos + 1;
-            if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Semicolon) {
+            if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Semicolon, ctx) {
                 // return val, so the type for this statement should be type(val)
                 let mut cur_node =
                     ParseNode::new(NodeType::JumpStatement("return".to_string(), None));
@@ -3420,19 +4071,16 @@ fn p_jump_statement(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
             } else {
                 let mut cur_node =
                     ParseNode::new(NodeType::JumpStatement("return".to_string(), None));
-                let (child_node, pos) = p_expression(toks, pos)?;
+                let (child_node, pos) = p_expression(toks, pos, ctx)?;
                 cur_node.type_exp = child_node.type_exp.clone();
                 cur_node.child.push(child_node);
+                check_tok(pos, toks, &lexer::TokType::Semicolon, ctx)?;
                 let pos = pos + 1;
                 return Ok((cur_node, pos));
             }
         }
         _ => {
-            return Err(error_handler(
-                "[goto, continue, break, return]",
-                &toks[pos],
-                pos,
-            ));
+            return Err(error_handler("`goto`, `continue`, `break` or `return`", toks, pos, ctx));
         }
     }
 }
@@ -3440,78 +4088,119 @@ fn p_jump_statement(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
 // external_declaration
 // 	: function_definition
 // 	| declaration
+// 	| pragma
 // 	;
 fn p_external_declaration(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node = ParseNode::new(NodeType::ExternalDeclaration);
-    if let Ok((child_node, pos)) = p_function_definition(toks, pos) {
+    if let Ok((child_node, pos)) = p_pragma(toks, pos, ctx) {
+        cur_node.type_exp = child_node.type_exp.clone();
+        cur_node.child.push(child_node);
+        return Ok((cur_node, pos));
+    } else if let Ok((child_node, pos)) = p_function_definition(toks, pos, ctx) {
         cur_node.type_exp = child_node.type_exp.clone();
         cur_node.child.push(child_node);
         return Ok((cur_node, pos));
     } else {
-        let (child_node, pos) = p_declaration(toks, pos)?;
+        let (child_node, pos) = p_declaration(toks, pos, ctx)?;
         cur_node.type_exp = child_node.type_exp.clone();
         cur_node.child.push(child_node);
         return Ok((cur_node, pos));
     }
 }
 
+// pragma
+// 	: PRAGMA    /* #pragma line left by the preprocessor */
+// 	;
+fn p_pragma(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks.len())?;
+    match &toks[pos].kind {
+        lexer::TokType::Pragma(s) => {
+            let mut cur_node = ParseNode::new(NodeType::Pragma(s.to_string()));
+            cur_node.type_exp = TypeExpression::new_val(BaseType::NoneExpression);
+            Ok((cur_node, pos + 1))
+        }
+        _ => Err(error_handler("`#pragma`", toks, pos, ctx)),
+    }
+}
+
 // function_definition
 // 	: declaration_specifiers declarator declaration_list compound_statement
 // 	| declaration_specifiers declarator compound_statement
 // 	;
 fn p_function_definition(
-    toks: &[lexer::TokType],
+    toks: &[lexer::Token],
     pos: usize,
+    ctx: &mut Parser,
 ) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node = ParseNode::new(NodeType::FunctionDefinition);
     cur_node.type_exp = TypeExpression::new_val(BaseType::Function);
 
-    let (child_node, pos) = p_declaration_specifiers(toks, pos)?;
-    cur_node.type_exp.child.push(child_node.type_exp.clone());
-    cur_node.child.push(child_node);
-
-    let (child_node, pos) = p_declarator(toks, pos)?;
+    let (child_node, pos) = p_declaration_specifiers(toks, pos, ctx)?;
     cur_node.type_exp.child.push(child_node.type_exp.clone());
     cur_node.child.push(child_node);
 
-    if let Ok((child_node, pos)) = p_declaration_list(toks, pos) {
+    let (child_node, pos) = p_declarator(toks, pos, ctx)?;
+    let name = declarator_name(&child_node).map(|name| name.to_string());
+    // the parameters are in the scope of the body
+    let pos = ctx.in_scope(|ctx| -> Result<usize, String> {
+        for param in parameter_names(&child_node) {
+            ctx.declare(&param, false);
+        }
         cur_node.type_exp.child.push(child_node.type_exp.clone());
         cur_node.child.push(child_node);
 
-        let (child_node, pos) = p_compound_statement(toks, pos)?;
+        if let Ok((child_node, pos)) = p_declaration_list(toks, pos, ctx) {
+            cur_node.type_exp.child.push(child_node.type_exp.clone());
+            cur_node.child.push(child_node);
 
-        cur_node.type_exp.child.push(child_node.type_exp.clone());
-        cur_node.child.push(child_node);
-        return Ok((cur_node, pos));
-    } else {
-        let (child_node, pos) = p_compound_statement(toks, pos)?;
+            let (child_node, pos) = p_compound_statement(toks, pos, ctx)?;
 
-        cur_node.type_exp.child.push(child_node.type_exp.clone());
-        cur_node.child.push(child_node);
-        return Ok((cur_node, pos));
+            cur_node.type_exp.child.push(child_node.type_exp.clone());
+            cur_node.child.push(child_node);
+            return Ok(pos);
+        } else {
+            let (child_node, pos) = p_compound_statement(toks, pos, ctx)?;
+
+            cur_node.type_exp.child.push(child_node.type_exp.clone());
+            cur_node.child.push(child_node);
+            return Ok(pos);
+        }
+    })?;
+    if let Some(name) = name {
+        ctx.declare(&name, false);
     }
+    return Ok((cur_node, pos));
 }
 // declaration_list
 // 	: declaration
 // 	| declaration_list declaration
 // 	;
 //  -> declaration { declaration }
-fn p_declaration_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_declaration_list(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     check_pos(pos, toks.len())?;
     let mut cur_node: ParseNode = ParseNode::new(NodeType::DeclarationList);
-    let (child_node, pos) = p_declaration(toks, pos)?;
+    let (child_node, pos) = p_declaration(toks, pos, ctx)?;
     let pre_type = child_node.type_exp.clone();
     let mut inc = 0;
 
     cur_node.type_exp.child.push(child_node.type_exp.clone());
     cur_node.child.push(child_node);
     let mut pos: usize = pos;
-    while let Ok((child_node, tmp_pos)) = p_declaration(toks, pos) {
+    while let Ok((child_node, tmp_pos)) = p_declaration(toks, pos, ctx) {
         inc += 1;
         cur_node.type_exp.child.push(child_node.type_exp.clone());
         cur_node.child.push(child_node);
@@ -3530,32 +4219,66 @@ fn p_declaration_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
 // 	| translation_unit external_declaration
 // 	;
 //  -> external_declaration { external_declaration }
-fn p_translation_unit(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+// the parser goes on after a syntax error, from the end of the declaration
+fn p_translation_unit(
+    toks: &[lexer::Token],
+    pos: usize,
+    ctx: &mut Parser,
+) -> Result<(ParseNode, usize), String> {
     let mut cur_node: ParseNode = ParseNode::new(NodeType::TranslationUnit);
     let mut pos: usize = pos;
     loop {
+        ctx.clear_failure();
+        // what is parsed ahead is used right away
+        ctx.memo.clear();
+        match p_external_declaration(toks, pos, ctx) {
+            Ok((child_node, tmp_pos)) => {
+                cur_node.type_exp.child.push(child_node.type_exp.clone());
+                cur_node.child.push(child_node);
+                pos = tmp_pos;
+            }
+            Err(_) => pos = ctx.recover(toks, pos, "a declaration"),
+        }
         if pos >= toks.len() {
             break;
         }
-        let (child_node, tmp_pos) = p_external_declaration(toks, pos)?;
-        cur_node.type_exp.child.push(child_node.type_exp.clone());
-        cur_node.child.push(child_node);
-        pos = tmp_pos;
     }
     return Ok((cur_node, pos));
 }
 
-pub fn parser_driver(toks: &[lexer::TokType], c_src_name: &str) -> Result<ParseNode, String> {
-    let (cur_node, pos) = p_translation_unit(&toks, 0)?;
-    if pos == toks.len() {
+// The parse tree and all the syntax errors of the tokens lexed from `source`, whose
+// identifiers are in `interner`.
+pub fn parse_with_errors(
+    source: &str,
+    toks: &[lexer::Token],
+    interner: &lexer::Interner,
+) -> (ParseNode, Vec<ParseError>) {
+    let mut ctx = Parser::new(source, interner);
+    let (cur_node, _) = p_translation_unit(toks, 0, &mut ctx).unwrap_or_else(|_| {
+        (ParseNode::new(NodeType::TranslationUnit), toks.len())
+    });
+    let mut errors = ctx.errors;
+    errors.sort_by_key(|e| e.span.offset);
+    return (cur_node, errors);
+}
+
+// all the syntax errors as one message, the compiler itself reports them one by one
+#[allow(dead_code)]
+pub fn parser_driver(
+    source: &str,
+    toks: &[lexer::Token],
+    interner: &lexer::Interner,
+    c_src_name: &str,
+) -> Result<ParseNode, String> {
+    let (cur_node, errors) = parse_with_errors(source, toks, interner);
+    if errors.is_empty() {
         return Ok(cur_node);
-    } else {
-        Err(format!(
-            "Parser drive fails to parse the file {}",
-            c_src_name
-        ))
     }
+    let messages: Vec<String> = errors
+        .iter()
+        .map(|e| format!("{}:{}:{}: {}", c_src_name, e.span.line, e.span.col, e.message))
+        .collect();
+    return Err(messages.join("\n"));
 }
 
 pub fn parser_pretty_printer(tree: &ParseNode, depth: usize) -> String {
@@ -15,10 +15,12 @@
 // symtable.rs: symbol table for identifiers.
 // ------------------------------------------------------------------------
 
-const _X86_64_CHAR_BYTES: u64 = 1;
-const _X86_64_SHORT_BYTES: u64 = 2;
-const _X86_64_INT_BYTES: u64 = 4;
-const _X86_64_LONG_BYTES: u64 = 8;
+pub const X86_64_CHAR_BYTES: u64 = 1;
+pub const X86_64_SHORT_BYTES: u64 = 2;
+pub const X86_64_INT_BYTES: u64 = 4;
+pub const X86_64_LONG_BYTES: u64 = 8;
+pub const X86_64_LONG_LONG_BYTES: u64 = 8;
+pub const X86_64_POINTER_BYTES: u64 = 8;
 
 const _NUM_REG: usize = 16;
 
@@ -77,6 +79,7 @@ pub enum BaseType {
     Struct,
     Union,
     Identifier(String),
+    TypedefName(String),
     VaList,
 
     Noreturn,
@@ -87,6 +90,7 @@ pub enum BaseType {
     Volatile,
     Atomic,
 
+    Typedef,
     Static,
     ThreadLocal,
     Extern,
@@ -181,8 +185,8 @@ impl SymbolAttr {
     pub fn new() -> SymbolAttr {
         SymbolAttr {
             volatile: false,
-            size: _X86_64_INT_BYTES,
-            boundary: _X86_64_INT_BYTES,
+            size: X86_64_INT_BYTES,
+            boundary: X86_64_INT_BYTES,
             base_type: TypeExpression::new_val(BaseType::Int),
             n_elements: 1,
             register: false,
@@ -25,7 +25,7 @@ test_fun() {
 }
 
 crust_compile() {
-    echo "crust compile $1.c -> $2.s" && ./target/debug/crust -o $2.s $1.c
+    echo "crust compile $1.c -> $2.s" && ./target/debug/crust --emit=asm -o $2.s $1.c
 }
 
 gcc_compile() {
@@ -0,0 +1,5 @@
+#error this file should not compile
+
+int main(void) {
+    return 0;
+}
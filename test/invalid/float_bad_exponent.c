@@ -0,0 +1,4 @@
+int main() {
+    double d = 1e;
+    return 0;
+}
@@ -0,0 +1,4 @@
+int main() {
+    double d = 0x1.8;
+    return 0;
+}
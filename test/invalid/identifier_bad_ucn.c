@@ -0,0 +1,5 @@
+int main() {
+    int a\u0301 = 0;
+    int \u0301b = a\u0301;
+    return 0;
+}
@@ -0,0 +1,5 @@
+#if defined(
+#endif
+int main(void) {
+    return 0;
+}
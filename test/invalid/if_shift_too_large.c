@@ -0,0 +1,5 @@
+#if 1 >> 64
+#endif
+int main(void) {
+    return 0;
+}
@@ -0,0 +1,6 @@
+#ifdef
+#endif
+
+int main(void) {
+    return 0;
+}
@@ -0,0 +1,6 @@
+#ifndef
+#endif
+
+int main(void) {
+    return 0;
+}
@@ -0,0 +1,6 @@
+#define HEADER guard.h
+#include HEADER
+
+int main(void) {
+    return 0;
+}
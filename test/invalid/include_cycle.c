@@ -0,0 +1,5 @@
+#include "include_cycle.h"
+
+int main(void) {
+    return 0;
+}
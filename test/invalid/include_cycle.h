@@ -0,0 +1,2 @@
+// includes itself with no include guard, the include chain is reported
+#include "include_cycle.h"
@@ -0,0 +1,5 @@
+#include "include_self_conditional.h"
+
+int main(void) {
+    return 0;
+}
@@ -0,0 +1,5 @@
+// includes itself in a conditional which is not an include guard, the cycle is
+// reported when it is entered again
+#if 1
+#include "include_self_conditional.h"
+#endif
@@ -0,0 +1,6 @@
+#define FOO 1
+#defin BAR 2
+
+int main(void) {
+    return FOO;
+}
@@ -0,0 +1,5 @@
+#line 0
+
+int main(void) {
+    return 0;
+}
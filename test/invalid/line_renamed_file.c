@@ -0,0 +1,3 @@
+// the snippet of the error is read from this file, not from the name of #line
+#line 100 "renamed.c"
+int c = 1 int d;
@@ -0,0 +1,4 @@
+#define CONCAT(a, b) a ## b
+int main(void) {
+    return 1 CONCAT(+, /) 1;
+}
@@ -0,0 +1,4 @@
+#define STR(x) #y
+int main(void) {
+    return 0;
+}
@@ -0,0 +1,4 @@
+#define TWO(a, b) a + b
+int main(void) {
+    return TWO(1);
+}
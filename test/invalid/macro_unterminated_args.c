@@ -0,0 +1,4 @@
+#define ID(x) x
+int main(void) {
+    return ID(1;
+}
@@ -0,0 +1,4 @@
+#define F(x) __VA_ARGS__
+int main(void) {
+    return 0;
+}
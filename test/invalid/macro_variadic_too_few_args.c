@@ -0,0 +1,5 @@
+#define LOG(fmt, x, ...) printf(fmt, x, __VA_ARGS__)
+int main(void) {
+    LOG("%d");
+    return 0;
+}
@@ -0,0 +1,6 @@
+int main() {
+    {
+        return 0
+    }
+    return 1;
+}
@@ -0,0 +1,4 @@
+int main() {
+    int *s = L"wide" u"utf-16";
+    return 0;
+}
@@ -0,0 +1,2 @@
+int main() {
+    int a = 1
@@ -0,0 +1,24 @@
+// 7 syntax errors, each is reported once
+struct S { int x y; };
+
+int a[] = { 1, 2 3 };
+
+enum { X = 3 Y };
+
+int f(int a) {
+    int b = a * ;
+    if (b > ) {
+        b = 1;
+    }
+    return b;
+}
+
+int g(void) {
+    int c = 2
+    return c;
+}
+
+int main() {
+    int d = (f(1) + ;
+    return d + g();
+}
@@ -0,0 +1,7 @@
+int main() {
+    {
+        typedef long i64;
+    }
+    i64 x = 1;
+    return x;
+}
@@ -0,0 +1,6 @@
+#ifdef FOO
+int main(void) {
+    return 0;
+}
+#endif
+#endif
@@ -0,0 +1,6 @@
+#define FOO 1
+#undef
+
+int main(void) {
+    return FOO;
+}
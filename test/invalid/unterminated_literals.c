@@ -0,0 +1,5 @@
+int main() {
+    char *s = "unterminated;
+    char c = 'x;
+    return 0;
+}
@@ -0,0 +1,46 @@
+#include <limits.h>
+#include <float.h>
+#include <stdbool.h>
+#include <stdalign.h>
+#include <stdnoreturn.h>
+#include <iso646.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdarg.h>
+
+#if CHAR_BIT != 8 || INT_MAX != 2147483647 || UINT_MAX != 4294967295
+#error wrong limits of int
+#endif
+
+#if LONG_MAX != 9223372036854775807 || LONG_MIN != -9223372036854775807 - 1
+#error wrong limits of long
+#endif
+
+#if UCHAR_MAX != 255 || CHAR_MIN != -128 || USHRT_MAX != 65535
+#error wrong limits of char and short
+#endif
+
+#if INT64_MAX != LONG_MAX || INT8_MIN != -128 || UINT32_MAX != UINT_MAX || SIZE_MAX != ULONG_MAX
+#error wrong limits of stdint.h
+#endif
+
+#if INT_FAST16_MAX != INTPTR_MAX || UINT64_C(1) != 1 || WCHAR_MIN != INT_MIN
+#error wrong limits of the fast, pointer or wchar_t types
+#endif
+
+#if FLT_RADIX != 2 || DBL_MANT_DIG != 53 || !__bool_true_false_are_defined
+#error wrong float.h or stdbool.h
+#endif
+
+noreturn void stop(void);
+
+int32_t counter;
+va_list args;
+
+int main(void) {
+    bool ok = true;
+    alignas(8) int aligned = INT_MIN;
+    size_t size = sizeof(max_align_t) + offsetof(max_align_t, __max_align_ld);
+    uint8_t byte = (uint8_t)size;
+    return not ok or aligned == SHRT_MIN or byte == 0;
+}
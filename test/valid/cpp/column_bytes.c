@@ -0,0 +1,7 @@
+// the columns count bytes, é takes two of them
+char *s = "é"; int x;
+/* ü */ int y;
+
+int main(void) {
+    return 0;
+}
@@ -0,0 +1,35 @@
+#include "guard.h"
+#include "guard.h"
+
+#define FEATURE 1
+
+#ifdef FEATURE
+int feature = 1;
+#else
+this line should never be compiled
+#endif
+
+#ifndef MISSING
+int missing = 0;
+#elif 1
+this line should never be compiled
+#endif
+
+#if 0
+#include "no_such_header.h"
+#  if 1
+this line should never be compiled
+#  else
+this line should never be compiled
+#  endif
+#elif FEATURE
+int elif_taken = 1;
+#else
+this line should never be compiled
+#endif
+
+int main(void) {
+#ifdef GUARD_H
+    return guarded();
+#endif
+}
@@ -0,0 +1,9 @@
+%:define STR(x) %:x
+%:define CAT(a, b) a %:%: b
+%:if 1
+int CAT(ma, in)(void) <%
+    int a<:3:> = <%1, 2, 3%>;
+    char *s = STR(digraphs);
+    return a<:0:> < a<:1:> ? a<:2:> : 0;
+%>
+%:endif
@@ -0,0 +1,35 @@
+#include "once.h"
+#include "once.h"
+
+#pragma pack(push, 4)
+
+#define VALUE 1
+#undef VALUE
+#ifdef VALUE
+this line should never be compiled
+#endif
+#undef NEVER_DEFINED
+
+#define VALUE 2
+#if VALUE != 2
+#error VALUE should be 2 after the redefinition
+#endif
+
+#
+#warning this is only a warning
+
+#define LINE 100
+#line LINE "renamed.c"
+#line 20
+
+#if 0
+#error skipped groups are not checked
+#unknown_directive
+#endif
+
+int main(void) {
+#pragma unroll
+    for (int i = 0; i < VALUE; i++)
+        ;
+    return once();
+}
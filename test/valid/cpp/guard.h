@@ -0,0 +1,8 @@
+#ifndef GUARD_H
+#define GUARD_H
+
+int guarded(void) {
+    return 1;
+}
+
+#endif
@@ -0,0 +1,33 @@
+#define ONE 1
+#define TWO (ONE + ONE)
+#define EMPTY
+
+#if !defined ONE || !defined(TWO) || defined(THREE) || !defined EMPTY
+this line should never be compiled
+#endif
+
+#if TWO * 3 != 6 || -TWO / 2 != -1 || 7 % TWO != 1 || (1 << 4 | 1) != 17
+this line should never be compiled
+#endif
+
+#if -1 < 0u || !(-1 < 0) || 0xffffffffffffffff != -1 || 0x10 != 16 || 010 != 8
+this line should never be compiled
+#endif
+
+#if UNDEFINED_NAME || 201112L < 199901L || 'a' != 97 || '\n' != 10
+this line should never be compiled
+#endif
+
+#if 0 && 1 / 0
+this line should never be compiled
+#elif (1 ? 2 : 1 / 0) != 2 || (0 ? 1 / 0 : 3) != 3 || (1 ? -1 : 0u) < 0
+this line should never be compiled
+#elif ~0 != -1 || !!5 != 1 || (3 > 2) + (2 >= 2) + (1 <= 0) != 2
+this line should never be compiled
+#elif (6 & 3) != 2 || (6 ^ 3) != 5 || (6 | 3) != 7 || (-16 >> 2) != -4
+this line should never be compiled
+#else
+int main(void) {
+    return 0;
+}
+#endif
@@ -0,0 +1,13 @@
+#define HEADER "guard.h"
+#include HEADER
+
+#define STR(x) #x
+#define XSTR(x) STR(x)
+#define NAME once.h
+#include XSTR(NAME)
+
+#include "header/../guard.h"
+
+int main(void) {
+    return guarded() + once();
+}
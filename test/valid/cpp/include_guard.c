@@ -0,0 +1,8 @@
+#include "include_guard.h"
+#include "include_guard.h"
+#include "guard.h"
+#include "guard.h"
+
+int main(void) {
+    return include_guard() + guarded();
+}
@@ -0,0 +1,12 @@
+/* the whole header is inside the guard, it is skipped the second time */
+#if !defined(INCLUDE_GUARD_H)
+#define INCLUDE_GUARD_H
+
+// includes itself, the guard stops the recursion
+#include "include_guard.h"
+
+int include_guard(void) {
+    return 2;
+}
+
+#endif /* INCLUDE_GUARD_H */
@@ -0,0 +1,6 @@
+#include_next <stddef.h>
+
+int main(void) {
+    size_t zero = 0;
+    return zero;
+}
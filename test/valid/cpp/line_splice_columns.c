@@ -0,0 +1,11 @@
+// the tokens after the spliced lines and the comments keep their columns
+#define ADD(a, b) ((a) + \
+                   (b))
+int main() {
+    int x = 1; /* a comment
+    over two lines */ int y = ADD(x,
+                                  2);
+    int z = x + \
+        y;
+    return ADD(/* in an argument */ z, 1) ??' 0;
+}
@@ -0,0 +1,26 @@
+int f(int x) {
+    return x;
+}
+
+#define ADD(a, b) ((a) + (b))
+#define FIRST(x, y) x
+#define CALL(fn, arg) fn(arg)
+#define ID(x) x
+#define f(x) f(x + 1)
+#define g f
+#define value value + 1
+#define len_of(len) len + length
+
+int main(void) {
+    int length = 2;
+    int a = ADD(ADD(1, 2), (3, 4));
+    int b = FIRST((1, 2), 3);
+    int c = ADD(
+        1,
+        2
+    );
+    int d = CALL(ID, (5));
+    int e = g(2) + f(f(3));
+    int h = len_of(1);
+    return a + b + c + d + e + h;
+}
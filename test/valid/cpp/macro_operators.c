@@ -0,0 +1,22 @@
+#define STR(x) #x
+#define XSTR(x) STR(x)
+#define CONCAT(a, b) a ## b
+#define CONCAT3(a, b, c) a ## b ## c
+#define ALT_STR(x) %:x
+#define ALT_CONCAT(a, b) a %:%: b
+#define VALUE 42
+
+int prefix_name = 1;
+
+int main(void) {
+    char *s1 = STR(  hello    world  );
+    char *s3 = XSTR(VALUE);
+    char *s4 = STR();
+    char *s5 = ALT_STR(digraph);
+    int CONCAT(my_, var) = CONCAT(prefix_, name);
+    int CONCAT(, empty) = CONCAT(2, );
+    int CONCAT3(a, , b) = CONCAT3(, , 3);
+    int x = CONCAT(1, 2) + ALT_CONCAT(3, 4);
+    x CONCAT(+, =) 1;
+    return my_var + empty + ab + x;
+}
@@ -0,0 +1,25 @@
+#define LOG(fmt, ...) printf(fmt, __VA_ARGS__)
+#define GNU_LOG(fmt, ...) printf(fmt, ## __VA_ARGS__)
+#define OPT_LOG(fmt, ...) printf(fmt __VA_OPT__(,) __VA_ARGS__)
+#define NAMED_LOG(fmt, args...) printf(fmt, ## args)
+#define CALL(f, ...) f(__VA_ARGS__)
+#define STR(...) #__VA_ARGS__
+#define FIRST(first, ...) first
+#define CAT_OPT(a, ...) a ## __VA_OPT__(_ ## __VA_ARGS__)
+
+int main(void) {
+    int x = 1;
+    int x_y = 2;
+    LOG("%d %d\n", 1, 2);
+    LOG("%d\n", (1, 2));
+    GNU_LOG("no arguments\n");
+    GNU_LOG("%d\n", 3);
+    OPT_LOG("no arguments\n");
+    OPT_LOG("%d %d\n", 4, 5);
+    NAMED_LOG("no arguments\n");
+    NAMED_LOG("%d\n", 6);
+    char *s = STR(a, b,  c);
+    int y = FIRST(7) + FIRST(8, 9, 10);
+    int z = CAT_OPT(x) + CAT_OPT(x, y);
+    return CALL(sum, 1, 2, 3) + CALL(zero) + y + z;
+}
@@ -0,0 +1,5 @@
+#pragma once
+
+int once(void) {
+    return 1;
+}
@@ -0,0 +1,8 @@
+// the pragma which is passed to the parser is at its `#`, not after the last token
+#define ONE 1
+int x = ONE;
+  #pragma pack(1)
+
+int main(void) {
+    return 0;
+}
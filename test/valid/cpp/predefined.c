@@ -0,0 +1,39 @@
+#if !defined(__STDC__) || __STDC_VERSION__ < 201112L
+#error a C11 compiler is expected
+#endif
+
+#if !defined(__x86_64__) || !defined(__linux__) || __SIZEOF_POINTER__ != 8
+#error the target should be x86_64 linux
+#endif
+
+#if __SIZEOF_INT__ != 4 || __SIZEOF_LONG__ != 8 || __CHAR_BIT__ != 8
+#error wrong sizes of the integer types
+#endif
+
+#if __LINE__ != 13
+#error __LINE__ should be the line number of the current line
+#endif
+
+#line 100 "renamed.c"
+#if __LINE__ != 100
+#error __LINE__ should follow #line
+#endif
+
+#if __COUNTER__ != 0 || __COUNTER__ != 1
+#error __COUNTER__ should count from 0
+#endif
+
+#define LINE_OF(x) __LINE__
+#define STR(x) #x
+#define XSTR(x) STR(x)
+
+char *file = __FILE__;
+char *date = __DATE__;
+char *time = __TIME__;
+char *line = XSTR(__LINE__);
+
+int main(void) {
+    int counter = __COUNTER__;
+    return LINE_OF(
+        0) + counter;
+}
@@ -0,0 +1,6 @@
+int main() {
+    int a = 1;
+    int b = ((((((((((((((((((((((((((((((((((((((((a))))))))))))))))))))))))))))))))))))))));
+    a = (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + (a + b))))))))))))))))))))))))))))))))))))))));
+    return -(-(-(-(-(-(-(-(-(-(-(-(-(-(-(-(-(-(-(-(a)))))))))))))))))))) + b;
+}
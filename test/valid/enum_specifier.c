@@ -0,0 +1,8 @@
+enum Color { RED, GREEN = 5, BLUE, };
+enum Color c;
+enum { ONE = 1, TWO } n;
+
+int main() {
+    enum Color d;
+    return BLUE - GREEN - ONE;
+}
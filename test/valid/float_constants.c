@@ -0,0 +1,7 @@
+int main() {
+    double dec = 1.5 + .5 + 1. + 1e10 + 2.5E-3 + 6.02e+23;
+    double hex = 0x1.8p3 + 0x.8p1 + 0X1P-2 + 0xAp0;
+    float f = 1.5f + 2.F + 0x1p4f;
+    long double ld = 2.5L + 1e-5l + 0x1.8P1L;
+    return dec + hex + f + ld;
+}
@@ -0,0 +1,6 @@
+int main() {
+    double large = 0x1p999999999 + 0x1p99999999999999999999;
+    double small = 0x1p-999999999 + 0x1p-99999999999999999999;
+    float f = 0x1p128f + 0x1p-150f;
+    return small;
+}
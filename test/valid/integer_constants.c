@@ -0,0 +1,8 @@
+int main() {
+    long dec = 2147483647 + 2147483648 + 9223372036854775807;
+    long oct = 0 + 0755 + 0u;
+    long hex = 0x1F + 0XfF + 0xFFFFFFFF + 0x100000000 + 0xFFFFFFFFFFFFFFFF;
+    long bin = 0b101 + 0B11;
+    long suffixed = 10u + 10U + 10l + 10L + 10ul + 10LU + 10ll + 10LL + 1ULL + 1llu;
+    return dec + oct + hex + bin + suffixed;
+}
@@ -0,0 +1,11 @@
+int main() {
+    char *escapes = "a\"b\'c\?\\\a\b\f\n\r\t\v\0\101\x41\u00e9\U0001F600";
+    char *concat = "hello, " "world" "\x41" "B";
+    char *utf8 = u8"caf\u00e9" "!";
+    int *wide = L"wide" "string";
+    unsigned short *utf16 = u"utf-16 \U0001F600";
+    unsigned int *utf32 = U"utf-32" U"\U0001F600";
+    int chars = 'a' + '\'' + '\n' + '\0' + '\101' + '\x41' + '\377' + 'ab';
+    int prefixed = L'a' + u'\u00e9' + U'\U0001F600';
+    return chars + prefixed;
+}
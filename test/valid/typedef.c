@@ -0,0 +1,53 @@
+typedef int myint;
+typedef unsigned long size, *size_ptr;
+typedef struct point { int x; int y; } point_t;
+typedef char T;
+
+myint x;
+const myint cx = 1;
+size_ptr sp;
+point_t origin;
+
+static int add(myint a, myint b) { return a + b; }
+
+myint apply(myint n, T T) {
+    myint r = n + T;
+    return r + (myint)sizeof(T);
+}
+
+int shadow(void) {
+    int T;
+    T = 1;
+    {
+        long T = 2;
+        T = T * 2;
+    }
+    for (T = 0; T < 3; T++) {
+    }
+    return T;
+}
+
+int after(void) {
+    T c = 'a';
+    for (myint i = 0; i < 3; i++) {
+        typedef long i64;
+        i64 v = (i64)i;
+        c = c + (T)v;
+    }
+    {
+        int myint = 3;
+        myint = myint + 1;
+    }
+    myint m = (myint)c;
+    return m + sizeof(myint) + (int)sizeof(point_t);
+}
+
+int knr(a, b) myint a; T b;
+{
+    return a + b;
+}
+
+int main(void) {
+    T c = 2;
+    return add(c, x) + apply(1, c) + shadow() + after() + knr(1, c);
+}
@@ -0,0 +1,12 @@
+#define CAFÉ 1
+
+int café(int x) {
+    return x;
+}
+
+int main() {
+    int λ = CAFÉ;
+    int λ2 = café(λ) + λ;
+    int 変数 = λ2 + caf\u00e9(1);
+    return 変数;
+}
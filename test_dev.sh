@@ -2,7 +2,7 @@
 # build the project
 
 ## PS. Now this test do not generate any file.
-## keep the -o file_name just for satisfy the command line option requirement
+## -fsyntax-only stops after the checks, there is no code generator now
 
 cargo build
 
@@ -13,7 +13,7 @@ inc=0
 
 crust_compile() {
     echo "TEST $inc: parse [$1]"
-    echo "crust parse $1.c -> ast" && ./target/debug/crust $1.c 
+    echo "crust parse $1.c -> ast" && ./target/debug/crust -fsyntax-only $1.c
 }
 
 echo -e "[${BLUE}test begins${NC}]"
@@ -92,4 +92,30 @@ do
     fi
 done
 
+# the positions of the tokens, against the expected --emit=tokens output
+for f in test/valid/cpp/*.tokens
+do
+    inc=$(($inc+1))
+    file=${f%.*}
+    echo "TEST $inc: tokens [$file]"
+    ./target/debug/crust --emit=tokens $file.c | diff $f -
+    if [ "$?" -ne 0 ]; then
+        echo -e "[${RED}Error${NC}]"
+        exit 1
+    else
+        echo -e "[${BLUE}tokens ok${NC}]"
+    fi
+done
+
+# the value of an option is kept as it is, even if it looks like an option
+inc=$(($inc+1))
+echo "TEST $inc: option values"
+./target/debug/crust -M -MT -MP -I -isystem test/valid/cpp/pragma_location.c | grep -q '^-MP: '
+if [ "$?" -ne 0 ]; then
+    echo -e "[${RED}Error${NC}]"
+    exit 1
+else
+    echo -e "[${BLUE}options ok${NC}]"
+fi
+
 echo -e "Now the parser can parse them all"